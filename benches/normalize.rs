@@ -0,0 +1,61 @@
+//! Compares parsing a PNG fragmented into thousands of tiny IDAT chunks with parsing it after `pngme normalize`,
+//! and parsing it with every chunk owning a copy of its data with parsing it in an arena holding the whole file
+//!
+//! Run with `cargo bench`
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::sync::Arc;
+
+use pngme::chunk::ChunkTypes;
+use pngme::png::Png;
+
+/// Number of IDAT chunks of the fragmented file, one byte each
+const FRAGMENTS: usize = 100_000;
+
+/// Chunk as bytes: length, type, data and CRC
+fn chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let checked: Vec<u8> = chunk_type.iter().chain(data.iter()).copied().collect();
+
+    (data.len() as u32)
+        .to_be_bytes()
+        .iter()
+        .chain(checked.iter())
+        .chain(crc::crc32::checksum_ieee(&checked).to_be_bytes().iter())
+        .copied()
+        .collect()
+}
+
+/// PNG whose image data is split into one byte IDAT chunks
+fn fragmented_png() -> Vec<u8> {
+    let header = [0, 0, 1, 0, 0, 0, 1, 0, 8, 6, 0, 0, 0];
+
+    let mut bytes = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    bytes.extend(chunk(b"IHDR", &header));
+
+    for index in 0..FRAGMENTS {
+        bytes.extend(chunk(b"IDAT", &[index as u8]));
+    }
+
+    bytes.extend(chunk(b"IEND", &[]));
+    bytes
+}
+
+fn parse(c: &mut Criterion) {
+    let fragmented = fragmented_png();
+
+    let mut normalized = Png::from_bytes(&fragmented, ChunkTypes::Valid).unwrap();
+    normalized.coalesce_idat(8192).unwrap();
+    let normalized = normalized.as_bytes();
+
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(20);
+    group.bench_function("fragmented", |b| b.iter(|| Png::from_bytes(black_box(&fragmented), ChunkTypes::Valid)));
+    group.bench_function("fragmented in arena", |b| {
+        b.iter(|| Png::from_arena(Arc::from(black_box(&fragmented[..])), ChunkTypes::Valid))
+    });
+    group.bench_function("normalized", |b| b.iter(|| Png::from_bytes(black_box(&normalized), ChunkTypes::Valid)));
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+
+/// Make sure a file can be overwritten, before any work is done on it
+///
+/// Write-protected files are refused even when the current user could overwrite them anyway,
+/// and opening the file for writing catches read-only filesystems and missing permissions
+pub fn check_writable(path: &Path) -> Result<()> {
+    if fs::metadata(path)?.permissions().readonly() {
+        return Err(AccessError::ReadOnly(path.to_path_buf()).into());
+    }
+
+    // The file is opened without truncating it, so nothing is written
+    match OpenOptions::new().write(true).open(path) {
+        Ok(_) => Ok(()),
+        Err(error)
+            if error.kind() == io::ErrorKind::PermissionDenied
+                || error.kind() == io::ErrorKind::ReadOnlyFilesystem =>
+        {
+            Err(AccessError::ReadOnly(path.to_path_buf()).into())
+        },
+        Err(error) => Err(error.into())
+    }
+}
+
+/// File access errors
+#[derive(Debug)]
+pub enum AccessError {
+    /// The file, or the filesystem holding it, is read-only
+    ReadOnly(PathBuf)
+}
+
+impl error::Error for AccessError {}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessError::ReadOnly(path) => write!(f, "Can't overwrite {}, it is read-only", path.display())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, PngmeError};
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_check_writable() {
+        let path = env::temp_dir().join(format!("pngme_access_{}.png", process::id()));
+        fs::write(&path, b"test").unwrap();
+
+        assert!(check_writable(&path).is_ok());
+
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let error = check_writable(&path).unwrap_err();
+        assert!(matches!(error, PngmeError::Access(AccessError::ReadOnly(_))));
+        assert_eq!(error.kind(), ErrorKind::Io);
+
+        // Nothing was written while checking
+        assert_eq!(fs::read(&path).unwrap(), b"test");
+
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_check_missing_file() {
+        assert!(check_writable(Path::new("./missing.png")).is_err());
+    }
+}
@@ -0,0 +1,602 @@
+use serde::{Deserialize, Serialize};
+use std::convert::{TryFrom, TryInto};
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+/// Animation control (acTL) chunk data of an APNG
+///
+/// See [APNG Specification](https://wiki.mozilla.org/APNG_Specification) for details
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    pub num_plays: u32
+}
+
+impl AnimationControl {
+    /// Length of the acTL chunk's data
+    pub const LENGTH: usize = 8;
+
+    /// Animation control as chunk data bytes
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.num_frames
+            .to_be_bytes()
+            .iter()
+            .chain(self.num_plays.to_be_bytes().iter())
+            .copied()
+            .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != AnimationControl::LENGTH {
+            return Err(ApngError::InvalidAnimationControl.into());
+        }
+
+        Ok(AnimationControl {
+            num_frames: u32::from_be_bytes(bytes[0..4].try_into()?),
+            num_plays: u32::from_be_bytes(bytes[4..8].try_into()?)
+        })
+    }
+}
+
+/// How the frame's area is disposed of before rendering the next frame
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisposeOp {
+    /// Leave the frame's area as it is
+    #[default]
+    None,
+
+    /// Clear the frame's area to fully transparent black
+    Background,
+
+    /// Revert the frame's area to its previous contents
+    Previous
+}
+
+impl TryFrom<u8> for DisposeOp {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(DisposeOp::None),
+            1 => Ok(DisposeOp::Background),
+            2 => Ok(DisposeOp::Previous),
+            _ => Err(ApngError::InvalidFrameControl.into())
+        }
+    }
+}
+
+/// How the frame is rendered on top of the frame's area
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendOp {
+    /// Overwrite the frame's area
+    #[default]
+    Source,
+
+    /// Alpha blend the frame over the frame's area
+    Over
+}
+
+impl TryFrom<u8> for BlendOp {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(BlendOp::Source),
+            1 => Ok(BlendOp::Over),
+            _ => Err(ApngError::InvalidFrameControl.into())
+        }
+    }
+}
+
+/// Frame control (fcTL) chunk data of an APNG
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp
+}
+
+impl FrameControl {
+    /// Length of the fcTL chunk's data
+    pub const LENGTH: usize = 26;
+
+    /// Frame control as chunk data bytes
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset
+        ]
+        .iter()
+        .flat_map(|value| value.to_be_bytes().to_vec())
+        .chain(self.delay_num.to_be_bytes().iter().copied())
+        .chain(self.delay_den.to_be_bytes().iter().copied())
+        .chain([self.dispose_op as u8, self.blend_op as u8].iter().copied())
+        .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != FrameControl::LENGTH {
+            return Err(ApngError::InvalidFrameControl.into());
+        }
+
+        Ok(FrameControl {
+            sequence_number: u32::from_be_bytes(bytes[0..4].try_into()?),
+            width: u32::from_be_bytes(bytes[4..8].try_into()?),
+            height: u32::from_be_bytes(bytes[8..12].try_into()?),
+            x_offset: u32::from_be_bytes(bytes[12..16].try_into()?),
+            y_offset: u32::from_be_bytes(bytes[16..20].try_into()?),
+            delay_num: u16::from_be_bytes(bytes[20..22].try_into()?),
+            delay_den: u16::from_be_bytes(bytes[22..24].try_into()?),
+            dispose_op: DisposeOp::try_from(bytes[24])?,
+            blend_op: BlendOp::try_from(bytes[25])?
+        })
+    }
+}
+
+impl FrameControl {
+    /// Playback options of the frame
+    pub fn options(&self) -> FrameOptions {
+        FrameOptions {
+            delay_num: self.delay_num,
+            delay_den: self.delay_den,
+            x_offset: self.x_offset,
+            y_offset: self.y_offset,
+            dispose_op: self.dispose_op,
+            blend_op: self.blend_op
+        }
+    }
+}
+
+/// Playback options used when assembling a frame into an APNG
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct FrameOptions {
+    /// Numerator of the frame's delay in seconds
+    pub delay_num: u16,
+
+    /// Denominator of the frame's delay in seconds
+    pub delay_den: u16,
+
+    #[serde(default)]
+    pub x_offset: u32,
+
+    #[serde(default)]
+    pub y_offset: u32,
+
+    #[serde(default)]
+    pub dispose_op: DisposeOp,
+
+    #[serde(default)]
+    pub blend_op: BlendOp
+}
+
+impl FrameOptions {
+    /// Options for a frame shown for a delay in milliseconds at the top left corner
+    pub fn with_delay_ms(delay: u16) -> Self {
+        FrameOptions {
+            delay_num: delay,
+            delay_den: 1000,
+            x_offset: 0,
+            y_offset: 0,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source
+        }
+    }
+}
+
+/// A single frame of an APNG: its frame control and its image data
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Frame {
+    pub control: FrameControl,
+    pub data: Vec<Vec<u8>>
+}
+
+/// Extracts every frame of an APNG
+pub fn frames(png: &Png) -> Result<Vec<Frame>> {
+    if png.chunk_by_type("acTL").is_none() {
+        return Err(ApngError::NotAnimated.into());
+    }
+
+    let mut frames: Vec<Frame> = Vec::new();
+
+    for chunk in png.chunks() {
+        match chunk.chunk_type().to_string().as_str() {
+            "fcTL" => frames.push(Frame {
+                control: FrameControl::try_from(chunk.data())?,
+                data: Vec::new()
+            }),
+            // IDAT only belongs to the animation if an fcTL comes before it
+            "IDAT" => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.data.push(chunk.data().to_vec());
+                }
+            },
+            // fdAT data starts with a 4 byte sequence number
+            "fdAT" => {
+                let frame = frames.last_mut().ok_or(ApngError::InvalidFrameData)?;
+
+                if chunk.data().len() < 4 {
+                    return Err(ApngError::InvalidFrameData.into());
+                }
+
+                frame.data.push(chunk.data()[4..].to_vec());
+            },
+            _ => {}
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Animation control of an APNG
+pub fn animation_control(png: &Png) -> Result<AnimationControl> {
+    let chunk = png.chunk_by_type("acTL").ok_or(ApngError::NotAnimated)?;
+    AnimationControl::try_from(chunk.data())
+}
+
+/// Builds a standalone PNG for every frame of an APNG, along with the frame's control
+pub fn export_frames(png: &Png) -> Result<Vec<(FrameControl, Png)>> {
+    let ihdr = png.chunk_by_type("IHDR").ok_or(ApngError::MissingHeader)?;
+    let shared = shared_chunks(png);
+
+    frames(png)?
+        .into_iter()
+        .map(|frame| {
+            let mut chunks = vec![resized_header(ihdr, frame.control.width, frame.control.height)?];
+            chunks.extend(shared.iter().map(|chunk| (*chunk).clone()));
+
+            for data in frame.data {
+                chunks.push(Chunk::new(ChunkType::from_str("IDAT")?, data));
+            }
+
+            chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new()));
+
+            Ok((frame.control, Png::from_chunks(chunks)))
+        })
+        .collect()
+}
+
+/// Builds an APNG from standalone PNG frames, each played with its own options
+pub fn assemble(frames: &[Png], options: &[FrameOptions], num_plays: u32) -> Result<Png> {
+    let first = frames.first().ok_or(ApngError::NoFrames)?;
+    let ihdr = first.chunk_by_type("IHDR").ok_or(ApngError::MissingHeader)?;
+
+    if options.len() != frames.len() {
+        return Err(ApngError::OptionsCountMismatch(options.len(), frames.len()).into());
+    }
+
+    let (width, height) = dimensions(ihdr)?;
+
+    let animation_control = AnimationControl {
+        num_frames: frames.len() as u32,
+        num_plays
+    };
+
+    let mut chunks = vec![
+        ihdr.clone(),
+        Chunk::new(ChunkType::from_str("acTL")?, animation_control.as_bytes())
+    ];
+    chunks.extend(shared_chunks(first).into_iter().cloned());
+
+    let mut sequence_number = 0;
+
+    for (index, (frame, options)) in frames.iter().zip(options).enumerate() {
+        let frame_ihdr = frame.chunk_by_type("IHDR").ok_or(ApngError::MissingHeader)?;
+        let (frame_width, frame_height) = dimensions(frame_ihdr)?;
+
+        // Frames must share the pixel format and fit inside the first frame
+        let fits = u64::from(options.x_offset) + u64::from(frame_width) <= u64::from(width)
+            && u64::from(options.y_offset) + u64::from(frame_height) <= u64::from(height);
+
+        if frame_ihdr.data()[8..] != ihdr.data()[8..] || !fits {
+            return Err(ApngError::FrameMismatch(index).into());
+        }
+
+        let control = FrameControl {
+            sequence_number,
+            width: frame_width,
+            height: frame_height,
+            x_offset: options.x_offset,
+            y_offset: options.y_offset,
+            delay_num: options.delay_num,
+            delay_den: options.delay_den,
+            dispose_op: options.dispose_op,
+            blend_op: options.blend_op
+        };
+        chunks.push(Chunk::new(ChunkType::from_str("fcTL")?, control.as_bytes()));
+        sequence_number += 1;
+
+        let image_data = frame
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().to_string() == "IDAT");
+
+        for chunk in image_data {
+            // The first frame is also the default image, so it keeps its IDAT chunks
+            if index == 0 {
+                chunks.push(chunk.clone());
+            } else {
+                let data: Vec<u8> = sequence_number
+                    .to_be_bytes()
+                    .iter()
+                    .chain(chunk.data().iter())
+                    .copied()
+                    .collect();
+                chunks.push(Chunk::new(ChunkType::from_str("fdAT")?, data));
+                sequence_number += 1;
+            }
+        }
+    }
+
+    chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new()));
+
+    Ok(Png::from_chunks(chunks))
+}
+
+/// Chunks between IHDR and the image data that every frame needs (palette, transparency, etc.)
+fn shared_chunks(png: &Png) -> Vec<&Chunk> {
+    png.chunks()
+        .iter()
+        .skip(1)
+        .take_while(|chunk| {
+            let chunk_type = chunk.chunk_type().to_string();
+            chunk_type != "IDAT" && chunk_type != "fcTL"
+        })
+        .filter(|chunk| chunk.chunk_type().to_string() != "acTL")
+        .collect()
+}
+
+/// Width and height of an IHDR chunk
+fn dimensions(ihdr: &Chunk) -> Result<(u32, u32)> {
+    let data = ihdr.data();
+
+    if data.len() != 13 {
+        return Err(ApngError::MissingHeader.into());
+    }
+
+    Ok((
+        u32::from_be_bytes(data[0..4].try_into()?),
+        u32::from_be_bytes(data[4..8].try_into()?)
+    ))
+}
+
+/// Copy of an IHDR chunk with different dimensions
+fn resized_header(ihdr: &Chunk, width: u32, height: u32) -> Result<Chunk> {
+    dimensions(ihdr)?;
+
+    let data: Vec<u8> = width
+        .to_be_bytes()
+        .iter()
+        .chain(height.to_be_bytes().iter())
+        .chain(ihdr.data()[8..].iter())
+        .copied()
+        .collect();
+
+    Ok(Chunk::new(ChunkType::from_str("IHDR")?, data))
+}
+
+/// APNG errors
+#[derive(Debug)]
+pub enum ApngError {
+    /// The PNG has no acTL chunk
+    NotAnimated,
+
+    /// The PNG has no valid IHDR chunk
+    MissingHeader,
+
+    /// acTL chunk data is malformed
+    InvalidAnimationControl,
+
+    /// fcTL chunk data is malformed
+    InvalidFrameControl,
+
+    /// fdAT chunk is malformed or appears before any fcTL chunk
+    InvalidFrameData,
+
+    /// No frames were given to assemble an APNG
+    NoFrames,
+
+    /// The number of frame options does not match the number of frames
+    OptionsCountMismatch(usize, usize),
+
+    /// The frame at the given index is incompatible with the first frame
+    FrameMismatch(usize)
+}
+
+impl error::Error for ApngError {}
+
+impl Display for ApngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApngError::NotAnimated => write!(f, "The PNG is not animated"),
+            ApngError::MissingHeader => write!(f, "Missing or invalid IHDR chunk"),
+            ApngError::InvalidAnimationControl => write!(f, "Invalid acTL chunk"),
+            ApngError::InvalidFrameControl => write!(f, "Invalid fcTL chunk"),
+            ApngError::InvalidFrameData => write!(f, "Invalid fdAT chunk"),
+            ApngError::NoFrames => write!(f, "At least one frame is needed to build an APNG"),
+            ApngError::OptionsCountMismatch(options, frames) => write!(
+                f,
+                "Received options for {} frames but found {} frames",
+                options, frames
+            ),
+            ApngError::FrameMismatch(index) => write!(
+                f,
+                "Frame {} does not fit inside the first frame or does not match its pixel format",
+                index
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_frame(width: u32, height: u32, data: &str) -> Png {
+        let ihdr: Vec<u8> = width
+            .to_be_bytes()
+            .iter()
+            .chain(height.to_be_bytes().iter())
+            .chain([8, 6, 0, 0, 0].iter())
+            .copied()
+            .collect();
+
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr),
+            Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 177, 143]),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), data.as_bytes().to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())
+        ])
+    }
+
+    fn testing_apng() -> Png {
+        let frames = vec![
+            testing_frame(4, 4, "first"),
+            testing_frame(2, 3, "second"),
+            testing_frame(4, 4, "third")
+        ];
+
+        let options = vec![
+            FrameOptions::with_delay_ms(100),
+            FrameOptions { x_offset: 1, blend_op: BlendOp::Over, ..FrameOptions::with_delay_ms(50) },
+            FrameOptions { dispose_op: DisposeOp::Previous, ..FrameOptions::with_delay_ms(100) }
+        ];
+
+        assemble(&frames, &options, 0).unwrap()
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let control = FrameControl {
+            sequence_number: 3,
+            width: 10,
+            height: 20,
+            x_offset: 1,
+            y_offset: 2,
+            delay_num: 100,
+            delay_den: 1000,
+            dispose_op: DisposeOp::Background,
+            blend_op: BlendOp::Over
+        };
+
+        let bytes = control.as_bytes();
+        assert_eq!(bytes.len(), FrameControl::LENGTH);
+        assert_eq!(FrameControl::try_from(&bytes[..]).unwrap(), control);
+    }
+
+    #[test]
+    fn test_invalid_frame_control() {
+        assert!(FrameControl::try_from(&[0, 1, 2][..]).is_err());
+
+        let mut bytes = FrameControl::try_from(&[0; 26][..]).unwrap().as_bytes();
+        bytes[24] = 3;
+        assert!(FrameControl::try_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn test_assemble_chunk_layout() {
+        let apng = testing_apng();
+        let chunk_types: Vec<String> = apng
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(
+            chunk_types,
+            vec!["IHDR", "acTL", "gAMA", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"]
+        );
+
+        let control = AnimationControl::try_from(apng.chunk_by_type("acTL").unwrap().data()).unwrap();
+        assert_eq!(control.num_frames, 3);
+    }
+
+    #[test]
+    fn test_assemble_sequence_numbers() {
+        let apng = testing_apng();
+        let frames = frames(&apng).unwrap();
+        let sequence_numbers: Vec<u32> = frames
+            .iter()
+            .map(|frame| frame.control.sequence_number)
+            .collect();
+
+        assert_eq!(sequence_numbers, vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn test_assemble_frame_options() {
+        let apng = testing_apng();
+        let frames = frames(&apng).unwrap();
+
+        assert_eq!(frames[1].control.x_offset, 1);
+        assert_eq!(frames[1].control.delay_num, 50);
+        assert_eq!(frames[1].control.blend_op, BlendOp::Over);
+        assert_eq!(frames[2].control.dispose_op, DisposeOp::Previous);
+    }
+
+    #[test]
+    fn test_assemble_options_mismatch() {
+        let frames = vec![testing_frame(4, 4, "first")];
+        assert!(assemble(&frames, &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_assemble_oversized_frame() {
+        let frames = vec![testing_frame(4, 4, "first"), testing_frame(5, 4, "second")];
+        let options = vec![FrameOptions::with_delay_ms(1); 2];
+        assert!(assemble(&frames, &options, 0).is_err());
+
+        // A frame that fits alone can still overflow the canvas through its offset
+        let frames = vec![testing_frame(4, 4, "first"), testing_frame(2, 4, "second")];
+        let options = vec![
+            FrameOptions::with_delay_ms(1),
+            FrameOptions { x_offset: 3, ..FrameOptions::with_delay_ms(1) }
+        ];
+        assert!(assemble(&frames, &options, 0).is_err());
+    }
+
+    #[test]
+    fn test_export_frames() {
+        let apng = testing_apng();
+        let exported = export_frames(&apng).unwrap();
+
+        assert_eq!(exported.len(), 3);
+
+        let (control, second) = &exported[1];
+        assert_eq!(control.blend_op, BlendOp::Over);
+        assert_eq!(second.chunk_by_type("IHDR").unwrap().data()[0..8], [0, 0, 0, 2, 0, 0, 0, 3]);
+        assert!(second.chunk_by_type("gAMA").is_some());
+        assert!(second.chunk_by_type("acTL").is_none());
+        assert_eq!(second.chunk_by_type("IDAT").unwrap().data(), b"second");
+    }
+
+    #[test]
+    fn test_export_still_image() {
+        let png = testing_frame(4, 4, "still");
+        assert!(export_frames(&png).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::files::{FileProvider, ListOptions, Listing};
+use crate::Result;
+
+/// First bytes of a ZIP archive, or of an empty one
+const SIGNATURES: [[u8; 4]; 2] = [*b"PK\x03\x04", *b"PK\x05\x06"];
+
+/// Largest member read out of an archive, whose size a few kilobytes of deflated zeros can make huge
+const MAX_MEMBER_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Files inside ZIP archives, such as EPUB books or Office documents, on top of the files of another provider
+///
+/// `archive.zip!inner/path.png` points to a file inside an archive, and with a default member every archive given in
+/// place of a file stands for that member. Files inside archives can be read but not written yet
+pub struct ArchiveFiles<'a> {
+    files: &'a dyn FileProvider,
+
+    /// Member read out of archives given without one
+    member: Option<String>
+}
+
+impl<'a> ArchiveFiles<'a> {
+    pub fn new(files: &'a dyn FileProvider, member: Option<String>) -> Self {
+        ArchiveFiles { files, member }
+    }
+
+    /// Archive and member a path points to, `None` for a file outside of any archive
+    ///
+    /// Archive names may hold a `!` themselves, so the first prefix before a `!` that is a file is the archive
+    fn locate(&self, path: &Path) -> Option<(PathBuf, String)> {
+        let text = path.to_str()?;
+
+        let inside = text.match_indices('!').map(|(index, _)| (&text[..index], &text[index + 1..])).find(
+            |(archive, member)| !member.is_empty() && self.files.exists(Path::new(archive))
+        );
+
+        match (inside, &self.member) {
+            (Some((archive, member)), _) => Some((PathBuf::from(archive), member.to_string())),
+            (None, Some(member)) if self.is_archive(path) => Some((path.to_path_buf(), member.clone())),
+            (None, _) => None
+        }
+    }
+
+    fn is_archive(&self, path: &Path) -> bool {
+        self.files.read_prefix(path, 4).is_ok_and(|prefix| SIGNATURES.iter().any(|signature| prefix == signature))
+    }
+
+    fn check_outside(&self, path: &Path) -> Result<()> {
+        match self.locate(path) {
+            Some(_) => Err(ArchiveError::ReadOnly(path.to_path_buf()).into()),
+            None => Ok(())
+        }
+    }
+}
+
+impl FileProvider for ArchiveFiles<'_> {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.locate(path) {
+            Some((archive, member)) => extract(&self.files.read(&archive)?, &archive, &member, MAX_MEMBER_SIZE),
+            None => self.files.read(path)
+        }
+    }
+
+    fn read_prefix(&self, path: &Path, length: usize) -> Result<Vec<u8>> {
+        match self.locate(path) {
+            Some(_) => {
+                let mut bytes = self.read(path)?;
+                bytes.truncate(length);
+                Ok(bytes)
+            },
+            None => self.files.read_prefix(path, length)
+        }
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        self.check_outside(path)?;
+        self.files.write(path, bytes)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_outside(from)?;
+        self.check_outside(to)?;
+        self.files.rename(from, to)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        match self.locate(path) {
+            Some(_) => self.read(path).is_ok(),
+            None => self.files.exists(path)
+        }
+    }
+
+    fn list(&self, directory: &Path, options: &ListOptions) -> Result<Vec<Listing>> {
+        self.files.list(directory, options)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.files.create_dir_all(path)
+    }
+
+    fn check_writable(&self, path: &Path) -> Result<()> {
+        self.check_outside(path)?;
+        self.files.check_writable(path)
+    }
+}
+
+/// Contents of a member of an archive, refused when they are bigger than the limit
+fn extract(bytes: &[u8], archive: &Path, member: &str, limit: u64) -> Result<Vec<u8>> {
+    let invalid = |error: ZipError| ArchiveError::InvalidArchive(archive.to_path_buf(), error.to_string());
+
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(invalid)?;
+    let file = zip.by_name(member).map_err(|error| match error {
+        ZipError::FileNotFound => ArchiveError::MemberNotFound(archive.to_path_buf(), member.to_string()),
+        error => invalid(error)
+    })?;
+
+    // The size in the archive's directory isn't trusted, only what is actually decompressed
+    let mut contents = Vec::new();
+    file.take(limit + 1).read_to_end(&mut contents).map_err(|error| invalid(error.into()))?;
+
+    if contents.len() as u64 > limit {
+        return Err(ArchiveError::MemberTooLarge(archive.to_path_buf(), member.to_string(), limit).into());
+    }
+
+    Ok(contents)
+}
+
+/// Archive errors
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// Archive has no member at this path
+    MemberNotFound(PathBuf, String),
+
+    /// Archive isn't a ZIP archive or is damaged
+    InvalidArchive(PathBuf, String),
+
+    /// Files inside archives can only be read
+    ReadOnly(PathBuf),
+
+    /// Member decompresses to more bytes than the limit
+    MemberTooLarge(PathBuf, String, u64)
+}
+
+impl error::Error for ArchiveError {}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::MemberNotFound(archive, member) => {
+                write!(f, "{} has no file named {}", archive.display(), member)
+            },
+            ArchiveError::InvalidArchive(archive, reason) => {
+                write!(f, "{} isn't a readable ZIP archive: {}", archive.display(), reason)
+            },
+            ArchiveError::ReadOnly(path) => {
+                write!(f, "{} is inside an archive, files inside archives can only be read", path.display())
+            },
+            ArchiveError::MemberTooLarge(archive, member, limit) => {
+                write!(f, "{} in {} is bigger than {} bytes", member, archive.display(), limit)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::MemoryFiles;
+    use crate::Error;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    fn zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        for (name, contents) in members {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn files() -> MemoryFiles {
+        let book = zip(&[("OEBPS/images/cover.png", b"cover"), ("mimetype", b"application/epub+zip")]);
+
+        MemoryFiles::new().with_file("book!.epub", book).with_file("dice.png", b"dice".to_vec())
+    }
+
+    #[test]
+    fn test_read_member() {
+        let files = files();
+        let archives = ArchiveFiles::new(&files, None);
+
+        assert_eq!(archives.read(Path::new("book!.epub!OEBPS/images/cover.png")).unwrap(), b"cover");
+        assert_eq!(archives.read_prefix(Path::new("book!.epub!OEBPS/images/cover.png"), 2).unwrap(), b"co");
+        assert!(archives.exists(Path::new("book!.epub!mimetype")));
+        assert!(!archives.exists(Path::new("book!.epub!missing.png")));
+        assert_eq!(archives.read(Path::new("dice.png")).unwrap(), b"dice");
+
+        assert!(matches!(
+            archives.read(Path::new("book!.epub!missing.png")),
+            Err(Error::Archive(ArchiveError::MemberNotFound(_, _)))
+        ));
+        assert!(matches!(
+            archives.read(Path::new("dice.png!cover.png")),
+            Err(Error::Archive(ArchiveError::InvalidArchive(_, _)))
+        ));
+    }
+
+    #[test]
+    fn test_member_too_large() {
+        let bomb = zip(&[("bomb.png", &[0; 4096])]);
+        let archive = Path::new("bomb.zip");
+
+        assert_eq!(extract(&bomb, archive, "bomb.png", 4096).unwrap().len(), 4096);
+        assert!(matches!(
+            extract(&bomb, archive, "bomb.png", 4095),
+            Err(Error::Archive(ArchiveError::MemberTooLarge(_, _, 4095)))
+        ));
+    }
+
+    #[test]
+    fn test_default_member() {
+        let files = files();
+        let archives = ArchiveFiles::new(&files, Some(String::from("OEBPS/images/cover.png")));
+
+        assert_eq!(archives.read(Path::new("book!.epub")).unwrap(), b"cover");
+        assert_eq!(archives.read(Path::new("dice.png")).unwrap(), b"dice");
+    }
+
+    #[test]
+    fn test_archives_are_read_only() {
+        let files = files();
+        let book = files.get(Path::new("book!.epub")).unwrap();
+        let archives = ArchiveFiles::new(&files, Some(String::from("OEBPS/images/cover.png")));
+
+        for path in ["book!.epub!OEBPS/images/cover.png", "book!.epub"] {
+            assert!(matches!(archives.check_writable(Path::new(path)), Err(Error::Archive(ArchiveError::ReadOnly(_)))));
+            assert!(archives.write(Path::new(path), b"changed").is_err());
+        }
+
+        archives.write(Path::new("output.png"), b"output").unwrap();
+        assert_eq!(files.get(Path::new("output.png")).unwrap(), b"output");
+        assert_eq!(files.get(Path::new("book!.epub")).unwrap(), book);
+    }
+}
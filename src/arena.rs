@@ -0,0 +1,51 @@
+use std::fmt::{self, Debug};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// Data of a chunk, either owned or a range of an arena shared with the other chunks of its file
+#[derive(Clone)]
+pub enum ChunkData {
+    Owned(Vec<u8>),
+    Shared(Arc<[u8]>, Range<usize>)
+}
+
+impl Deref for ChunkData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ChunkData::Owned(data) => data,
+            ChunkData::Shared(arena, range) => &arena[range.clone()]
+        }
+    }
+}
+
+// Chunks are equal when their bytes are, wherever they are stored
+impl PartialEq for ChunkData {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for ChunkData {}
+
+impl Debug for ChunkData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_data() {
+        let arena: Arc<[u8]> = Arc::from(&b"IHDRruSt"[..]);
+        let shared = ChunkData::Shared(arena, 4..8);
+
+        assert_eq!(&*shared, b"ruSt");
+        assert_eq!(shared, ChunkData::Owned(b"ruSt".to_vec()));
+        assert_eq!(format!("{:?}", shared), format!("{:?}", b"ruSt"));
+    }
+}
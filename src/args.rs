@@ -1,7 +1,22 @@
+use std::env;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+use crate::carrier::PayloadSize;
 use crate::chunk_type::ChunkType;
+use crate::compress::{Compression, Dictionary};
+use crate::diagnostics::{Policy, Rule};
+use crate::events::{EventTarget, Events};
+use crate::locale::Locale;
+use crate::output::OutputFormat;
+use crate::png::ChunkPosition;
+use crate::profile::Profile;
+use crate::rename::{Collision, Pattern};
+use crate::scan::{ScanPolicy, Scanner};
+use crate::settings::{Settings, DETERMINISTIC_VAR};
+use crate::stego::Mode;
+use crate::sync::MatchBy;
+use crate::Result;
 
 #[derive(Debug, StructOpt, PartialEq)]
 /// Add secret message in PNG file
@@ -18,7 +33,48 @@ pub struct Encode {
 
     /// Optional - file path for output file
     #[structopt(parse(from_os_str))]
-    pub output_file: Option<PathBuf>
+    pub output_file: Option<PathBuf>,
+
+    /// Optional - key file used to sign the message with HMAC-SHA256
+    #[structopt(long, parse(from_os_str))]
+    pub sign: Option<PathBuf>,
+
+    /// Where to insert the message's chunk: after-ihdr, before-iend or index:<n>
+    #[structopt(long, default_value = "before-iend")]
+    pub position: ChunkPosition,
+
+    /// Where to hide the message: chunk or lsb - chunk type and position are ignored in lsb mode
+    #[structopt(long, default_value = "chunk")]
+    pub mode: Mode,
+
+    /// Write the message even if its chunk type is critical or registered, which may corrupt the image
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Replace an existing chunk of the same chunk type instead of adding another one
+    #[structopt(long)]
+    pub replace: bool,
+
+    /// Compress the message before hiding it: none or zstd - decode decompresses it on its own
+    #[structopt(long, default_value = "none")]
+    pub compress: Compression,
+
+    /// Dictionary of sample text shrinking small messages further when compressing: builtin-text
+    #[structopt(long)]
+    pub dict: Option<Dictionary>,
+
+    /// Store only the difference with the latest version hidden in chunks of the same type, right after them
+    #[structopt(long, conflicts_with = "replace")]
+    pub delta: bool,
+
+    /// Record a digest of the image along with the message, so decode warns once the image is resized or recompressed
+    #[structopt(long)]
+    pub seal: bool,
+
+    /// Optional - file path where parity data is written, so `recover` can rebuild the message once it is damaged or
+    /// stripped from the image
+    #[structopt(long, parse(from_os_str))]
+    pub par2_out: Option<PathBuf>
 }
 
 #[derive(Debug, StructOpt, PartialEq)]
@@ -29,7 +85,63 @@ pub struct Decode {
     pub filepath: PathBuf,
 
     /// Chunk type of chunk that we want to decode
-    pub chunk_type: ChunkType
+    pub chunk_type: ChunkType,
+
+    /// Optional - key file used to verify the message's signature
+    #[structopt(long, parse(from_os_str))]
+    pub verify: Option<PathBuf>,
+
+    /// Where the message is hidden: chunk or lsb - chunk type is ignored in lsb mode
+    #[structopt(long, default_value = "chunk")]
+    pub mode: Mode,
+
+    /// Optional - file path where the message's bytes are written verbatim
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+
+    /// Write the message's bytes verbatim to stdout
+    #[structopt(long, conflicts_with = "output")]
+    pub raw: bool,
+
+    /// Optional - command the message's bytes are piped to before being shown, any exit code other than 0 flags them
+    #[structopt(long)]
+    pub scan: Option<Scanner>,
+
+    /// What to do when the scanner flags the message: block or flag
+    #[structopt(long, default_value = "block")]
+    pub scan_policy: ScanPolicy
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Check that a chunk of a PNG file holds the expected data, failing otherwise
+pub struct Assert {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Chunk type of chunk that we want to check
+    pub chunk_type: ChunkType,
+
+    /// Optional - key file used to verify the message's signature before it is checked
+    #[structopt(long, parse(from_os_str))]
+    pub verify: Option<PathBuf>,
+
+    /// File holding the exact bytes expected in the chunk's data
+    #[structopt(
+        long,
+        parse(from_os_str),
+        required_unless_one = &["equals", "matches"],
+        conflicts_with_all = &["equals", "matches"]
+    )]
+    pub equals_file: Option<PathBuf>,
+
+    /// Text expected as the chunk's data
+    #[structopt(long, conflicts_with = "matches")]
+    pub equals: Option<String>,
+
+    /// Regular expression the chunk's data must contain a match of - use ^ and $ to match all of it
+    #[structopt(long)]
+    pub matches: Option<String>
 }
 
 #[derive(Debug, StructOpt, PartialEq)]
@@ -49,6 +161,376 @@ pub struct Print {
     /// File path of output file
     #[structopt(parse(from_os_str))]
     pub filepath: PathBuf,
+
+    /// Only print chunks whose type matches one of these patterns - `*` and `?` act as wildcards
+    #[structopt(short = "t", long = "type", use_delimiter = true)]
+    pub types: Vec<String>,
+
+    /// Only print critical chunks
+    #[structopt(long, conflicts_with = "ancillary-only")]
+    pub critical_only: bool,
+
+    /// Only print ancillary chunks
+    #[structopt(long)]
+    pub ancillary_only: bool,
+
+    /// Don't print the chunks' data
+    #[structopt(long, conflicts_with = "hex")]
+    pub no_data: bool,
+
+    /// Print the chunks' data as a hex dump, truncated to its first 256 bytes
+    #[structopt(long)]
+    pub hex: bool,
+
+    /// Don't truncate the hex dump
+    #[structopt(long, requires = "hex")]
+    pub full: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Compare the chunks of two PNG files
+pub struct Diff {
+    /// File path of the first PNG file
+    #[structopt(parse(from_os_str))]
+    pub first: PathBuf,
+
+    /// File path of the second PNG file
+    #[structopt(parse(from_os_str))]
+    pub second: PathBuf,
+
+    /// Skip IDAT chunks, to focus on metadata
+    #[structopt(long)]
+    pub ignore_idat: bool,
+
+    /// List every chunk of both files side by side, aligned so chunks that kept their place share a line
+    #[structopt(long)]
+    pub side_by_side: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Encrypt chunks of a PNG file in place, hiding sensitive metadata while the image stays viewable
+pub struct Protect {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Chunk types to encrypt, such as tEXt,eXIf - critical chunks can't be encrypted
+    #[structopt(long, required = true, use_delimiter = true)]
+    pub chunks: Vec<ChunkType>,
+
+    /// Password the encryption key is derived from
+    #[structopt(long)]
+    pub password: String
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Decrypt the chunks of a PNG file encrypted by protect
+pub struct Unprotect {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Password given to protect
+    #[structopt(long)]
+    pub password: String
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Write metadata from a sidecar JSON file into the chunks of a PNG file
+pub struct ImportMetadata {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Sidecar JSON file with title, author, description, copyright, keywords, dpi, gamma and custom fields
+    #[structopt(long, parse(from_os_str))]
+    pub from: PathBuf
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Save the metadata of a PNG file as a sidecar JSON file
+pub struct ExportMetadata {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path of the sidecar JSON file, printed to stdout otherwise
+    #[structopt(long, parse(from_os_str))]
+    pub to: Option<PathBuf>
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Copy the safe to copy metadata chunks of original PNG files to the derivative files that lost them
+pub struct SyncMetadata {
+    /// Directory holding the original PNG files
+    #[structopt(long, parse(from_os_str))]
+    pub from: PathBuf,
+
+    /// Directory holding the derivative PNG files, which are updated
+    #[structopt(long, parse(from_os_str))]
+    pub to: PathBuf,
+
+    /// How derivative files are matched with their originals: name
+    #[structopt(long, default_value = "name")]
+    pub match_by: MatchBy,
+
+    /// Show how many chunks would be copied without changing any file
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Skip read-only derivative files instead of failing before any file is changed
+    #[structopt(long)]
+    pub if_writable: bool,
+
+    #[structopt(flatten)]
+    pub walk: Walk
+}
+
+/// How commands working on many files pick the files of a directory
+#[derive(Debug, StructOpt, PartialEq, Clone, Copy, Default)]
+pub struct Walk {
+    /// Pick PNG files by their signature rather than their .png extension
+    #[structopt(long)]
+    pub by_content: bool,
+
+    /// Follow symbolic links to files instead of skipping them
+    #[structopt(long)]
+    pub follow_symlinks: bool,
+
+    /// Skip files on another filesystem than the directory, such as those a symbolic link points to
+    #[structopt(long)]
+    pub one_file_system: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Convert a truecolor PNG file to a palette based one
+pub struct Quantize {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Maximum number of colors in the palette, from 2 to 256
+    #[structopt(short, long, default_value = "256")]
+    pub colors: usize,
+
+    /// Spread the color error over neighbouring pixels with Floyd-Steinberg dithering
+    #[structopt(long)]
+    pub dither: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Merge runs of tiny IDAT chunks into fewer, larger ones
+pub struct Normalize {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Maximum data length of the merged IDAT chunks, in bytes
+    #[structopt(long, default_value = "8192")]
+    pub max_size: usize
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Check the compressed streams of a PNG file layer by layer
+pub struct Validate {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Restore a damaged PNG signature, regenerate a missing IHDR chunk or recompute the Adler-32 of the image data
+pub struct Repair {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Width of the image, to regenerate a missing IHDR chunk
+    #[structopt(long, requires = "height")]
+    pub width: Option<u32>,
+
+    /// Height of the image, to regenerate a missing IHDR chunk
+    #[structopt(long, requires = "width")]
+    pub height: Option<u32>,
+
+    /// Bit depth of the regenerated IHDR chunk
+    #[structopt(long, default_value = "8")]
+    pub bit_depth: u8,
+
+    /// Color type of the regenerated IHDR chunk: 0 grayscale, 2 RGB, 3 palette, 4 grayscale with alpha or 6 RGBA
+    #[structopt(long, default_value = "6")]
+    pub color_type: u8,
+
+    /// Whether the image data of the regenerated IHDR chunk is Adam7 interlaced
+    #[structopt(long)]
+    pub interlaced: bool,
+
+    /// Recompute the Adler-32 checksum of the image data when it is wrong but the deflate data is intact
+    #[structopt(long)]
+    pub fix_adler: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Keep the intact chunks a truncated PNG starts with and end it with an IEND chunk
+pub struct Salvage {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Pad image data cut short with zeros, so the top of the image that was recovered can be shown
+    #[structopt(long)]
+    pub pad_image_data: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Rebuild a message damaged or stripped from a PNG file out of its parity file, and hide it again
+pub struct Recover {
+    /// File path of PNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// File path of the parity file written by `encode --par2-out`
+    #[structopt(parse(from_os_str))]
+    pub parity_file: PathBuf,
+
+    /// Optional - file path for output file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Rename every PNG file in a directory based on its metadata
+pub struct RenameBy {
+    /// Directory holding the PNG files
+    #[structopt(parse(from_os_str))]
+    pub directory: PathBuf,
+
+    /// New file name with {keyword:<keyword>}, {date}, {chunk:<type>} or {name} placeholders
+    #[structopt(short, long)]
+    pub pattern: Pattern,
+
+    /// Show the new file names without renaming anything
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// What to do when a new file name is taken: skip or suffix
+    #[structopt(long, default_value = "suffix")]
+    pub on_collision: Collision,
+
+    #[structopt(flatten)]
+    pub walk: Walk
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Rank the PNG files in a directory by how well they can hide a payload in their pixels
+pub struct PickCarrier {
+    /// Directory holding the PNG files
+    #[structopt(parse(from_os_str))]
+    pub directory: PathBuf,
+
+    /// Size of the payload, in bytes or with a unit such as KB, MB, KiB or MiB
+    #[structopt(short, long)]
+    pub payload_size: PayloadSize,
+
+    /// Maximum number of candidates to show
+    #[structopt(short, long, default_value = "5")]
+    pub count: usize,
+
+    #[structopt(flatten)]
+    pub walk: Walk
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Export every frame of an APNG as a standalone PNG file, along with a JSON manifest
+pub struct ExportFrames {
+    /// File path of APNG file
+    #[structopt(parse(from_os_str))]
+    pub filepath: PathBuf,
+
+    /// Directory where the frames are saved
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: PathBuf
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Build an APNG from standalone PNG frames
+pub struct ImportFrames {
+    /// File paths of the PNG frames, in order
+    #[structopt(required_unless = "manifest", parse(from_os_str))]
+    pub frames: Vec<PathBuf>,
+
+    /// Optional - JSON manifest listing the frames and their playback options
+    #[structopt(long, conflicts_with = "frames", parse(from_os_str))]
+    pub manifest: Option<PathBuf>,
+
+    /// File path for the APNG output file
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: PathBuf,
+
+    /// Delay of each frame in milliseconds - a single value applies to every frame
+    #[structopt(short, long, default_value = "100", use_delimiter = true)]
+    pub delay: Vec<u16>,
+
+    /// Number of times to play the animation - 0 loops forever
+    #[structopt(long, default_value = "0")]
+    pub plays: u32
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Hide and decode a message in a generated sample image, explaining every step
+pub struct Demo {
+    /// Optional - directory the sample images are written to, a temporary one by default
+    #[structopt(long, parse(from_os_str))]
+    pub directory: Option<PathBuf>,
+
+    /// Message hidden in the sample image
+    #[structopt(long, default_value = "Hello from pngme!")]
+    pub message: String
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Show the version, features, accepted values, limits and output schema versions of this build of pngme
+pub struct Capabilities {
+    /// Show the capabilities as a JSON object, the same as --format json
+    #[structopt(long)]
+    pub json: bool
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+/// Export or import the frames of an APNG
+pub enum Frames {
+    /// Export every frame of an APNG as a standalone PNG file
+    Export(ExportFrames),
+    /// Build an APNG from standalone PNG frames
+    Import(ImportFrames)
 }
 
 #[derive(Debug, StructOpt, PartialEq)]
@@ -58,16 +540,165 @@ pub enum Subcommand {
     Encode(Encode),
     /// Show a secret message from a PNG file
     Decode(Decode),
+    /// Check that a chunk of a PNG file holds the expected data
+    Assert(Assert),
     /// Remove a secret message from a PNG file
     Remove(Remove),
     /// Print every chunk from a PNG file
-    Print(Print)
+    Print(Print),
+    /// Compare the chunks of two PNG files
+    Diff(Diff),
+    /// Encrypt chunks of a PNG file in place, hiding sensitive metadata while the image stays viewable
+    Protect(Protect),
+    /// Decrypt the chunks of a PNG file encrypted by protect
+    Unprotect(Unprotect),
+    /// Write metadata from a sidecar JSON file into the chunks of a PNG file
+    ImportMetadata(ImportMetadata),
+    /// Save the metadata of a PNG file as a sidecar JSON file
+    ExportMetadata(ExportMetadata),
+    /// Copy the safe to copy metadata chunks of original PNG files to the derivative files that lost them
+    SyncMetadata(SyncMetadata),
+    /// Export or import the frames of an APNG
+    Frames(Frames),
+    /// Convert a truecolor PNG file to a palette based one
+    Quantize(Quantize),
+    /// Merge runs of tiny IDAT chunks into fewer, larger ones to speed up parsing
+    Normalize(Normalize),
+    /// Check that compressed chunks and the image data have an intact zlib header, deflate data and Adler-32
+    Validate(Validate),
+    /// Restore a damaged PNG signature, regenerate a missing IHDR chunk or recompute the Adler-32 of the image data
+    Repair(Repair),
+    /// Keep the intact chunks of a truncated PNG file, such as a partial download, and make it readable again
+    Salvage(Salvage),
+    /// Rebuild a hidden message out of the parity file written by encode, after the image was re-saved or damaged
+    Recover(Recover),
+    /// Rename every PNG file in a directory based on its metadata
+    RenameBy(RenameBy),
+    /// Rank the PNG files in a directory by how well they can hide a payload in their pixels
+    PickCarrier(PickCarrier),
+    /// Build a command step by step by answering questions, with explanations along the way
+    Wizard,
+    /// Hide and decode a message in a generated sample image, explaining every step
+    Demo(Demo),
+    /// Show the version, features, accepted values, limits and output schema versions of this build of pngme
+    Capabilities(Capabilities)
+}
+
+impl Subcommand {
+    /// Name of the subcommand as typed on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subcommand::Encode(_) => "encode",
+            Subcommand::Decode(_) => "decode",
+            Subcommand::Assert(_) => "assert",
+            Subcommand::Remove(_) => "remove",
+            Subcommand::Print(_) => "print",
+            Subcommand::Diff(_) => "diff",
+            Subcommand::Protect(_) => "protect",
+            Subcommand::Unprotect(_) => "unprotect",
+            Subcommand::ImportMetadata(_) => "import-metadata",
+            Subcommand::ExportMetadata(_) => "export-metadata",
+            Subcommand::SyncMetadata(_) => "sync-metadata",
+            Subcommand::Frames(Frames::Export(_)) => "frames export",
+            Subcommand::Frames(Frames::Import(_)) => "frames import",
+            Subcommand::Quantize(_) => "quantize",
+            Subcommand::Normalize(_) => "normalize",
+            Subcommand::Validate(_) => "validate",
+            Subcommand::Repair(_) => "repair",
+            Subcommand::Salvage(_) => "salvage",
+            Subcommand::Recover(_) => "recover",
+            Subcommand::RenameBy(_) => "rename-by",
+            Subcommand::PickCarrier(_) => "pick-carrier",
+            Subcommand::Wizard => "wizard",
+            Subcommand::Demo(_) => "demo",
+            Subcommand::Capabilities(_) => "capabilities"
+        }
+    }
 }
 
 #[derive(StructOpt)]
 pub struct Opt {
     #[structopt(subcommand)]
     pub subcommand: Subcommand,
+
+    /// Write progress events as lines of JSON to stdout, stderr or a file path such as /dev/fd/3
+    #[structopt(long, global = true)]
+    pub events: Option<EventTarget>,
+
+    /// Show results as text, json, csv, cbor or not at all with quiet
+    #[structopt(long, global = true, default_value = "text")]
+    pub format: OutputFormat,
+
+    /// Write cbor output even when stdout is a terminal
+    #[structopt(long, global = true)]
+    pub force_binary: bool,
+
+    /// Make output reproducible: ignore the locale of the environment and derive the salt and nonce of protected
+    /// chunks from their content - also turned on by setting PNGME_DETERMINISTIC
+    #[structopt(long, global = true)]
+    pub deterministic: bool,
+
+    /// Fail on any warning, such as chunks following IEND or a file skipped by a command working on many files
+    #[structopt(long, global = true)]
+    pub strict: bool,
+
+    /// Only warn about this rule in strict mode, given by code such as PNGME001 or by name such as trailing-data
+    #[structopt(long, global = true, number_of_values = 1)]
+    pub allow: Vec<Rule>,
+
+    /// Neither warn nor fail about this rule, given by code such as PNGME004 or by name such as skipped-file
+    #[structopt(long, global = true, number_of_values = 1)]
+    pub ignore: Vec<Rule>,
+
+    /// Make every PNG file written conform to a profile: web-safe for 8 bit non-interlaced files with standard chunks
+    #[structopt(long, global = true)]
+    pub profile: Option<Profile>,
+
+    /// Read this file out of the ZIP archives, such as EPUB books or Office documents, given in place of PNG files -
+    /// archive.zip!inner/path.png does the same for a single file
+    #[structopt(long, global = true)]
+    pub zip_member: Option<String>,
+
+    /// Write numbers, sizes and dates in text output as in this locale, such as de_DE or en-GB, instead of the one
+    /// named by LC_ALL, LC_NUMERIC or LANG - json, csv and cbor output never depend on the locale
+    #[structopt(long, global = true)]
+    pub locale: Option<Locale>
+}
+
+impl Opt {
+    /// Format the results are shown in, json when `capabilities --json` asks for it
+    pub fn output_format(&self) -> OutputFormat {
+        match self.subcommand {
+            Subcommand::Capabilities(Capabilities { json: true }) => OutputFormat::Json,
+            _ => self.format
+        }
+    }
+
+    /// Settings the command runs with, opening the event stream if one was requested
+    pub fn settings(&self) -> Result<Settings> {
+        let events = match &self.events {
+            Some(target) => Events::new(target)?,
+            None => Events::default()
+        };
+
+        let deterministic = self.deterministic || env::var_os(DETERMINISTIC_VAR).is_some_and(|value| !value.is_empty());
+
+        // The locale of the environment differs between machines, so deterministic output uses C unless told otherwise
+        let locale = match (self.locale, deterministic) {
+            (Some(locale), _) => locale,
+            (None, true) => Locale::C,
+            (None, false) => Locale::from_env()
+        };
+
+        Ok(Settings {
+            policy: Policy { strict: self.strict, allowed: self.allow.clone(), ignored: self.ignore.clone() },
+            locale,
+            profile: self.profile,
+            format: self.output_format(),
+            events,
+            deterministic
+        })
+    }
 }
 
 #[cfg(test)]
@@ -81,7 +712,17 @@ mod test {
             filepath: PathBuf::from("./dice.png"),
             chunk_type: ChunkType::from_str("ruSt").unwrap(),
             message: String::from("This is a test"),
-            output_file: None
+            output_file: None,
+            sign: None,
+            position: ChunkPosition::BeforeIend,
+            mode: Mode::Chunk,
+            force: false,
+            replace: false,
+            compress: Compression::None,
+            dict: None,
+            delta: false,
+            seal: false,
+            par2_out: None
         });
 
         let opt = Opt::from_iter(vec![
@@ -104,7 +745,17 @@ mod test {
             filepath: PathBuf::from("./dice.png"),
             chunk_type: ChunkType::from_str("ruSt").unwrap(),
             message: String::from("This is a test"),
-            output_file: Some(PathBuf::from("./output.png"))
+            output_file: Some(PathBuf::from("./output.png")),
+            sign: None,
+            position: ChunkPosition::BeforeIend,
+            mode: Mode::Chunk,
+            force: false,
+            replace: false,
+            compress: Compression::None,
+            dict: None,
+            delta: false,
+            seal: false,
+            par2_out: None
         });
 
         let opt = Opt::from_iter(vec![
@@ -123,17 +774,32 @@ mod test {
     }
 
     #[test]
-    fn test_decode() {
-        let expected = Subcommand::Decode(Decode {
+    fn test_encode_with_signature() {
+        let expected = Subcommand::Encode(Encode {
             filepath: PathBuf::from("./dice.png"),
             chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            message: String::from("This is a test"),
+            output_file: None,
+            sign: Some(PathBuf::from("./secret.key")),
+            position: ChunkPosition::BeforeIend,
+            mode: Mode::Chunk,
+            force: false,
+            replace: false,
+            compress: Compression::None,
+            dict: None,
+            delta: false,
+            seal: false,
+            par2_out: None
         });
 
         let opt = Opt::from_iter(vec![
             "pngme", 
-            "decode", 
+            "encode", 
             "./dice.png", 
-            "ruSt"
+            "ruSt", 
+            "This is a test",
+            "--sign",
+            "./secret.key"
         ]);
 
         let actual = opt.subcommand;
@@ -143,17 +809,32 @@ mod test {
     }
 
     #[test]
-    fn test_remove() {
-        let expected = Subcommand::Remove(Remove {
+    fn test_encode_with_position() {
+        let expected = Subcommand::Encode(Encode {
             filepath: PathBuf::from("./dice.png"),
             chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            message: String::from("This is a test"),
+            output_file: None,
+            sign: None,
+            position: ChunkPosition::Index(2),
+            mode: Mode::Chunk,
+            force: false,
+            replace: false,
+            compress: Compression::None,
+            dict: None,
+            delta: false,
+            seal: false,
+            par2_out: None
         });
 
         let opt = Opt::from_iter(vec![
             "pngme", 
-            "remove", 
+            "encode", 
             "./dice.png", 
-            "ruSt"
+            "ruSt", 
+            "This is a test",
+            "--position",
+            "index:2"
         ]);
 
         let actual = opt.subcommand;
@@ -163,15 +844,32 @@ mod test {
     }
 
     #[test]
-    fn test_print() {
-        let expected = Subcommand::Print(Print {
-            filepath: PathBuf::from("./output.png")
+    fn test_encode_with_lsb_mode() {
+        let expected = Subcommand::Encode(Encode {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            message: String::from("This is a test"),
+            output_file: None,
+            sign: None,
+            position: ChunkPosition::BeforeIend,
+            mode: Mode::Lsb,
+            force: false,
+            replace: false,
+            compress: Compression::None,
+            dict: None,
+            delta: false,
+            seal: false,
+            par2_out: None
         });
 
         let opt = Opt::from_iter(vec![
             "pngme", 
-            "print", 
-            "./output.png"
+            "encode", 
+            "./dice.png", 
+            "ruSt", 
+            "This is a test",
+            "--mode",
+            "lsb"
         ]);
 
         let actual = opt.subcommand;
@@ -180,6 +878,865 @@ mod test {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_encode_with_force_and_replace() {
+        let expected = Subcommand::Encode(Encode {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("tEXt").unwrap(),
+            message: String::from("This is a test"),
+            output_file: None,
+            sign: None,
+            position: ChunkPosition::BeforeIend,
+            mode: Mode::Chunk,
+            force: true,
+            replace: true,
+            compress: Compression::None,
+            dict: None,
+            delta: false,
+            seal: false,
+            par2_out: None
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "encode", 
+            "./dice.png", 
+            "tEXt", 
+            "This is a test",
+            "--force",
+            "--replace"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_encode_with_compression() {
+        let opt = Opt::from_iter(vec![
+            "pngme", "encode", "./dice.png", "ruSt", "{}", "--compress", "zstd", "--dict", "builtin-text"
+        ]);
+
+        match opt.subcommand {
+            Subcommand::Encode(encode) => {
+                assert_eq!(encode.compress, Compression::Zstd);
+                assert_eq!(encode.dict, Some(Dictionary::BuiltinText));
+            },
+            subcommand => panic!("Expected encode, got {:?}", subcommand)
+        }
+
+        assert!(Opt::from_iter_safe(vec!["pngme", "encode", "./dice.png", "ruSt", "{}", "--compress", "lzma"]).is_err());
+    }
+
+    #[test]
+    fn test_encode_delta_conflicts_with_replace() {
+        assert!(Opt::from_iter_safe(vec!["pngme", "encode", "./dice.png", "ruSt", "{}", "--delta"]).is_ok());
+        assert!(Opt::from_iter_safe(vec!["pngme", "encode", "./dice.png", "ruSt", "{}", "--delta", "--replace"]).is_err());
+    }
+
+    #[test]
+    fn test_encode_seal() {
+        let opt = Opt::from_iter(vec!["pngme", "encode", "./dice.png", "ruSt", "Hi", "--seal"]);
+
+        match opt.subcommand {
+            Subcommand::Encode(encode) => assert!(encode.seal),
+            subcommand => panic!("Expected encode, got {:?}", subcommand)
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let expected = Subcommand::Decode(Decode {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            verify: None,
+            mode: Mode::Chunk,
+            output: None,
+            raw: false,
+            scan: None,
+            scan_policy: ScanPolicy::Block
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "decode", 
+            "./dice.png", 
+            "ruSt"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_decode_with_verification() {
+        let expected = Subcommand::Decode(Decode {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            verify: Some(PathBuf::from("./secret.key")),
+            mode: Mode::Chunk,
+            output: None,
+            raw: false,
+            scan: None,
+            scan_policy: ScanPolicy::Block
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "decode", 
+            "./dice.png", 
+            "ruSt",
+            "--verify",
+            "./secret.key"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_decode_with_output() {
+        let expected = Subcommand::Decode(Decode {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            verify: None,
+            mode: Mode::Chunk,
+            output: Some(PathBuf::from("./message.bin")),
+            raw: false,
+            scan: None,
+            scan_policy: ScanPolicy::Block
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "decode", 
+            "./dice.png", 
+            "ruSt",
+            "--output",
+            "./message.bin"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_decode_with_scan() {
+        let expected = Subcommand::Decode(Decode {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            verify: None,
+            mode: Mode::Chunk,
+            output: None,
+            raw: true,
+            scan: Some(Scanner::from_str("clamscan --no-summary -").unwrap()),
+            scan_policy: ScanPolicy::Flag
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme",
+            "decode",
+            "./dice.png",
+            "ruSt",
+            "--raw",
+            "--scan",
+            "clamscan --no-summary -",
+            "--scan-policy",
+            "flag"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_decode_raw_conflicts_with_output() {
+        let result = Opt::from_iter_safe(vec![
+            "pngme",
+            "decode",
+            "./dice.png",
+            "ruSt",
+            "--raw",
+            "--output",
+            "./message.bin"
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_assert() {
+        let expected = Subcommand::Assert(Assert {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            verify: None,
+            equals_file: None,
+            equals: None,
+            matches: Some(String::from("^build-[0-9]+$"))
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "assert", 
+            "./dice.png", 
+            "ruSt",
+            "--matches",
+            "^build-[0-9]+$"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_assert_needs_a_single_expectation() {
+        let result = Opt::from_iter_safe(vec!["pngme", "assert", "./dice.png", "ruSt"]);
+        assert!(result.is_err());
+
+        let result = Opt::from_iter_safe(vec![
+            "pngme",
+            "assert",
+            "./dice.png",
+            "ruSt",
+            "--equals",
+            "This is a test",
+            "--equals-file",
+            "./expected.bin"
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let expected = Subcommand::Remove(Remove {
+            filepath: PathBuf::from("./dice.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "remove", 
+            "./dice.png", 
+            "ruSt"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_print() {
+        let expected = Subcommand::Print(Print {
+            filepath: PathBuf::from("./output.png"),
+            types: vec![],
+            critical_only: false,
+            ancillary_only: false,
+            no_data: false,
+            hex: false,
+            full: false
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "print", 
+            "./output.png"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_print_with_filters() {
+        let expected = Subcommand::Print(Print {
+            filepath: PathBuf::from("./output.png"),
+            types: vec![String::from("tEXt"), String::from("*TXt")],
+            critical_only: false,
+            ancillary_only: true,
+            no_data: false,
+            hex: true,
+            full: true
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "print", 
+            "./output.png",
+            "--type",
+            "tEXt,*TXt",
+            "--ancillary-only",
+            "--hex",
+            "--full"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+
+        let result = Opt::from_iter_safe(vec![
+            "pngme",
+            "print",
+            "./output.png",
+            "--critical-only",
+            "--ancillary-only"
+        ]);
+
+        assert!(result.is_err());
+
+        let result = Opt::from_iter_safe(vec!["pngme", "print", "./output.png", "--no-data", "--hex"]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = Subcommand::Diff(Diff {
+            first: PathBuf::from("./dice.png"),
+            second: PathBuf::from("./output.png"),
+            ignore_idat: true,
+            side_by_side: true
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "diff", 
+            "./dice.png",
+            "./output.png",
+            "--ignore-idat",
+            "--side-by-side"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_protect() {
+        let expected = Subcommand::Protect(Protect {
+            filepath: PathBuf::from("./dice.png"),
+            output_file: None,
+            chunks: vec![ChunkType::from_str("tEXt").unwrap(), ChunkType::from_str("eXIf").unwrap()],
+            password: String::from("secret")
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme",
+            "protect",
+            "./dice.png",
+            "--chunks",
+            "tEXt,eXIf",
+            "--password",
+            "secret"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+
+        let result = Opt::from_iter_safe(vec!["pngme", "protect", "./dice.png", "--password", "secret"]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unprotect() {
+        let expected = Subcommand::Unprotect(Unprotect {
+            filepath: PathBuf::from("./dice.png"),
+            output_file: Some(PathBuf::from("./output.png")),
+            password: String::from("secret")
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme",
+            "unprotect",
+            "./dice.png",
+            "./output.png",
+            "--password",
+            "secret"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_import_metadata() {
+        let expected = Subcommand::ImportMetadata(ImportMetadata {
+            filepath: PathBuf::from("./dice.png"),
+            output_file: Some(PathBuf::from("./output.png")),
+            from: PathBuf::from("./meta.json")
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme",
+            "import-metadata",
+            "./dice.png",
+            "./output.png",
+            "--from",
+            "./meta.json"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_export_metadata() {
+        let expected = Subcommand::ExportMetadata(ExportMetadata {
+            filepath: PathBuf::from("./dice.png"),
+            to: Some(PathBuf::from("./meta.json"))
+        });
+
+        let opt = Opt::from_iter(vec!["pngme", "export-metadata", "./dice.png", "--to", "./meta.json"]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_sync_metadata() {
+        let expected = Subcommand::SyncMetadata(SyncMetadata {
+            from: PathBuf::from("./originals"),
+            to: PathBuf::from("./processed"),
+            match_by: MatchBy::Name,
+            dry_run: true,
+            if_writable: true,
+            walk: Walk::default()
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme",
+            "sync-metadata",
+            "--from",
+            "./originals",
+            "--to",
+            "./processed",
+            "--match-by",
+            "name",
+            "--dry-run",
+            "--if-writable"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let expected = Subcommand::Quantize(Quantize {
+            filepath: PathBuf::from("./dice.png"),
+            output_file: Some(PathBuf::from("./output.png")),
+            colors: 16,
+            dither: true
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "quantize", 
+            "./dice.png",
+            "./output.png",
+            "--colors",
+            "16",
+            "--dither"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let expected = Subcommand::Normalize(Normalize {
+            filepath: PathBuf::from("./dice.png"),
+            output_file: None,
+            max_size: 65536
+        });
+
+        let opt = Opt::from_iter(vec!["pngme", "normalize", "./dice.png", "--max-size", "65536"]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_wizard() {
+        let opt = Opt::from_iter(vec!["pngme", "wizard"]);
+
+        assert_eq!(opt.subcommand, Subcommand::Wizard);
+        assert_eq!(opt.subcommand.name(), "wizard");
+    }
+
+    #[test]
+    fn test_demo() {
+        let expected = Subcommand::Demo(Demo {
+            directory: Some(PathBuf::from("./demo")),
+            message: String::from("Hello from pngme!")
+        });
+
+        let opt = Opt::from_iter(vec!["pngme", "demo", "--directory", "./demo"]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_strict() {
+        let opt = Opt::from_iter(vec![
+            "pngme", "encode", "./dice.png", "ruSt", "Hi", "--strict", "--allow", "trailing-data"
+        ]);
+        assert!(opt.strict);
+        assert_eq!(opt.allow, vec![Rule::TrailingData]);
+
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png"]);
+        assert!(!opt.strict);
+        assert!(opt.allow.is_empty());
+
+        assert!(Opt::from_iter_safe(vec!["pngme", "print", "./dice.png", "--allow", "everything"]).is_err());
+
+        let opt = Opt::from_iter(vec![
+            "pngme", "pick-carrier", ".", "--payload-size", "16", "--ignore", "PNGME004", "--ignore", "flagged-payload"
+        ]);
+        assert_eq!(opt.ignore, vec![Rule::SkippedFile, Rule::FlaggedPayload]);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let opt = Opt::from_iter(vec!["pngme", "--deterministic", "print", "./dice.png"]);
+        assert!(opt.settings().unwrap().deterministic);
+        assert_eq!(opt.settings().unwrap().locale, Locale::C);
+
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png", "--deterministic", "--locale", "de"]);
+        assert_eq!(opt.settings().unwrap().locale, Locale::from_str("de").unwrap());
+    }
+
+    #[test]
+    fn test_force_binary() {
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png", "--format", "cbor", "--force-binary"]);
+        assert!(opt.force_binary);
+
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png", "--format", "cbor"]);
+        assert!(!opt.force_binary);
+    }
+
+    #[test]
+    fn test_events() {
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png", "--events", "/dev/fd/3"]);
+
+        assert_eq!(opt.events, Some(EventTarget::File(PathBuf::from("/dev/fd/3"))));
+        assert_eq!(opt.subcommand.name(), "print");
+
+        let opt = Opt::from_iter(vec!["pngme", "--events", "stderr", "frames", "export", "./dice.apng", "-o", "./frames"]);
+
+        assert_eq!(opt.events, Some(EventTarget::Stderr));
+        assert_eq!(opt.subcommand.name(), "frames export");
+    }
+
+    #[test]
+    fn test_format() {
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png"]);
+        assert_eq!(opt.format, OutputFormat::Text);
+
+        let opt = Opt::from_iter(vec!["pngme", "--format", "csv", "diff", "./dice.png", "./output.png"]);
+        assert_eq!(opt.format, OutputFormat::Csv);
+
+        assert!(Opt::from_iter_safe(vec!["pngme", "print", "./dice.png", "--format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_profile() {
+        let opt = Opt::from_iter(vec!["pngme", "remove", "./dice.png", "ruSt", "--profile", "web-safe"]);
+        assert_eq!(opt.profile, Some(Profile::WebSafe));
+
+        let opt = Opt::from_iter(vec!["pngme", "remove", "./dice.png", "ruSt"]);
+        assert_eq!(opt.profile, None);
+
+        assert!(Opt::from_iter_safe(vec!["pngme", "print", "./dice.png", "--profile", "web"]).is_err());
+    }
+
+    #[test]
+    fn test_zip_member() {
+        let opt = Opt::from_iter(vec!["pngme", "print", "./report.docx", "--zip-member", "word/media/image1.png"]);
+        assert_eq!(opt.zip_member, Some(String::from("word/media/image1.png")));
+
+        let opt = Opt::from_iter(vec!["pngme", "print", "./report.docx!word/media/image1.png"]);
+        assert_eq!(opt.zip_member, None);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let opt = Opt::from_iter(vec!["pngme", "capabilities", "--json"]);
+        assert_eq!(opt.subcommand, Subcommand::Capabilities(Capabilities { json: true }));
+        assert_eq!(opt.output_format(), OutputFormat::Json);
+
+        let opt = Opt::from_iter(vec!["pngme", "capabilities", "--format", "csv"]);
+        assert_eq!(opt.output_format(), OutputFormat::Csv);
+
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png", "--format", "json"]);
+        assert_eq!(opt.output_format(), OutputFormat::Json);
+    }
+
+    #[test]
+    fn test_locale() {
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png", "--locale", "de_DE.UTF-8"]);
+        assert_eq!(opt.locale, Some(Locale::from_str("de").unwrap()));
+
+        let opt = Opt::from_iter(vec!["pngme", "print", "./dice.png"]);
+        assert_eq!(opt.locale, None);
+
+        assert!(Opt::from_iter_safe(vec!["pngme", "print", "./dice.png", "--locale", "tlh"]).is_err());
+    }
+
+    #[test]
+    fn test_salvage() {
+        let expected = Subcommand::Salvage(Salvage {
+            filepath: PathBuf::from("./partial.png"),
+            output_file: Some(PathBuf::from("./salvaged.png")),
+            pad_image_data: true
+        });
+
+        let opt = Opt::from_iter(vec!["pngme", "salvage", "./partial.png", "./salvaged.png", "--pad-image-data"]);
+
+        assert_eq!(expected, opt.subcommand);
+    }
+
+    #[test]
+    fn test_recover() {
+        let expected = Subcommand::Recover(Recover {
+            filepath: PathBuf::from("./dice.png"),
+            parity_file: PathBuf::from("./recovery.par2"),
+            output_file: None
+        });
+
+        let opt = Opt::from_iter(vec!["pngme", "recover", "./dice.png", "./recovery.par2"]);
+
+        assert_eq!(expected, opt.subcommand);
+
+        let opt = Opt::from_iter(vec!["pngme", "encode", "./dice.png", "ruSt", "Hi", "--par2-out", "./recovery.par2"]);
+
+        match opt.subcommand {
+            Subcommand::Encode(encode) => assert_eq!(encode.par2_out, Some(PathBuf::from("./recovery.par2"))),
+            subcommand => panic!("Expected encode, got {:?}", subcommand)
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let expected = Subcommand::Validate(Validate { filepath: PathBuf::from("./dice.png") });
+
+        let opt = Opt::from_iter(vec!["pngme", "validate", "./dice.png"]);
+
+        assert_eq!(expected, opt.subcommand);
+    }
+
+    #[test]
+    fn test_repair() {
+        let expected = Subcommand::Repair(Repair {
+            filepath: PathBuf::from("./dice.png"),
+            output_file: None,
+            width: Some(640),
+            height: Some(480),
+            bit_depth: 8,
+            color_type: 2,
+            interlaced: false,
+            fix_adler: false
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme",
+            "repair",
+            "./dice.png",
+            "--width",
+            "640",
+            "--height",
+            "480",
+            "--color-type",
+            "2"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+
+        let result = Opt::from_iter_safe(vec!["pngme", "repair", "./dice.png", "--width", "640"]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_rename_by() {
+        let expected = Subcommand::RenameBy(RenameBy {
+            directory: PathBuf::from("./photos"),
+            pattern: Pattern::from_str("{keyword:Title}_{date}.png").unwrap(),
+            dry_run: true,
+            on_collision: Collision::Suffix,
+            walk: Walk { follow_symlinks: true, ..Walk::default() }
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "rename-by", 
+            "--pattern",
+            "{keyword:Title}_{date}.png",
+            "--dry-run",
+            "--follow-symlinks",
+            "./photos"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_pick_carrier() {
+        let expected = Subcommand::PickCarrier(PickCarrier {
+            directory: PathBuf::from("./photos"),
+            payload_size: PayloadSize(2 * 1024 * 1024),
+            count: 5,
+            walk: Walk { by_content: true, one_file_system: true, ..Walk::default() }
+        });
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "pick-carrier", 
+            "./photos",
+            "--payload-size",
+            "2MiB",
+            "--by-content",
+            "--one-file-system"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_frames_export() {
+        let expected = Subcommand::Frames(Frames::Export(ExportFrames {
+            filepath: PathBuf::from("./animation.png"),
+            output: PathBuf::from("./frames")
+        }));
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "frames", 
+            "export", 
+            "./animation.png",
+            "-o",
+            "./frames"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_frames_import() {
+        let expected = Subcommand::Frames(Frames::Import(ImportFrames {
+            frames: vec![PathBuf::from("./frame_0.png"), PathBuf::from("./frame_1.png")],
+            manifest: None,
+            output: PathBuf::from("./animation.png"),
+            delay: vec![100, 250],
+            plays: 0
+        }));
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "frames", 
+            "import", 
+            "./frame_0.png",
+            "./frame_1.png",
+            "-o",
+            "./animation.png",
+            "--delay",
+            "100,250"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_frames_import_with_manifest() {
+        let expected = Subcommand::Frames(Frames::Import(ImportFrames {
+            frames: vec![],
+            manifest: Some(PathBuf::from("./frames/manifest.json")),
+            output: PathBuf::from("./animation.png"),
+            delay: vec![100],
+            plays: 0
+        }));
+
+        let opt = Opt::from_iter(vec![
+            "pngme", 
+            "frames", 
+            "import", 
+            "--manifest",
+            "./frames/manifest.json",
+            "-o",
+            "./animation.png"
+        ]);
+
+        let actual = opt.subcommand;
+        println!("{:?}", actual);
+
+        assert_eq!(expected, actual);
+
+        let result = Opt::from_iter_safe(vec![
+            "pngme",
+            "frames",
+            "import",
+            "./frame_0.png",
+            "--manifest",
+            "./frames/manifest.json",
+            "-o",
+            "./animation.png"
+        ]);
+
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_unknown_subcommand() {
         let result = Opt::from_iter_safe(vec!["pngme", "add", "./dice.png"]);
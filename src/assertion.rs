@@ -0,0 +1,131 @@
+use regex::bytes::Regex;
+use std::error;
+use std::fmt::{self, Display};
+
+use crate::Result;
+
+/// What a chunk's data is expected to be
+#[derive(Debug, Clone)]
+pub enum Expectation {
+    /// Data is exactly these bytes
+    Equals(Vec<u8>),
+
+    /// Data contains a match of this regular expression
+    Matches(Regex)
+}
+
+impl Expectation {
+    /// Expect data to contain a match of the given regular expression
+    pub fn matching(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|error| AssertionError::InvalidPattern(error.to_string()))?;
+
+        Ok(Expectation::Matches(regex))
+    }
+
+    /// Make sure the data meets the expectation
+    pub fn check(&self, data: &[u8]) -> Result<()> {
+        match self {
+            Expectation::Equals(expected) if expected == data => Ok(()),
+            Expectation::Equals(expected) => {
+                // Point at the first byte that differs, or at the end of the shortest one
+                let position = expected
+                    .iter()
+                    .zip(data.iter())
+                    .position(|(a, b)| a != b)
+                    .unwrap_or_else(|| expected.len().min(data.len()));
+
+                Err(AssertionError::NotEqual {
+                    expected_length: expected.len(),
+                    actual_length: data.len(),
+                    position
+                }
+                .into())
+            },
+            Expectation::Matches(regex) if regex.is_match(data) => Ok(()),
+            Expectation::Matches(regex) => Err(AssertionError::NoMatch(regex.to_string()).into())
+        }
+    }
+}
+
+/// Assertion errors
+#[derive(Debug)]
+pub enum AssertionError {
+    /// Regular expression could not be compiled
+    InvalidPattern(String),
+
+    /// Data differs from the expected bytes
+    NotEqual {
+        expected_length: usize,
+        actual_length: usize,
+
+        /// Index of the first byte that differs
+        position: usize
+    },
+
+    /// Data has no match of the regular expression
+    NoMatch(String)
+}
+
+impl error::Error for AssertionError {}
+
+impl Display for AssertionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssertionError::InvalidPattern(error) => write!(f, "Invalid regular expression: {}", error),
+            AssertionError::NotEqual { expected_length, actual_length, position } if expected_length == actual_length => {
+                write!(f, "Data differs from the expected value at byte {}", position)
+            },
+            AssertionError::NotEqual { expected_length, actual_length, position } => write!(
+                f,
+                "Expected {} bytes but found {} bytes, first difference at byte {}",
+                expected_length, actual_length, position
+            ),
+            AssertionError::NoMatch(pattern) => write!(f, "Data does not match '{}'", pattern)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, PngmeError};
+
+    #[test]
+    fn test_equals() {
+        let expectation = Expectation::Equals(b"This is a test".to_vec());
+
+        assert!(expectation.check(b"This is a test").is_ok());
+        assert!(matches!(
+            expectation.check(b"This is a tent"),
+            Err(PngmeError::Assertion(AssertionError::NotEqual { position: 12, .. }))
+        ));
+        assert!(matches!(
+            expectation.check(b"This is"),
+            Err(PngmeError::Assertion(AssertionError::NotEqual { position: 7, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_matches() {
+        let expectation = Expectation::matching(r"^build-\d+$").unwrap();
+
+        assert!(expectation.check(b"build-42").is_ok());
+        assert!(expectation.check(b"build-").is_err());
+        assert!(expectation.check(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(Expectation::matching("build-(").is_err());
+    }
+
+    #[test]
+    fn test_failed_assertion_kind() {
+        let error = Expectation::Equals(vec![1]).check(&[2]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AssertionFailed);
+
+        let error = Expectation::matching("(").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,127 @@
+use serde_json::{json, Value};
+
+use crate::chunk::Chunk;
+use crate::compress::{Compression, Dictionary};
+use crate::diagnostics::Rule;
+use crate::events;
+use crate::image_data;
+use crate::locale::Locale;
+use crate::output::{self, OutputFormat, Record};
+use crate::parity;
+use crate::print;
+use crate::profile::Profile;
+use crate::stego::Mode;
+
+/// Version of the capabilities record, raised whenever one of its fields is renamed or removed
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Optional features this binary was built with, so wrappers can check for one before relying on it
+///
+/// Every feature is built in for now, but a feature left out of a build is left out of this list
+const FEATURES: [&str; 8] = ["apng", "archives", "compression", "crypto", "delta", "lsb", "parity", "seal"];
+
+/// Kinds of archive PNG files can be read out of
+const ARCHIVES: [&str; 1] = ["zip"];
+
+/// What this binary supports: its version, features, accepted values, limits and the versions of its output schemas,
+/// with the limits in the text written as in the locale
+pub fn record(locale: Locale) -> Record {
+    let output_formats: Vec<&str> = OutputFormat::ALL.iter().map(OutputFormat::name).collect();
+    let compression: Vec<&str> = Compression::ALL.iter().map(Compression::name).collect();
+    let dictionaries: Vec<&str> = Dictionary::ALL.iter().map(Dictionary::name).collect();
+    let modes: Vec<&str> = Mode::ALL.iter().map(Mode::name).collect();
+    let profiles: Vec<&str> = Profile::ALL.iter().map(Profile::name).collect();
+    let rules: Vec<Value> = Rule::ALL.iter().map(|rule| json!({ "code": rule.code(), "name": rule.name() })).collect();
+
+    let text = [
+        format!("pngme {}", env!("CARGO_PKG_VERSION")),
+        format!("features: {}", FEATURES.join(", ")),
+        format!("output formats: {}", output_formats.join(", ")),
+        format!("compression: {} (dictionaries: {})", compression.join(", "), dictionaries.join(", ")),
+        format!("modes: {}", modes.join(", ")),
+        format!("profiles: {}", profiles.join(", ")),
+        format!("archives: {}", ARCHIVES.join(", ")),
+        format!("max chunk length: {} bytes", locale.number(Chunk::MAX_LENGTH.into())),
+        format!("max parity shards: {}", locale.number(parity::MAX_DATA_SHARDS as u64)),
+        format!(
+            "schemas: capabilities {}, output {}, events {}",
+            SCHEMA_VERSION,
+            output::SCHEMA_VERSION,
+            events::SCHEMA_VERSION
+        )
+    ];
+
+    Record::new(text.join("\n"))
+        .field("version", env!("CARGO_PKG_VERSION"))
+        .field("schema_version", SCHEMA_VERSION)
+        .field("features", FEATURES.to_vec())
+        .field(
+            "formats",
+            json!({
+                "output": output_formats,
+                "compression": compression,
+                "dictionaries": dictionaries,
+                "modes": modes,
+                "profiles": profiles,
+                "archives": ARCHIVES
+            })
+        )
+        .field(
+            "limits",
+            json!({
+                "max_chunk_length": Chunk::MAX_LENGTH,
+                "idat_chunk_size": image_data::IDAT_CHUNK_SIZE,
+                "hex_dump_limit": print::HEX_DUMP_LIMIT,
+                "max_parity_shards": parity::MAX_DATA_SHARDS
+            })
+        )
+        .field(
+            "schemas",
+            json!({
+                "capabilities": SCHEMA_VERSION,
+                "output": output::SCHEMA_VERSION,
+                "events": events::SCHEMA_VERSION
+            })
+        )
+        .field("rules", rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Field;
+    use std::str::FromStr;
+
+    fn field<'a>(record: &'a Record, name: &str) -> &'a Value {
+        match record.fields.iter().find(|(field, _)| field == name) {
+            Some((_, Field::Value(value))) => value,
+            _ => panic!("no {} field", name)
+        }
+    }
+
+    fn names(value: &Value) -> Vec<&str> {
+        value.as_array().unwrap().iter().map(|name| name.as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_values_are_accepted() {
+        let record = record(Locale::C);
+        let formats = field(&record, "formats");
+
+        // Every value listed is one the command line accepts
+        assert!(names(&formats["output"]).into_iter().all(|name| OutputFormat::from_str(name).is_ok()));
+        assert!(names(&formats["compression"]).into_iter().all(|name| Compression::from_str(name).is_ok()));
+        assert!(names(&formats["dictionaries"]).into_iter().all(|name| Dictionary::from_str(name).is_ok()));
+        assert!(names(&formats["modes"]).into_iter().all(|name| Mode::from_str(name).is_ok()));
+        assert!(names(&formats["profiles"]).into_iter().all(|name| Profile::from_str(name).is_ok()));
+        for rule in field(&record, "rules").as_array().unwrap() {
+            assert!(Rule::from_str(rule["code"].as_str().unwrap()).is_ok());
+            assert!(Rule::from_str(rule["name"].as_str().unwrap()).is_ok());
+        }
+
+        assert_eq!(names(&formats["modes"]), ["chunk", "lsb"]);
+        assert!(names(field(&record, "features")).contains(&"crypto"));
+        assert_eq!(field(&record, "schema_version"), &json!(SCHEMA_VERSION));
+        assert_eq!(field(&record, "limits")["max_chunk_length"], json!(Chunk::MAX_LENGTH));
+    }
+}
@@ -0,0 +1,166 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::locale::Locale;
+use crate::{Error, Result};
+
+/// Units accepted after a payload size, with their size in bytes
+const UNITS: [(&str, u64); 7] = [
+    ("b", 1),
+    ("kb", 1000),
+    ("mb", 1000 * 1000),
+    ("gb", 1000 * 1000 * 1000),
+    ("kib", 1024),
+    ("mib", 1024 * 1024),
+    ("gib", 1024 * 1024 * 1024)
+];
+
+/// Size of a payload in bytes, parsed from strings such as `512`, `64KB` or `2MiB`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PayloadSize(pub u64);
+
+impl FromStr for PayloadSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || CarrierError::InvalidPayloadSize(s.to_string());
+
+        let split = s
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let unit = unit.trim().to_ascii_lowercase();
+
+        let multiplier = match unit.as_str() {
+            "" => 1,
+            unit => UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(invalid)?
+        };
+
+        number
+            .checked_mul(multiplier)
+            .map(PayloadSize)
+            .ok_or_else(|| invalid().into())
+    }
+}
+
+/// An image that could carry a payload in its pixels
+#[derive(Debug, PartialEq, Clone)]
+pub struct Candidate {
+    pub filepath: PathBuf,
+
+    /// Number of message bytes the image can hide
+    pub capacity: usize,
+
+    /// Average difference between neighbouring samples
+    pub noise: f64
+}
+
+impl Candidate {
+    /// Path, capacity and noise of the image as shown to a person, with numbers written as in the locale
+    pub fn describe(&self, locale: Locale) -> String {
+        format!(
+            "{} (capacity: {} bytes, noise: {})",
+            self.filepath.display(),
+            locale.number(self.capacity as u64),
+            locale.decimal(self.noise, 2)
+        )
+    }
+}
+
+/// Candidates able to hide the payload, noisiest first and then largest first
+pub fn rank(candidates: Vec<Candidate>, payload_size: PayloadSize) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.capacity as u64 >= payload_size.0)
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.noise
+            .partial_cmp(&a.noise)
+            .unwrap_or(Ordering::Equal)
+            .then(b.capacity.cmp(&a.capacity))
+    });
+
+    candidates
+}
+
+/// Carrier selection errors
+#[derive(Debug)]
+pub enum CarrierError {
+    /// Payload size is not a number followed by an optional unit
+    InvalidPayloadSize(String)
+}
+
+impl error::Error for CarrierError {}
+
+impl Display for CarrierError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CarrierError::InvalidPayloadSize(size) => write!(
+                f,
+                "Invalid payload size '{}', expected a number of bytes optionally followed by KB, MB, GB, KiB, MiB or GiB",
+                size
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, capacity: usize, noise: f64) -> Candidate {
+        Candidate {
+            filepath: PathBuf::from(name),
+            capacity,
+            noise
+        }
+    }
+
+    #[test]
+    fn test_payload_size_from_str() {
+        assert_eq!(PayloadSize::from_str("512").unwrap(), PayloadSize(512));
+        assert_eq!(PayloadSize::from_str("64KB").unwrap(), PayloadSize(64_000));
+        assert_eq!(PayloadSize::from_str("2MiB").unwrap(), PayloadSize(2 * 1024 * 1024));
+        assert_eq!(PayloadSize::from_str("1 gib").unwrap(), PayloadSize(1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_invalid_payload_size() {
+        assert!(PayloadSize::from_str("").is_err());
+        assert!(PayloadSize::from_str("MiB").is_err());
+        assert!(PayloadSize::from_str("2PB").is_err());
+        assert!(PayloadSize::from_str("1.5MiB").is_err());
+        assert!(PayloadSize::from_str("99999999999999GiB").is_err());
+    }
+
+    #[test]
+    fn test_rank() {
+        let candidates = vec![
+            candidate("small.png", 10, 50.0),
+            candidate("flat.png", 1000, 2.0),
+            candidate("noisy.png", 500, 30.0),
+            candidate("noisy_large.png", 2000, 30.0)
+        ];
+
+        let ranked = rank(candidates, PayloadSize(100));
+
+        assert_eq!(
+            ranked,
+            vec![
+                candidate("noisy_large.png", 2000, 30.0),
+                candidate("noisy.png", 500, 30.0),
+                candidate("flat.png", 1000, 2.0)
+            ]
+        );
+    }
+}
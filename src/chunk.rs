@@ -1,42 +1,67 @@
 use crc::crc32;
 use std::fmt::{self, Display};
 use std::convert::{TryFrom, TryInto};
+use std::ops::Range;
 use std::str;
 use std::error;
+use std::sync::Arc;
 
 use crate::{Error, Result};
+use crate::arena::ChunkData;
 use crate::chunk_type::ChunkType;
 
+/// Chunk types accepted when parsing chunks
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChunkTypes {
+    /// Only ASCII letters with the reserved bit unset, as the PNG spec requires
+    Valid,
+
+    /// Any 4 bytes, so the chunks of corrupted files can still be printed and removed
+    Any
+}
+
 /// Represents a single chunk in the PNG spec
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
-    data: Vec<u8>,
+    data: ChunkData,
     crc: u32
 }
 
 impl Chunk {
-    /// Create new chunk
+    /// Largest length a chunk's data may have (2^31 - 1 bytes)
+    pub const MAX_LENGTH: u32 = i32::MAX as u32;
+
+    /// Create new chunk, whose data must not be longer than `MAX_LENGTH`
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
         let length = data.len() as u32;
-
-        let chunk_data: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect();
-        let crc = crc32::checksum_ieee(&chunk_data);
+        let crc = checksum(&chunk_type, &data);
 
         Self {
             length,
             chunk_type,
-            data,
+            data: ChunkData::Owned(data),
             crc
         }
     }
 
+    /// Parse the chunk the bytes start with, accepting the given chunk types
+    pub fn from_bytes(bytes: &[u8], types: ChunkTypes) -> Result<Self> {
+        let (length, chunk_type, data, crc) = parse(bytes, types)?;
+        let data = ChunkData::Owned(bytes[data].to_vec());
+
+        Ok(Chunk { length, chunk_type, data, crc })
+    }
+
+    /// Parse the chunk starting at the given offset of the arena, pointing into it instead of copying its data
+    pub fn from_arena(arena: &Arc<[u8]>, offset: usize, types: ChunkTypes) -> Result<Self> {
+        let (length, chunk_type, data, crc) = parse(&arena[offset..], types)?;
+        let data = ChunkData::Shared(Arc::clone(arena), offset + data.start..offset + data.end);
+
+        Ok(Chunk { length, chunk_type, data, crc })
+    }
+
     /// Length of the chunk
     pub fn length(&self) -> u32 {
         self.length
@@ -61,7 +86,7 @@ impl Chunk {
     pub fn data_as_string(&self) -> Result<String> {
         match str::from_utf8(&self.data) {
             Ok(s) => Ok(s.to_string()),
-            Err(e) => Err(Box::new(e))
+            Err(e) => Err(e.into())
         }
     }
 
@@ -82,54 +107,62 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        // Throw error if bytes has less than the necessary chunk metadata
-        if bytes.len() < 12 {
-            return Err(Box::from(ChunkError::InputTooSmall));
-        }
+        Chunk::from_bytes(bytes, ChunkTypes::Valid)
+    }
+}
 
-        // Get first 4 bytes which correspond to the chunk's data length
-        let (data_length, bytes) = bytes.split_at(4);
-        let length = u32::from_be_bytes(data_length.try_into()?);
+/// CRC of a chunk, computed over its type and data
+fn checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    crc32::update(crc32::checksum_ieee(&chunk_type.bytes()), &crc32::IEEE_TABLE, data)
+}
 
-        // Get next 4 bytes which correspond to the chunk's type
-        let (chunk_type_bytes, bytes) = bytes.split_at(4);
+/// Length, type, range of the data within the bytes, and CRC of the chunk the bytes start with, once its CRC is checked
+fn parse(bytes: &[u8], types: ChunkTypes) -> Result<(u32, ChunkType, Range<usize>, u32)> {
+    // Throw error if bytes has less than the necessary chunk metadata
+    if bytes.len() < 12 {
+        return Err(ChunkError::InputTooSmall.into());
+    }
 
-        let chunk_type_bytes: [u8; 4] = chunk_type_bytes.try_into()?;
-        let chunk_type = ChunkType::try_from(chunk_type_bytes)?;
+    // Get first 4 bytes which correspond to the chunk's data length
+    let (data_length, bytes) = bytes.split_at(4);
+    let length = u32::from_be_bytes(data_length.try_into()?);
 
-        if !chunk_type.is_valid() {
-            return Err(Box::from(ChunkError::InvalidChunkType));
-        }
+    if length > Chunk::MAX_LENGTH {
+        return Err(ChunkError::LengthTooLarge(length).into());
+    }
 
-        // Get chunk's data and crc from remaining bytes
-        // length refers to the chunk's data length
-        let (data, bytes) = bytes.split_at(length as usize);
-        let (crc, _) = bytes.split_at(4);
+    // Get next 4 bytes which correspond to the chunk's type
+    let (chunk_type_bytes, bytes) = bytes.split_at(4);
 
-        let data: Vec<u8> = data.try_into()?;
-        let crc = u32::from_be_bytes(crc.try_into()?);
-        
-        // Calculate crc from chunk's type and chunk's data
-        let chunk_data: Vec<u8> = chunk_type.bytes()
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect();
+    let chunk_type_bytes: [u8; 4] = chunk_type_bytes.try_into()?;
+    let chunk_type = ChunkType::try_from(chunk_type_bytes)?;
 
-        let actual_crc = crc32::checksum_ieee(&chunk_data);
-        let expected_crc = crc;
+    if types == ChunkTypes::Valid && !chunk_type.is_valid() {
+        return Err(ChunkError::InvalidChunkType.into());
+    }
 
-        if actual_crc != expected_crc {
-            return Err(Box::new(ChunkError::InvalidCrc(expected_crc, actual_crc)));
-        }
-        
-        Ok(Chunk {
-            length,
-            chunk_type,
-            data,
-            crc
-        })
+    // Data and crc must fit in the remaining bytes
+    if bytes.len() - 4 < length as usize {
+        return Err(ChunkError::Truncated(length, bytes.len() - 4).into());
+    }
+
+    // Get chunk's data and crc from remaining bytes
+    // length refers to the chunk's data length
+    let (data, bytes) = bytes.split_at(length as usize);
+    let (crc, _) = bytes.split_at(4);
+
+    let crc = u32::from_be_bytes(crc.try_into()?);
+    
+    // Calculate crc from chunk's type and chunk's data
+    let actual_crc = checksum(&chunk_type, data);
+    let expected_crc = crc;
+
+    if actual_crc != expected_crc {
+        return Err(ChunkError::InvalidCrc(expected_crc, actual_crc).into());
     }
+
+    // Data follows the length and chunk type
+    Ok((length, chunk_type, 8..8 + length as usize, crc))
 }
 
 impl Display for Chunk {
@@ -153,6 +186,12 @@ pub enum ChunkError {
     /// Invalid crc for chunk
     InvalidCrc(u32, u32),
 
+    /// Declared data length is larger than 2^31 - 1 bytes
+    LengthTooLarge(u32),
+
+    /// Declared data length is larger than the bytes left for it
+    Truncated(u32, usize),
+
     /// Invalid chunk type
     InvalidChunkType
 }
@@ -172,6 +211,17 @@ impl Display for ChunkError {
                     expected, actual
                 )
             },
+            ChunkError::LengthTooLarge(length) => write!(
+                f,
+                "Chunk length {} is larger than the maximum of {} bytes",
+                length,
+                Chunk::MAX_LENGTH
+            ),
+            ChunkError::Truncated(length, available) => write!(
+                f,
+                "Chunk length is {} bytes but only {} bytes of data are left",
+                length, available
+            ),
             ChunkError::InvalidChunkType => write!(f, "Invalid chunk type")
         }
     }
@@ -180,6 +230,8 @@ impl Display for ChunkError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::PngmeError;
+    use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
         let data_length: u32 = 42;
@@ -274,6 +326,62 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_invalid_chunk_type() {
+        for chunk_type in [[0x89, 0x50, 0x4E, 0x47], *b"RUst", *b"Ru1t"] {
+            let bytes = Chunk::new(ChunkType::try_from(chunk_type).unwrap(), b"data".to_vec()).as_bytes();
+
+            assert!(matches!(Chunk::try_from(bytes.as_ref()), Err(PngmeError::Chunk(ChunkError::InvalidChunkType))));
+            assert!(matches!(
+                Chunk::from_arena(&Arc::from(&bytes[..]), 0, ChunkTypes::Valid),
+                Err(PngmeError::Chunk(ChunkError::InvalidChunkType))
+            ));
+
+            let chunk = Chunk::from_bytes(&bytes, ChunkTypes::Any).unwrap();
+            assert_eq!(chunk.chunk_type().bytes(), chunk_type);
+        }
+    }
+
+    #[test]
+    fn test_zero_length_chunk() {
+        let chunk = Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new());
+        let bytes = chunk.as_bytes();
+        assert_eq!(bytes.len(), 12);
+
+        let parsed = Chunk::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(parsed.length(), 0);
+        assert!(parsed.data().is_empty());
+        assert_eq!(parsed, chunk);
+    }
+
+    fn declared_chunk(length: u32, data_length: usize) -> Vec<u8> {
+        length
+            .to_be_bytes()
+            .iter()
+            .chain(b"RuSt".iter())
+            .copied()
+            .chain(std::iter::repeat_n(0, data_length + 4))
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_length_too_large() {
+        for length in [Chunk::MAX_LENGTH + 1, u32::MAX] {
+            let result = Chunk::try_from(declared_chunk(length, 0).as_ref());
+            assert!(matches!(result, Err(PngmeError::Chunk(ChunkError::LengthTooLarge(_)))));
+        }
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let result = Chunk::try_from(declared_chunk(100, 10).as_ref());
+        assert!(matches!(result, Err(PngmeError::Chunk(ChunkError::Truncated(100, 10)))));
+
+        // The largest allowed length is only refused for lack of data
+        let result = Chunk::try_from(declared_chunk(Chunk::MAX_LENGTH, 10).as_ref());
+        assert!(matches!(result, Err(PngmeError::Chunk(ChunkError::Truncated(_, 10)))));
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -5,7 +5,7 @@ use std::str::{self, FromStr};
 
 use crate::{Error, Result};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -62,12 +62,24 @@ impl FromStr for ChunkType {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
+        // Chunk types that aren't made of letters are written as a hex code such as 0x89504E47
+        if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            if hex.len() != 8 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(ChunkTypeError::InvalidHexCode(s.to_string()).into());
+            }
+
+            let code = u32::from_str_radix(hex, 16)
+                .map_err(|_| ChunkTypeError::InvalidHexCode(s.to_string()))?;
+
+            return ChunkType::try_from(code.to_be_bytes());
+        }
+
         // Get bytes from string input
         let bytes = s.as_bytes();
 
         // Throw error if input has less than 4 bytes
         if bytes.len() != 4 {
-            return Err(Box::new(ChunkTypeError::ByteLengthError(bytes.len())));
+            return Err(ChunkTypeError::ByteLengthError(bytes.len()).into());
         }
 
         // Check if all chars in the input for valid chunk type codes
@@ -76,7 +88,7 @@ impl FromStr for ChunkType {
             .all(|b| (65..=90).contains(b) || (97..=122).contains(b));
 
         if !valid_chars {
-            return Err(Box::new(ChunkTypeError::InvalidCharacter));
+            return Err(ChunkTypeError::InvalidCharacter.into());
         }
 
         // Create byte array from input
@@ -86,13 +98,15 @@ impl FromStr for ChunkType {
     }
 }
 
+/// Chunk types made of letters are shown as is, any other chunk type as a hex code that `from_str` accepts back
 impl Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            std::str::from_utf8(&self.bytes).map_err(|_| fmt::Error)?
-        )
+        if self.bytes.iter().all(u8::is_ascii_alphabetic) {
+            // Letters are always valid UTF-8
+            write!(f, "{}", str::from_utf8(&self.bytes).map_err(|_| fmt::Error)?)
+        } else {
+            write!(f, "0x{:08X}", u32::from_be_bytes(self.bytes))
+        }
     }
 }
 
@@ -104,6 +118,9 @@ pub enum ChunkTypeError {
 
     /// The input string contains one or more invalid characters
     InvalidCharacter,
+
+    /// The input string starts with 0x but isn't followed by 8 hex digits
+    InvalidHexCode(String),
 }
 
 impl error::Error for ChunkTypeError {}
@@ -119,6 +136,11 @@ impl Display for ChunkTypeError {
             ChunkTypeError::InvalidCharacter => {
                 write!(f, "Input contains one or more invalid characters")
             }
+            ChunkTypeError::InvalidHexCode(code) => write!(
+                f,
+                "Invalid chunk type hex code '{}', expected 0x followed by 8 hex digits",
+                code
+            ),
         }
     }
 }
@@ -213,6 +235,30 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_non_ascii_chunk_type_string() {
+        let chunk = ChunkType::try_from([0x89, 0x50, 0x4E, 0x47]).unwrap();
+        assert_eq!(&chunk.to_string(), "0x89504E47");
+
+        let chunk = ChunkType::try_from([82, 117, 49, 116]).unwrap();
+        assert_eq!(&chunk.to_string(), "0x52753174");
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_hex_code() {
+        let expected = ChunkType::try_from([0x89, 0x50, 0x4E, 0x47]).unwrap();
+        assert_eq!(ChunkType::from_str("0x89504E47").unwrap(), expected);
+        assert_eq!(ChunkType::from_str("0X89504e47").unwrap(), expected);
+
+        // Display and from_str round trip
+        assert_eq!(ChunkType::from_str(&expected.to_string()).unwrap(), expected);
+        assert_eq!(ChunkType::from_str("0x52755374").unwrap().to_string(), "RuSt");
+
+        assert!(ChunkType::from_str("0x89504E").is_err());
+        assert!(ChunkType::from_str("0x89504E4G").is_err());
+        assert!(ChunkType::from_str("0x+9504E47").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
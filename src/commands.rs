@@ -1,99 +1,1288 @@
-use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::convert::TryFrom;
+use std::str::FromStr;
 use structopt::StructOpt;
 
+use crate::apng::{self, FrameOptions};
 use crate::args::*;
+use crate::assertion::Expectation;
+use crate::capabilities;
+use crate::carrier::{self, Candidate};
 use crate::png;
-use crate::chunk;
+use crate::print::{self, ChunkFilter, DataFormat};
+use crate::profile;
+use crate::protect;
+use crate::quantize;
+use crate::registry;
+use crate::rename::{self, Action};
+use crate::repair;
+use crate::image_data::ImageHeader;
+use crate::chunk::{self, ChunkTypes};
+use crate::chunk_type::ChunkType;
+use crate::compress::Compression;
+use crate::compress;
+use crate::delta::{self, DeltaError};
+use crate::demo;
+use crate::diagnostics::{self, Rule};
+use crate::diff;
+use crate::envelope;
+use crate::files::{FileProvider, ListOptions, Listing, Skip};
+use crate::manifest::{self, Manifest, ManifestFrame};
+use crate::output::{Field, OutputRenderer, Record};
+use crate::parity::Parity;
+use crate::scan::ScanPolicy;
+use crate::seal::{self, SealError};
+use crate::sidecar::Sidecar;
+use crate::signature;
+use crate::stego::{self, Mode};
+use crate::sync;
+use crate::wizard::{self, Wizard};
+use crate::zlib::{self, ZlibError};
+use crate::settings::Settings;
 use crate::Result;
 
 /// Encodes a message into a PNG file and saves the result
-pub fn encode(args: Encode) -> Result<()> {
-    let Encode { filepath, chunk_type, message, output_file} = args;
+pub fn encode(args: Encode, settings: &Settings, files: &dyn FileProvider) -> Result<()> {
+    let Encode {
+        filepath, chunk_type, message, output_file, sign, position, mode, force, replace, compress, dict, delta, seal,
+        par2_out
+    } = args;
 
-    // Read PNG file to vector of bytes
-    let bytes = fs::read(&filepath)?;
+    // Critical and registered chunks are interpreted by decoders, so writing them may corrupt the image
+    if mode == Mode::Chunk && !force {
+        registry::check_writable(&chunk_type)?;
+    }
+
+    if mode == Mode::Chunk {
+        profile::check_chunk_type(settings.profile, &chunk_type)?;
+    }
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+    diagnostics::check_structure(settings, &filepath, &png)?;
+
+    // Sign message with the given key file if requested
+    let data: Vec<u8> = match sign {
+        Some(keyfile) => signature::sign(&files.read(&keyfile)?, message.as_bytes())?,
+        None => message.as_bytes().to_vec()
+    };
 
-    // Convert bytes array into png struct
-    let mut png = png::Png::try_from(&bytes[..])?;
+    // Diff the message against the latest version, the first version is stored whole and escaped if it starts like
+    // an envelope, so decode never takes it for one
+    let data = match (delta, mode) {
+        (false, _) => envelope::escape(&data),
+        (true, Mode::Lsb) => return Err(DeltaError::LsbUnsupported.into()),
+        (true, Mode::Chunk) => match delta::latest(&png, &chunk_type.to_string())? {
+            Some(base) => delta::diff(&base, &data),
+            None => envelope::escape(&data)
+        }
+    };
 
-    // Create chunk from chunk_type and message
-    let data: Vec<u8> = message.as_bytes().to_vec();
-    let chunk = chunk::Chunk::new(chunk_type, data);
+    // Compress the message, signature included, so decode finds it as it was signed
+    let data = compress::compress(&data, compress, dict)?;
 
-    // Append chunk to png struct
-    png.append_chunk(chunk);
+    // The digest covers the image as it is written, after the profile converted it
+    let data = match (seal, mode) {
+        (false, _) => data,
+        (true, Mode::Lsb) => return Err(SealError::LsbUnsupported.into()),
+        (true, Mode::Chunk) => seal::seal(&profile::apply(settings.profile, &png)?, &chunk_type.to_string(), &data)
+    };
+
+    // Parity data covers the bytes as they are hidden, so recover gives back exactly what decode expects
+    let parity = match par2_out {
+        Some(path) => Some((path, Parity::new(&data, mode, chunk_type.clone())?)),
+        None => None
+    };
+
+    let png = match mode {
+        Mode::Chunk => {
+            // Create chunk from chunk_type and message
+            let chunk = chunk::Chunk::new(chunk_type, data);
+
+            // Replace chunk of the same type if requested and one exists,
+            // otherwise insert chunk in png struct at the requested position
+            let mut png = png;
+
+            if replace && png.chunk_index(&chunk.chunk_type().to_string()).is_some() {
+                png.replace_chunk(chunk)?;
+            } else {
+                // A delta goes after the versions it is made against
+                let index = match delta::insert_index(&png, &chunk.chunk_type().to_string()) {
+                    Some(index) if delta => index,
+                    _ => png.position_index(&position)?
+                };
+                png.insert_chunk(index, chunk)?;
+            }
+            png
+        },
+        // Pixels are converted by the profile before the message is hidden in them, so it survives the conversion
+        Mode::Lsb => stego::embed(&profile::apply(settings.profile, &png)?, &data)?
+    };
 
     // Write updated png file to a specific output file or
     // overwrite original file
     match output_file {
-        Some(path) => fs::write(path, png.as_bytes())?,
-        None => fs::write(&filepath, png.as_bytes())?
+        Some(path) => write_png(settings, files, &path, &png)?,
+        None => write_png(settings, files, &filepath, &png)?
+    }
+
+    if let Some((path, parity)) = parity {
+        files.write(&path, &parity.as_bytes())?;
     }
 
     Ok(())
 }
 
 /// Searches for a message hidden in a PNG file and prints the message if one is found
-pub fn decode(args: Decode) -> Result<()> {
-    let Decode { filepath, chunk_type} = args;
+pub fn decode(args: Decode, settings: &Settings, files: &dyn FileProvider, out: &mut dyn OutputRenderer) -> Result<()> {
+    let Decode { filepath, chunk_type, verify, mode, output, raw, scan, scan_policy } = args;
 
-    // Read PNG file to vector of bytes
-    let bytes = fs::read(&filepath)?;
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
 
-    // Convert bytes array into png struct
-    let png = png::Png::try_from(&bytes[..])?;
+    // Only show a summary when the message isn't dumped verbatim
+    let interactive = output.is_none() && !raw;
 
-    // Show chunk if it exists in png
-    match png.chunk_by_type(&chunk_type.to_string()) {
-        Some(chunk) => {
-            println!("{}", chunk);
-            Ok(())
-        },
-        None => Err("Could not find chunk".into())
+    // Verify message's signature with the given key file if requested, so nothing unverified is shown or written
+    let verified = verify.is_some();
+    let message = hidden_message(settings, files, &filepath, &png, &chunk_type, mode, verify.as_deref())?;
+    let message = &message[..];
+
+    // Screen message with the given scanner before any of it is shown or written
+    if let Some(scanner) = scan {
+        scanner.screen(settings, message, scan_policy)?;
+    }
+
+    if interactive {
+        let text = String::from_utf8_lossy(message);
+
+        let record = match mode {
+            // The message is shown as a chunk holding it once decompressed, rebuilt from its deltas and verified
+            Mode::Chunk => {
+                let decoded = chunk::Chunk::new(chunk_type.clone(), message.to_vec());
+                let summary = match verified {
+                    true => format!("{}\nVerified message: {}", decoded, text),
+                    false => decoded.to_string()
+                };
+
+                Record::new(summary)
+                    .field("chunk_type", chunk_type.to_string())
+                    .field("length", decoded.length())
+                    .field("crc", decoded.crc())
+            },
+            Mode::Lsb if verified => Record::new(format!("Verified message: {}", text)),
+            Mode::Lsb => Record::new(text.clone())
+        };
+
+        out.record(&record.field("message", text).field("verified", verified))?;
     }
+
+    // Dump message's bytes verbatim to a file or to stdout
+    if let Some(path) = output {
+        files.write(&path, message)?;
+    } else if raw {
+        let mut stdout = io::stdout();
+        stdout.write_all(message)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Checks that a chunk of a PNG file holds the expected data
+pub fn assert(args: Assert, settings: &Settings, files: &dyn FileProvider) -> Result<()> {
+    let Assert { filepath, chunk_type, verify, equals_file, equals, matches } = args;
+
+    // Exactly one expectation is given, as enforced by the arguments
+    let expectation = match (equals_file, equals, matches) {
+        (Some(path), _, _) => Expectation::Equals(files.read(&path)?),
+        (_, Some(text), _) => Expectation::Equals(text.into_bytes()),
+        (_, _, Some(pattern)) => Expectation::matching(&pattern)?,
+        _ => unreachable!("assert requires --equals-file, --equals or --matches")
+    };
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    // The message is checked as decode shows it, not as the bytes it is stored as
+    let message = hidden_message(settings, files, &filepath, &png, &chunk_type, Mode::Chunk, verify.as_deref())?;
+
+    expectation.check(&message)
 }
 
 /// Removes a chunk from a PNG file and saves the result
-pub fn remove(args: Remove) -> Result<()> {
+pub fn remove(args: Remove, settings: &Settings, files: &dyn FileProvider, out: &mut dyn OutputRenderer) -> Result<()> {
     let Remove { filepath, chunk_type} = args;
-    // Read PNG file to vector of bytes
-    let bytes = fs::read(&filepath)?;
+    files.check_writable(&filepath)?;
 
-    // Convert bytes array into png struct
-    let mut png = png::Png::try_from(&bytes[..])?;
+    // Read PNG file into png struct, chunks of corrupted files having invalid types included so they can be removed
+    let mut png = read_png_with(settings, files, &filepath, ChunkTypes::Any)?;
 
-    // Remove chunk if it exists in png struct
-    let chunk = png.remove_chunk(&chunk_type.to_string())?;
+    // Remove chunk if it exists in png struct, along with the later versions of the message made against it
+    let (chunk, deltas) = delta::remove(&mut png, &chunk_type.to_string())?;
 
     // Overwrite PNG file with updated version
-    fs::write(&filepath, png.as_bytes())?;
+    write_png(settings, files, &filepath, &png)?;
+
+    out.record(
+        &Record::new(format!("Removed chunk: {}", chunk))
+            .field("chunk_type", chunk.chunk_type().to_string())
+            .field("length", chunk.length())
+            .field("crc", chunk.crc())
+            .field("deltas", deltas)
+    )?;
+
+    match deltas {
+        0 => Ok(()),
+        1 => out.note("Removed the later version of the message along with it"),
+        deltas => out.note(&format!("Removed {} later versions of the message along with it", deltas))
+    }
+
+}
+
+/// Prints the chunks in a PNG file that pass the filters
+pub fn print_chunks(
+    args: Print,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Print { filepath, types, critical_only, ancillary_only, no_data, hex, full } = args;
+    // Read PNG file into png struct, showing chunks of invalid types as well
+    let png = read_png_with(settings, files, &filepath, ChunkTypes::Any)?;
+
+    let filter = ChunkFilter {
+        types,
+        critical: match (critical_only, ancillary_only) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None
+        }
+    };
+
+    let data_format = match (no_data, hex, full) {
+        (true, _, _) => DataFormat::Hidden,
+        (_, true, true) => DataFormat::Hex(None),
+        (_, true, false) => DataFormat::Hex(Some(print::HEX_DUMP_LIMIT)),
+        _ => DataFormat::List
+    };
+
+    for entry in print::entries(&png, &filter) {
+        out.record(&print::entry_record(&entry, data_format, settings.locale))?;
+    }
+
+    Ok(())
+}
+
+/// Prints the chunks added, removed, modified or moved between two PNG files
+pub fn diff_chunks(
+    args: Diff,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Diff { first, second, ignore_idat, side_by_side } = args;
+
+    // Read both PNG files into png structs
+    let first = read_png(settings, files, &first)?;
+
+    let second = read_png(settings, files, &second)?;
+
+    let ignored: &[&str] = if ignore_idat { &["IDAT"] } else { &[] };
+
+    if side_by_side {
+        for record in diff::row_records(&diff::side_by_side(&first, &second, ignored), settings.locale) {
+            out.record(&record)?;
+        }
+
+        return Ok(());
+    }
+
+    let changes = diff::diff(&first, &second, ignored);
+
+    if changes.is_empty() {
+        out.note("No differences found")?;
+    }
+
+    for change in changes {
+        out.record(&diff::change_record(&change, settings.locale))?;
+    }
 
-    println!("Removed chunk: {}", chunk);
     Ok(())
 }
 
-/// Prints all of the chunks in a PNG file
-pub fn print_chunks(args: Print) -> Result<()> {
-    let Print { filepath} = args;
-    // Read PNG file to vector of bytes
-    let bytes = fs::read(&filepath)?;
+/// Encrypts chunks of a PNG file in place and saves the result
+pub fn protect(
+    args: Protect,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Protect { filepath, output_file, chunks, password } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
 
-    // Convert bytes array into png struct
-    let png = png::Png::try_from(&bytes[..])?;
+    let (png, count) = protect::protect(&png, &chunks, &password, settings.deterministic)?;
 
-    for chunk in png.chunks() {
-        println!("{}", chunk);
+    // Write protected png file to a specific output file or
+    // overwrite original file
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &png)?,
+        None => write_png(settings, files, &filepath, &png)?
+    }
+
+    out.record(&Record::new(format!("Protected {} chunks", count)).field("protected", count))
+}
+
+/// Decrypts the protected chunks of a PNG file and saves the result
+pub fn unprotect(
+    args: Unprotect,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Unprotect { filepath, output_file, password } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    let (png, count) = protect::unprotect(&png, &password)?;
+
+    // Write restored png file to a specific output file or
+    // overwrite original file
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &png)?,
+        None => write_png(settings, files, &filepath, &png)?
+    }
+
+    out.record(&Record::new(format!("Unprotected {} chunks", count)).field("unprotected", count))
+}
+
+/// Writes metadata from a sidecar JSON file into a PNG file and saves the result
+pub fn import_metadata(args: ImportMetadata, settings: &Settings, files: &dyn FileProvider) -> Result<()> {
+    let ImportMetadata { filepath, output_file, from } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    let sidecar = Sidecar::read(files, &from)?;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    let png = sidecar.apply(&png)?;
+
+    // Write updated png file to a specific output file or
+    // overwrite original file
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &png)?,
+        None => write_png(settings, files, &filepath, &png)?
     }
 
     Ok(())
 }
 
-pub fn run(subcommand: Subcommand) -> Result<()> {
+/// Saves the metadata of a PNG file as a sidecar JSON file, or prints it
+pub fn export_metadata(
+    args: ExportMetadata,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let ExportMetadata { filepath, to } = args;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    let sidecar = Sidecar::from_png(&png)?;
+    let json = sidecar.to_json()?;
+
+    match to {
+        Some(path) => files.write(&path, json.as_bytes())?,
+        None => {
+            let fields = match serde_json::to_value(&sidecar)? {
+                serde_json::Value::Object(fields) => fields
+                    .into_iter()
+                    .map(|(name, value)| (name, Field::Value(value)))
+                    .collect(),
+                _ => Vec::new()
+            };
+
+            out.record(&Record { text: json, fields })?
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the metadata chunks that derivative PNG files lost from their originals
+pub fn sync_metadata(
+    args: SyncMetadata,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let SyncMetadata { from, to, match_by, dry_run, if_writable, walk } = args;
+
+    let originals = png_files(settings, files, &from, walk)?;
+    let targets = png_files(settings, files, &to, walk)?;
+    let mut pairs = sync::pair(&originals, &targets, match_by);
+
+    for target in targets.iter().filter(|target| !pairs.iter().any(|(_, paired)| paired == *target)) {
+        settings.warn(Rule::SkippedFile, format!("Skipped {}: no matching original", target.display()))?;
+    }
+
+    // Read-only targets are found before any file is parsed or changed
+    if !dry_run {
+        if if_writable {
+            let mut writable = Vec::new();
+
+            for (original, target) in pairs {
+                match files.is_writable(&target) {
+                    true => writable.push((original, target)),
+                    false => settings.warn(Rule::SkippedFile, format!("Skipped {}: read-only", target.display()))?
+                }
+            }
+            pairs = writable;
+        } else {
+            for (_, target) in &pairs {
+                files.check_writable(target)?;
+            }
+        }
+    }
+
+    // Files that can't be read keep their chunks
+    for (original, target) in pairs {
+        let synced = read_png(settings, files, &original).and_then(|original| {
+            let png = read_png(settings, files, &target)?;
+            sync::sync(&original, &png)
+        });
+
+        let (png, count) = match synced {
+            Ok((_, 0)) => continue,
+            Ok(synced) => synced,
+            Err(error) => {
+                settings.warn(Rule::SkippedFile, format!("Skipped {}: {}", target.display(), error))?;
+                continue;
+            }
+        };
+
+        if !dry_run {
+            write_png(settings, files, &target, &png)?;
+        }
+
+        let verb = if dry_run { "Would copy" } else { "Copied" };
+
+        out.record(
+            &Record::new(format!("{} {} chunks: {} -> {}", verb, count, original.display(), target.display()))
+                .field("original", original.display().to_string())
+                .field("target", target.display().to_string())
+                .field("chunks", count)
+                .field("dry_run", dry_run)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Saves every frame of an APNG file as a standalone PNG file along with a manifest
+pub fn export_frames(
+    args: ExportFrames,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let ExportFrames { filepath, output } = args;
+
+    // Read APNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    let animation_control = apng::animation_control(&png)?;
+    let frames = apng::export_frames(&png)?;
+
+    // Save every frame in the output directory
+    files.create_dir_all(&output)?;
+
+    let mut manifest = Manifest {
+        num_plays: animation_control.num_plays,
+        frames: Vec::new()
+    };
+
+    for (index, (control, frame)) in frames.iter().enumerate() {
+        let file = PathBuf::from(format!("frame_{:03}.png", index));
+        let path = output.join(&file);
+        write_png(settings, files, &path, frame)?;
+        out.record(&exported_record("frame", &path))?;
+
+        manifest.frames.push(ManifestFrame {
+            file,
+            options: control.options()
+        });
+    }
+
+    // Save manifest so the frames can be assembled again
+    let manifest_path = output.join(manifest::MANIFEST_FILE_NAME);
+    manifest.write(files, &manifest_path)?;
+    out.record(&exported_record("manifest", &manifest_path))
+}
+
+/// Builds an APNG file from standalone PNG files and saves the result
+pub fn import_frames(args: ImportFrames, settings: &Settings, files: &dyn FileProvider) -> Result<()> {
+    let ImportFrames { frames, manifest, output, delay, plays } = args;
+
+    // Frames and their options come either from a manifest or from the arguments
+    let (paths, options, plays) = match manifest {
+        Some(manifest_path) => {
+            let manifest = Manifest::read(files, &manifest_path)?;
+            (manifest.frame_paths(&manifest_path), manifest.frame_options(), manifest.num_plays)
+        },
+        None => {
+            // A single delay applies to every frame, delays are given in milliseconds
+            let options = match delay.as_slice() {
+                [delay] => vec![FrameOptions::with_delay_ms(*delay); frames.len()],
+                delays => delays
+                    .iter()
+                    .map(|delay| FrameOptions::with_delay_ms(*delay))
+                    .collect()
+            };
+            (frames, options, plays)
+        }
+    };
+
+    // Read every PNG file into a png struct
+    let frames = paths
+        .iter()
+        .map(|path| read_png(settings, files, path))
+        .collect::<Result<Vec<png::Png>>>()?;
+
+    let animation = apng::assemble(&frames, &options, plays)?;
+
+    write_png(settings, files, &output, &animation)?;
+
+    Ok(())
+}
+
+/// Converts a truecolor PNG file to a palette based one and saves the result
+pub fn quantize(args: Quantize, settings: &Settings, files: &dyn FileProvider) -> Result<()> {
+    let Quantize { filepath, output_file, colors, dither } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    let quantized = quantize::quantize(&png, colors, dither)?;
+
+    // Write quantized png file to a specific output file or
+    // overwrite original file
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &quantized)?,
+        None => write_png(settings, files, &filepath, &quantized)?
+    }
+
+    Ok(())
+}
+
+/// Merges the runs of IDAT chunks of a PNG file and saves the result
+pub fn normalize(
+    args: Normalize,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Normalize { filepath, output_file, max_size } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    // Read PNG file into png struct
+    let png = read_png(settings, files, &filepath)?;
+
+    let mut normalized = png.clone();
+    normalized.coalesce_idat(max_size)?;
+
+    if normalized == png {
+        return out.note("Nothing to normalize");
+    }
+
+    let idat_count = |png: &png::Png| {
+        png.chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .count()
+    };
+    let (before, after) = (idat_count(&png), idat_count(&normalized));
+    out.record(
+        &Record::new(format!("IDAT chunks: {} -> {}", before, after))
+            .field("idat_before", before)
+            .field("idat_after", after)
+    )?;
+
+    // Write normalized png file to a specific output file or
+    // overwrite original file
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &normalized)?,
+        None => write_png(settings, files, &filepath, &normalized)?
+    }
+
+    Ok(())
+}
+
+/// Checks every compressed stream of a PNG file and reports the layer of the corrupt ones
+pub fn validate(
+    args: Validate,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let png = read_png(settings, files, &args.filepath)?;
+
+    // Chunks out of place are reported before the streams, under the same policy
+    diagnostics::check_structure(settings, &args.filepath, &png)?;
+
+    let checks = zlib::check_png(&png);
+
+    if checks.is_empty() {
+        return out.note("No compressed streams");
+    }
+
+    let mut corrupt = 0;
+
+    for check in &checks {
+        let rule = check.fault.as_ref().map(|fault| fault.layer().rule());
+
+        out.record(
+            &Record::new(check.to_string())
+                .field("chunk_type", check.chunk_type.clone())
+                .field("index", check.index)
+                .field("intact", check.fault.is_none())
+                .field("layer", check.fault.as_ref().map(|fault| fault.layer().name()))
+                .field("fault", check.fault.as_ref().map(|fault| fault.to_string()))
+                .field("code", rule.map(|rule| rule.code()))
+        )?;
+
+        // Corrupt streams are findings like any other, so they can be ignored or fail the command in strict mode
+        if let Some(rule) = rule.filter(|rule| !settings.policy.ignores(*rule)) {
+            settings.warn(rule, format!("{}: {}", args.filepath.display(), check))?;
+            corrupt += 1;
+        }
+    }
+
+    match corrupt {
+        0 => Ok(()),
+        corrupt => Err(ZlibError::CorruptStreams(corrupt).into())
+    }
+}
+
+/// Repairs the signature, IHDR chunk or image data checksum of a damaged PNG file and saves the result
+pub fn repair(args: Repair, settings: &Settings, files: &dyn FileProvider, out: &mut dyn OutputRenderer) -> Result<()> {
+    let Repair { filepath, output_file, width, height, bit_depth, color_type, interlaced, fix_adler } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    let bytes = files.read(&filepath)?;
+
+    // Validate the header the same way as one read from an IHDR chunk
+    let header = match (width, height) {
+        (Some(width), Some(height)) => {
+            let header = ImageHeader {
+                width,
+                height,
+                bit_depth,
+                color_type,
+                compression_method: 0,
+                filter_method: 0,
+                interlace_method: interlaced as u8
+            };
+
+            Some(ImageHeader::try_from(&header.as_bytes()[..])?)
+        },
+        _ => None
+    };
+
+    let (mut png, mut fixes) = repair::repair(&bytes, header)?;
+
+    if fix_adler {
+        fixes.extend(repair::recompute_adler(&mut png)?);
+    }
+
+    settings.events.emit_chunks(&filepath, &png);
+
+    if fixes.is_empty() {
+        return out.note("Nothing to repair");
+    }
+
+    for fix in &fixes {
+        out.record(&Record::new(fix.to_string()).field("fix", fix.to_string()))?;
+    }
+
+    // Write repaired png file to a specific output file or
+    // overwrite original file
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &png)?,
+        None => write_png(settings, files, &filepath, &png)?
+    }
+
+    Ok(())
+}
+
+/// Saves the intact chunks a truncated PNG file starts with, ended with an IEND chunk
+pub fn salvage(
+    args: Salvage,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Salvage { filepath, output_file, pad_image_data } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    let (png, fixes) = repair::salvage(&files.read(&filepath)?, pad_image_data)?;
+    settings.events.emit_chunks(&filepath, &png);
+
+    if fixes.is_empty() {
+        return out.note("Nothing to salvage");
+    }
+
+    for fix in &fixes {
+        out.record(&Record::new(fix.to_string()).field("fix", fix.to_string()))?;
+    }
+
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &png)?,
+        None => write_png(settings, files, &filepath, &png)?
+    }
+
+    Ok(())
+}
+
+/// Rebuilds a hidden message out of its parity file and hides it again in the PNG file
+pub fn recover(
+    args: Recover,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let Recover { filepath, parity_file, output_file } = args;
+
+    check_in_place(files, &filepath, &output_file)?;
+
+    let png = read_png(settings, files, &filepath)?;
+    let parity = Parity::try_from(&files.read(&parity_file)?[..])?;
+
+    if parity.mode == Mode::Chunk {
+        profile::check_chunk_type(settings.profile, &parity.chunk_type)?;
+    }
+
+    let recovery = parity.recover(&png)?;
+
+    if recovery.intact {
+        return out.note("Message is intact, nothing to recover");
+    }
+
+    let hidden_in = match parity.mode {
+        Mode::Chunk => format!("{} chunk", parity.chunk_type),
+        Mode::Lsb => String::from("pixels")
+    };
+
+    out.record(
+        &Record::new(format!(
+            "Rebuilt {} of {} shards ({}) of the {} byte message hidden in the {}",
+            recovery.rebuilt,
+            recovery.shards,
+            settings.locale.percent(recovery.rebuilt as f64 / recovery.shards as f64),
+            settings.locale.number(parity.length as u64),
+            hidden_in
+        ))
+        .field("hidden_in", hidden_in)
+        .field("length", parity.length)
+        .field("shards", recovery.shards)
+        .field("rebuilt", recovery.rebuilt)
+    )?;
+
+    match output_file {
+        Some(path) => write_png(settings, files, &path, &recovery.png)?,
+        None => write_png(settings, files, &filepath, &recovery.png)?
+    }
+
+    Ok(())
+}
+
+/// Renames every PNG file in a directory after its metadata
+pub fn rename_by(
+    args: RenameBy,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let RenameBy { directory, pattern, dry_run, on_collision, walk } = args;
+
+    // Files that can't be read or lack some metadata keep their name
+    let mut targets = Vec::new();
+
+    for filepath in png_files(settings, files, &directory, walk)? {
+        let name = read_png(settings, files, &filepath).and_then(|png| pattern.render(&png, &filepath));
+
+        match name {
+            Ok(name) => targets.push((filepath.clone(), filepath.with_file_name(name))),
+            Err(error) => settings.warn(Rule::SkippedFile, format!("Skipped {}: {}", filepath.display(), error))?
+        }
+    }
+
+    for action in rename::plan(&targets, on_collision, |path| files.exists(path)) {
+        match action {
+            Action::Rename { from, to } => {
+                if !dry_run {
+                    files.rename(&from, &to)?;
+                }
+
+                let verb = if dry_run { "Would rename" } else { "Renamed" };
+
+                out.record(
+                    &Record::new(format!("{}: {} -> {}", verb, from.display(), to.display()))
+                        .field("from", from.display().to_string())
+                        .field("to", to.display().to_string())
+                        .field("dry_run", dry_run)
+                )?;
+            },
+            Action::Unchanged(_) => {},
+            Action::Collision { from, to } => {
+                let message = format!("Skipped {}: {} already exists", from.display(), to.display());
+                settings.warn(Rule::SkippedFile, message)?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the PNG files in a directory best suited to hide a payload in their pixels
+pub fn pick_carrier(
+    args: PickCarrier,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
+    let PickCarrier { directory, payload_size, count, walk } = args;
+
+    // Images that can't carry a message in their pixels are left out
+    let mut candidates = Vec::new();
+
+    for filepath in png_files(settings, files, &directory, walk)? {
+        let candidate = read_png(settings, files, &filepath).and_then(|png| {
+                Ok(Candidate {
+                    filepath: filepath.clone(),
+                    capacity: stego::capacity(&png)?,
+                    noise: stego::noise(&png)?
+                })
+            });
+
+        match candidate {
+            Ok(candidate) => candidates.push(candidate),
+            Err(error) => settings.warn(Rule::SkippedFile, format!("Skipped {}: {}", filepath.display(), error))?
+        }
+    }
+
+    let ranked = carrier::rank(candidates, payload_size);
+
+    if ranked.is_empty() {
+        out.note(&format!("No image can hide {} bytes", settings.locale.number(payload_size.0)))?;
+    }
+
+    for (index, candidate) in ranked.iter().take(count).enumerate() {
+        out.record(
+            &Record::new(format!("{}. {}", index + 1, candidate.describe(settings.locale)))
+                .field("rank", index + 1)
+                .field("file", candidate.filepath.display().to_string())
+                .field("capacity", candidate.capacity)
+                .field("noise", candidate.noise)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Asks the user what to do on stdin, then prints the equivalent command
+///
+/// Questions go to stderr, so only the command is printed to stdout
+pub fn wizard(out: &mut dyn OutputRenderer) -> Result<()> {
+    let stdin = io::stdin();
+    let args = Wizard::new(stdin.lock(), io::stderr()).run()?;
+    let command = wizard::command_line(&args);
+
+    eprintln!("\nRun this command to do it again without the wizard:");
+    out.record(&Record::new(command.clone()).field("command", command))
+}
+
+/// Generates a sample image, hides a message in it and decodes it back, explaining every step
+pub fn demo(args: Demo, settings: &Settings, files: &dyn FileProvider, out: &mut dyn OutputRenderer) -> Result<()> {
+    let Demo { directory, message } = args;
+
+    // A fresh directory of its own by default, kept so the commands printed at the end can be run on its files
+    let directory = match directory {
+        Some(directory) => directory,
+        None => tempfile::Builder::new().prefix("pngme-demo-").tempdir()?.keep()
+    };
+    files.create_dir_all(&directory)?;
+
+    let sample_path = directory.join("sample.png");
+    let secret_path = directory.join("secret.png");
+    let [generated, hidden, decoded, next] = demo::EXPLANATIONS;
+
+    out.note(&format!("Step 1 - Generate a sample image\n{}\n", generated))?;
+    let sample = demo::sample_png()?;
+    write_png(settings, files, &sample_path, &sample)?;
+    out.record(&step_record(1, "generated", &sample_path))?;
+
+    for entry in print::entries(&sample, &ChunkFilter::default()) {
+        out.record(&print::entry_record(&entry, DataFormat::Hidden, settings.locale))?;
+    }
+
+    // The message is hidden and decoded by the very commands printed at the end
+    let chunk_type = ChunkType::from_str(demo::MESSAGE_CHUNK_TYPE)?;
+
+    out.note(&format!("\nStep 2 - Hide a message\n{}\n", hidden))?;
+    let encode_args = Encode {
+        filepath: sample_path.clone(),
+        chunk_type: chunk_type.clone(),
+        message: message.clone(),
+        output_file: Some(secret_path.clone()),
+        sign: None,
+        position: png::ChunkPosition::BeforeIend,
+        mode: Mode::Chunk,
+        force: false,
+        replace: false,
+        compress: Compression::None,
+        dict: None,
+        delta: false,
+        seal: false,
+        par2_out: None
+    };
+    encode(encode_args, settings, files)?;
+    let secret = read_png(settings, files, &secret_path)?;
+    out.record(&step_record(2, "hidden", &secret_path))?;
+
+    let filter = ChunkFilter { types: vec![demo::MESSAGE_CHUNK_TYPE.to_string()], critical: None };
+
+    for entry in print::entries(&secret, &filter) {
+        out.record(&print::entry_record(&entry, DataFormat::List, settings.locale))?;
+    }
+
+    out.note(&format!("\nStep 3 - Decode the message\n{}\n", decoded))?;
+    let decode_args = Decode {
+        filepath: secret_path.clone(),
+        chunk_type,
+        verify: None,
+        mode: Mode::Chunk,
+        output: None,
+        raw: false,
+        scan: None,
+        scan_policy: ScanPolicy::Block
+    };
+    decode(decode_args, settings, files, out)?;
+
+    // Commands doing the same on the demo's files
+    let sample_arg = sample_path.display().to_string();
+    let secret_arg = secret_path.display().to_string();
+    let commands = [
+        vec!["print", &sample_arg],
+        vec!["encode", &sample_arg, demo::MESSAGE_CHUNK_TYPE, &message, &secret_arg],
+        vec!["decode", &secret_arg, demo::MESSAGE_CHUNK_TYPE],
+        vec!["remove", &secret_arg, demo::MESSAGE_CHUNK_TYPE]
+    ];
+
+    out.note(&format!("\n{}", next))?;
+
+    for args in commands.iter() {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        out.note(&format!("  {}", wizard::command_line(&args)))?;
+    }
+
+    Ok(())
+}
+
+/// Record of a file written by a step of the demo
+/// Reads the message hidden in a PNG file the way decode shows it
+///
+/// Its seal is checked, it is decompressed and rebuilt from its deltas, and its signature is verified with the given
+/// key file, so every command that looks at a message looks at the same bytes
+fn hidden_message(
+    settings: &Settings,
+    files: &dyn FileProvider,
+    filepath: &Path,
+    png: &png::Png,
+    chunk_type: &ChunkType,
+    mode: Mode,
+    verify: Option<&Path>
+) -> Result<Vec<u8>> {
+    // Compressed messages are decompressed and deltas applied before anything else looks at them
+    let data = match mode {
+        Mode::Chunk => {
+            // The latest version is the one shown, so its seal tells whether the image changed since it was hidden
+            if let Some(chunk) = png.chunks().iter().rev().find(|chunk| chunk.chunk_type() == chunk_type) {
+                seal::check(settings, filepath, png, &chunk_type.to_string(), chunk.data())?;
+            }
+
+            delta::latest(png, &chunk_type.to_string())?.ok_or(png::PngError::ChunkNotFound)?
+        },
+        Mode::Lsb => envelope::unescape(&compress::decompress(&stego::extract(png)?)?).to_vec()
+    };
+
+    match verify {
+        Some(keyfile) => Ok(signature::verify(&files.read(keyfile)?, &data)?.to_vec()),
+        None => Ok(data)
+    }
+}
+
+fn step_record(step: usize, action: &str, path: &Path) -> Record {
+    Record::new(format!("Wrote {}", path.display()))
+        .field("step", step)
+        .field("action", action)
+        .field("file", path.display().to_string())
+}
+
+/// Record of a file written by an export
+fn exported_record(kind: &str, path: &Path) -> Record {
+    Record::new(format!("Exported {}: {}", kind, path.display()))
+        .field("exported", kind)
+        .field("file", path.display().to_string())
+}
+
+/// Fails before any work is done when the file would be overwritten in place but is read-only
+fn check_in_place(files: &dyn FileProvider, filepath: &Path, output_file: &Option<PathBuf>) -> Result<()> {
+    match output_file {
+        Some(_) => Ok(()),
+        None => files.check_writable(filepath)
+    }
+}
+
+/// Writes a PNG file, made to conform to the profile of the settings if they have one
+fn write_png(settings: &Settings, files: &dyn FileProvider, filepath: &Path, png: &png::Png) -> Result<()> {
+    files.write(filepath, &profile::apply(settings.profile, png)?.as_bytes())
+}
+
+/// Reads a PNG file into a png struct, writing an event for every chunk
+fn read_png(settings: &Settings, files: &dyn FileProvider, filepath: &Path) -> Result<png::Png> {
+    read_png_with(settings, files, filepath, ChunkTypes::Valid)
+}
+
+/// Reads a PNG file whose chunks may have any of the given types
+///
+/// The chunks point into a single buffer holding the whole file, which `cargo bench` shows parses files made of
+/// many small chunks faster than giving every chunk a copy of its data
+fn read_png_with(
+    settings: &Settings,
+    files: &dyn FileProvider,
+    filepath: &Path,
+    types: ChunkTypes
+) -> Result<png::Png> {
+    let png = png::Png::from_arena(files.read(filepath)?.into(), types)?;
+
+    settings.events.emit_chunks(filepath, &png);
+
+    Ok(png)
+}
+
+/// PNG files directly inside a directory, sorted by path
+///
+/// Files are picked by their .png extension, or by their signature with `--by-content`. Either way, files whose
+/// extension and signature disagree are warned about, as are the entries skipped by the walk options
+fn png_files(settings: &Settings, files: &dyn FileProvider, directory: &Path, walk: Walk) -> Result<Vec<PathBuf>> {
+    let options = ListOptions { follow_symlinks: walk.follow_symlinks, one_file_system: walk.one_file_system };
+    let mut listings = files.list(directory, &options)?;
+    listings.sort_by(|first, second| listing_path(first).cmp(listing_path(second)));
+
+    let mut png_files = Vec::new();
+
+    for listing in listings {
+        let filepath = match listing {
+            Listing::File(filepath) => filepath,
+            Listing::Skipped(filepath, skip) => {
+                // Entries that wouldn't be picked anyway aren't worth a warning
+                if !walk.by_content && !has_png_extension(&filepath) {
+                    continue;
+                }
+
+                let (rule, reason) = match skip {
+                    Skip::Symlink => (Rule::Symlink, "it is a symbolic link, pass --follow-symlinks to follow it"),
+                    Skip::Special => (Rule::SpecialFile, "it isn't a regular file"),
+                    Skip::OtherFilesystem => (Rule::OtherFilesystem, "it is on another filesystem")
+                };
+
+                settings.warn(rule, format!("Skipped {}: {}", filepath.display(), reason))?;
+                continue;
+            }
+        };
+
+        let named_png = has_png_extension(&filepath);
+
+        // Files that can't be read are left to fail when they are parsed
+        let signed_png = match files.read_prefix(&filepath, png::Png::STANDARD_HEADER.len()) {
+            Ok(prefix) => prefix == png::Png::STANDARD_HEADER,
+            Err(_) => named_png
+        };
+
+        match (named_png, signed_png) {
+            (true, true) => png_files.push(filepath),
+            (true, false) => settings.warn(
+                Rule::SignatureMismatch,
+                format!("Skipped {}: it has a .png extension but isn't a PNG file", filepath.display())
+            )?,
+            (false, true) if walk.by_content => png_files.push(filepath),
+            (false, true) => settings.warn(
+                Rule::ExtensionMismatch,
+                format!(
+                    "Skipped {}: it is a PNG file without a .png extension, pass --by-content to include it",
+                    filepath.display()
+                )
+            )?,
+            (false, false) => {}
+        }
+    }
+
+    Ok(png_files)
+}
+
+fn has_png_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+/// Path of an entry of a directory, whether it was listed or skipped
+fn listing_path(listing: &Listing) -> &Path {
+    match listing {
+        Listing::File(path) | Listing::Skipped(path, _) => path
+    }
+}
+
+/// Runs a subcommand, showing its results with the given renderer
+pub fn run(
+    subcommand: Subcommand,
+    settings: &Settings,
+    files: &dyn FileProvider,
+    out: &mut dyn OutputRenderer
+) -> Result<()> {
     match subcommand {
-        Subcommand::Encode(args) => encode(args),
-        Subcommand::Decode(args) => decode(args),
-        Subcommand::Remove(args) => remove(args),
-        Subcommand::Print(args) => print_chunks(args)
+        Subcommand::Encode(args) => encode(args, settings, files),
+        Subcommand::Decode(args) => decode(args, settings, files, out),
+        Subcommand::Assert(args) => assert(args, settings, files),
+        Subcommand::Remove(args) => remove(args, settings, files, out),
+        Subcommand::Print(args) => print_chunks(args, settings, files, out),
+        Subcommand::Diff(args) => diff_chunks(args, settings, files, out),
+        Subcommand::Protect(args) => protect(args, settings, files, out),
+        Subcommand::Unprotect(args) => unprotect(args, settings, files, out),
+        Subcommand::ImportMetadata(args) => import_metadata(args, settings, files),
+        Subcommand::ExportMetadata(args) => export_metadata(args, settings, files, out),
+        Subcommand::SyncMetadata(args) => sync_metadata(args, settings, files, out),
+        Subcommand::Frames(Frames::Export(args)) => export_frames(args, settings, files, out),
+        Subcommand::Frames(Frames::Import(args)) => import_frames(args, settings, files),
+        Subcommand::Quantize(args) => quantize(args, settings, files),
+        Subcommand::Normalize(args) => normalize(args, settings, files, out),
+        Subcommand::Validate(args) => validate(args, settings, files, out),
+        Subcommand::Repair(args) => repair(args, settings, files, out),
+        Subcommand::Salvage(args) => salvage(args, settings, files, out),
+        Subcommand::Recover(args) => recover(args, settings, files, out),
+        Subcommand::RenameBy(args) => rename_by(args, settings, files, out),
+        Subcommand::PickCarrier(args) => pick_carrier(args, settings, files, out),
+        Subcommand::Wizard => wizard(out),
+        Subcommand::Demo(args) => demo(args, settings, files, out),
+        Subcommand::Capabilities(_) => out.record(&capabilities::record(settings.locale))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::MemoryFiles;
+    use crate::locale::Locale;
+    use crate::output::TextRenderer;
+
+    /// Run a command line against the files, returning its text output
+    ///
+    /// Numbers are written as in the C locale unless the command line names another one
+    fn run_command(files: &MemoryFiles, command: &str) -> Result<String> {
+        let args = std::iter::once("pngme").chain(command.split_whitespace());
+        let opt = Opt::from_iter_safe(args).unwrap();
+        let settings = Settings { locale: opt.locale.unwrap_or(Locale::C), ..opt.settings()? };
+
+        let mut output = Vec::new();
+        run(opt.subcommand, &settings, files, &mut TextRenderer::new(&mut output))?;
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    fn dice() -> MemoryFiles {
+        MemoryFiles::new().with_file("dice.png", std::fs::read("dice.png").unwrap())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_encode_decode_remove_in_memory() {
+        let files = dice();
+        let original = files.get(Path::new("dice.png")).unwrap();
+
+        run_command(&files, "encode dice.png ruSt secret secret.png").unwrap();
+        assert_eq!(files.paths(), vec![PathBuf::from("dice.png"), PathBuf::from("secret.png")]);
+        assert!(run_command(&files, "decode secret.png ruSt").unwrap().contains("chunk type: ruSt"));
+
+        run_command(&files, "remove secret.png ruSt").unwrap();
+        assert_eq!(files.get(Path::new("secret.png")).unwrap(), original);
+    }
+
+    #[test]
+    fn test_settings_in_memory() {
+        let mut bytes = std::fs::read("dice.png").unwrap();
+        bytes.extend(chunk::Chunk::new("ruSt".parse().unwrap(), b"trailing".to_vec()).as_bytes());
+        let files = MemoryFiles::new().with_file("trailing.png", bytes);
+
+        assert!(run_command(&files, "encode trailing.png teSt secret").is_ok());
+        assert!(matches!(
+            run_command(&files, "encode trailing.png teSt secret --strict"),
+            Err(crate::Error::Diagnostics(_))
+        ));
+
+        assert!(run_command(&files, "print trailing.png --type IDAT").unwrap().contains("length: 8192"));
+        assert!(run_command(&files, "print trailing.png --type IDAT --locale de").unwrap().contains("length: 8.192"));
+    }
+
+    #[test]
+    fn test_demo_in_memory() {
+        let files = MemoryFiles::new();
+
+        let output = run_command(&files, "demo --directory demo --message Hi").unwrap();
+        assert_eq!(files.paths(), vec![PathBuf::from("demo/sample.png"), PathBuf::from("demo/secret.png")]);
+        assert!(output.contains("Wrote demo/secret.png"));
+        assert!(output.contains("chunk type: ruSt, data: [72, 105]"));
+
+        // The demo's message is found by decode like any other
+        let decoded = run_command(&files, "decode demo/secret.png ruSt").unwrap();
+        assert!(output.contains(&decoded));
+    }
+
+    #[test]
+    fn test_read_only_in_memory() {
+        let files = dice();
+        files.set_read_only(Path::new("dice.png"));
+
+        let error = run_command(&files, "encode dice.png ruSt secret").unwrap_err();
+        assert!(matches!(error, crate::Error::Access(_)));
+
+        // Writing elsewhere leaves the read-only file alone
+        run_command(&files, "encode dice.png ruSt secret copy.png").unwrap();
+    }
+
+    #[test]
+    fn test_rename_by_in_memory() {
+        let files = MemoryFiles::new().with_file("images/dice.png", std::fs::read("dice.png").unwrap());
+        let output = run_command(&files, "rename-by images --pattern {date}.png").unwrap();
+
+        let renamed = files.paths();
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(renamed[0].parent(), Some(Path::new("images")));
+        assert_ne!(renamed[0], PathBuf::from("images/dice.png"));
+        assert!(output.contains(&format!("Renamed: images/dice.png -> {}", renamed[0].display())));
+    }
+}
@@ -0,0 +1,293 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use crate::envelope;
+use crate::{Error, Result};
+
+/// Start of a compressed payload, looked for once the seal is stripped and followed by the algorithm and dictionary
+const MAGIC: [u8; 4] = [envelope::LEAD, b'P', b'Z', 0x1A];
+
+/// Magic, algorithm and dictionary ID
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 4;
+
+/// Algorithm byte of zstd in the envelope
+const ZSTD_ALGORITHM: u8 = 1;
+
+/// Payloads are small, so the slowest zstd levels cost next to nothing
+const ZSTD_LEVEL: i32 = 19;
+
+/// Largest payload compressed or decompressed, 8 MiB
+///
+/// Messages are short, while a few bytes of zstd can expand to gigabytes, so decompression stops here rather than at
+/// the most a chunk can hold
+pub const MAX_PAYLOAD_LENGTH: usize = 8 * 1024 * 1024;
+
+/// How a payload is compressed before it is hidden
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    /// Hidden as it is
+    None,
+
+    /// Compressed with zstd and wrapped in an envelope telling how
+    Zstd
+}
+
+impl Compression {
+    pub const ALL: [Compression; 2] = [Compression::None, Compression::Zstd];
+
+    /// Name given to `--compress`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd"
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Compression::ALL
+            .iter()
+            .find(|compression| compression.name() == s)
+            .copied()
+            .ok_or_else(|| CompressError::InvalidCompression(s.to_string()).into())
+    }
+}
+
+/// Dictionary shipped with pngme: sample text like payloads usually hold, which zstd finds matches in as if it came
+/// before the payload
+///
+/// Small payloads share little with themselves, so sample text shrinks them far more than compression alone. It is
+/// raw content, not a dictionary trained with `zstd --train`. The ID of a dictionary is recorded in the envelope, so
+/// a dictionary's bytes never change once released
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dictionary {
+    /// JSON documents, configuration files and plain messages
+    BuiltinText
+}
+
+impl Dictionary {
+    pub const ALL: [Dictionary; 1] = [Dictionary::BuiltinText];
+
+    /// ID recorded in the envelope, 0 standing for no dictionary
+    pub fn id(&self) -> u32 {
+        match self {
+            Dictionary::BuiltinText => 1
+        }
+    }
+
+    /// Name given to `--dict`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dictionary::BuiltinText => "builtin-text"
+        }
+    }
+
+    /// Content of the dictionary, used by zstd as a raw content dictionary
+    pub fn bytes(&self) -> &'static [u8] {
+        match self {
+            Dictionary::BuiltinText => include_bytes!("dictionaries/sample_text.txt")
+        }
+    }
+
+    fn from_id(id: u32) -> Option<Dictionary> {
+        Dictionary::ALL.iter().find(|dictionary| dictionary.id() == id).copied()
+    }
+}
+
+impl FromStr for Dictionary {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Dictionary::ALL
+            .iter()
+            .find(|dictionary| dictionary.name() == s)
+            .copied()
+            .ok_or_else(|| CompressError::InvalidDictionary(s.to_string()).into())
+    }
+}
+
+/// Compress a payload, wrapped in an envelope recording the algorithm and dictionary, or leave it as it is
+pub fn compress(data: &[u8], compression: Compression, dictionary: Option<Dictionary>) -> Result<Vec<u8>> {
+    match (compression, dictionary) {
+        (Compression::None, None) => Ok(data.to_vec()),
+        (Compression::None, Some(_)) => Err(CompressError::DictionaryWithoutCompression.into()),
+        (Compression::Zstd, _) if data.len() > MAX_PAYLOAD_LENGTH => Err(CompressError::TooLarge.into()),
+        (Compression::Zstd, dictionary) => {
+            let mut envelope = MAGIC.to_vec();
+            envelope.push(ZSTD_ALGORITHM);
+            envelope.extend_from_slice(&dictionary.map_or(0, |dictionary| dictionary.id()).to_be_bytes());
+
+            let dictionary_bytes = dictionary.map_or(&[][..], |dictionary| dictionary.bytes());
+            let mut encoder = zstd::Encoder::with_dictionary(envelope, ZSTD_LEVEL, dictionary_bytes)?;
+            encoder.write_all(data)?;
+
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+/// Payload out of its envelope, or as it is when it wasn't compressed
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if !data.starts_with(&MAGIC) {
+        return Ok(data.to_vec());
+    }
+
+    if data.len() < HEADER_LENGTH {
+        return Err(CompressError::InvalidEnvelope.into());
+    }
+
+    let algorithm = data[MAGIC.len()];
+
+    if algorithm != ZSTD_ALGORITHM {
+        return Err(CompressError::UnknownAlgorithm(algorithm).into());
+    }
+
+    let dictionary_id = u32::from_be_bytes([data[5], data[6], data[7], data[8]]);
+    let dictionary_bytes = match dictionary_id {
+        0 => &[][..],
+        id => Dictionary::from_id(id).ok_or(CompressError::UnknownDictionary(id))?.bytes()
+    };
+
+    let decoder = zstd::Decoder::with_dictionary(&data[HEADER_LENGTH..], dictionary_bytes)
+        .map_err(|_| CompressError::InvalidEnvelope)?;
+
+    // A tiny payload may expand to gigabytes, so no more than a payload may hold is decompressed
+    let mut payload = Vec::new();
+    decoder
+        .take(MAX_PAYLOAD_LENGTH as u64 + 1)
+        .read_to_end(&mut payload)
+        .map_err(|_| CompressError::InvalidEnvelope)?;
+
+    if payload.len() > MAX_PAYLOAD_LENGTH {
+        return Err(CompressError::TooLarge.into());
+    }
+
+    Ok(payload)
+}
+
+/// Compression errors
+#[derive(Debug)]
+pub enum CompressError {
+    /// Compression is neither none nor zstd
+    InvalidCompression(String),
+
+    /// No dictionary goes by this name
+    InvalidDictionary(String),
+
+    /// A dictionary was given without compression
+    DictionaryWithoutCompression,
+
+    /// Envelope is cut short or its compressed data is damaged
+    InvalidEnvelope,
+
+    /// Envelope was written with an algorithm this version doesn't know
+    UnknownAlgorithm(u8),
+
+    /// Envelope was written with a dictionary this version doesn't ship
+    UnknownDictionary(u32),
+
+    /// Payload is bigger than `MAX_PAYLOAD_LENGTH`
+    TooLarge
+}
+
+impl error::Error for CompressError {}
+
+impl Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressError::InvalidCompression(compression) => {
+                write!(f, "Invalid compression '{}', expected none or zstd", compression)
+            },
+            CompressError::InvalidDictionary(dictionary) => {
+                let names: Vec<&str> = Dictionary::ALL.iter().map(Dictionary::name).collect();
+                write!(f, "Invalid dictionary '{}', expected {}", dictionary, names.join(", "))
+            },
+            CompressError::DictionaryWithoutCompression => write!(f, "A dictionary requires --compress zstd"),
+            CompressError::InvalidEnvelope => write!(f, "Compressed payload is damaged"),
+            CompressError::UnknownAlgorithm(algorithm) => {
+                write!(f, "Payload is compressed with unknown algorithm {}", algorithm)
+            },
+            CompressError::UnknownDictionary(id) => {
+                write!(f, "Payload is compressed with unknown dictionary {}", id)
+            },
+            CompressError::TooLarge => {
+                write!(f, "Payload is bigger than the {} bytes compression is limited to", MAX_PAYLOAD_LENGTH)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = br#"{"type": "message", "id": 42, "user": {"username": "ferris", "email": "ferris@example.com"}}"#;
+
+    #[test]
+    fn test_round_trip() {
+        for dictionary in [None, Some(Dictionary::BuiltinText)] {
+            let compressed = compress(MESSAGE, Compression::Zstd, dictionary).unwrap();
+
+            assert!(compressed.starts_with(&MAGIC));
+            assert_eq!(decompress(&compressed).unwrap(), MESSAGE);
+        }
+
+        // Payloads without an envelope are left alone
+        assert_eq!(compress(MESSAGE, Compression::None, None).unwrap(), MESSAGE);
+        assert_eq!(decompress(MESSAGE).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn test_dictionary_shrinks_small_payloads() {
+        let plain = compress(MESSAGE, Compression::Zstd, None).unwrap();
+        let with_dictionary = compress(MESSAGE, Compression::Zstd, Some(Dictionary::BuiltinText)).unwrap();
+
+        assert_eq!(&with_dictionary[5..9], &[0, 0, 0, 1]);
+        assert!(with_dictionary.len() < plain.len());
+        assert!(with_dictionary.len() < plain.len() * 2 / 3);
+    }
+
+    #[test]
+    fn test_invalid_envelopes() {
+        let compressed = compress(MESSAGE, Compression::Zstd, Some(Dictionary::BuiltinText)).unwrap();
+
+        let mut unknown_dictionary = compressed.clone();
+        unknown_dictionary[8] = 9;
+        assert!(matches!(
+            decompress(&unknown_dictionary),
+            Err(Error::Compress(CompressError::UnknownDictionary(9)))
+        ));
+
+        let mut unknown_algorithm = compressed.clone();
+        unknown_algorithm[4] = 7;
+        assert!(matches!(
+            decompress(&unknown_algorithm),
+            Err(Error::Compress(CompressError::UnknownAlgorithm(7)))
+        ));
+
+        assert!(decompress(&compressed[..HEADER_LENGTH - 1]).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 4]).is_err());
+        assert!(compress(MESSAGE, Compression::None, Some(Dictionary::BuiltinText)).is_err());
+    }
+
+    #[test]
+    fn test_payload_limit() {
+        let oversized = vec![0; MAX_PAYLOAD_LENGTH + 1];
+        assert!(matches!(compress(&oversized, Compression::Zstd, None), Err(Error::Compress(CompressError::TooLarge))));
+
+        // A bomb of a few hundred bytes is refused once it decompresses past the limit
+        let mut bomb = MAGIC.to_vec();
+        bomb.push(ZSTD_ALGORITHM);
+        bomb.extend_from_slice(&[0; 4]);
+        bomb.extend(zstd::encode_all(&oversized[..], ZSTD_LEVEL).unwrap());
+
+        assert!(bomb.len() < 1024);
+        assert!(matches!(decompress(&bomb), Err(Error::Compress(CompressError::TooLarge))));
+        assert!(decompress(&compress(&oversized[1..], Compression::Zstd, None).unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display};
+
+use crc::crc32;
+
+use crate::chunk::Chunk;
+use crate::compress;
+use crate::envelope;
+use crate::png::Png;
+use crate::seal;
+use crate::Result;
+
+/// Start of a delta, which tells it apart from a version stored whole once the payload is decompressed
+const MAGIC: [u8; 4] = [envelope::LEAD, b'P', b'D', 0x1A];
+
+/// Magic, checksum of the base and length of the new version
+const HEADER_LENGTH: usize = MAGIC.len() + 4 + 4;
+
+/// Shortest run of bytes copied from the base, shorter runs take less room inserted as they are
+const MIN_COPY: usize = 8;
+
+/// Positions of a run of bytes remembered in the base, so repetitive bases don't make diffing quadratic
+const MAX_CANDIDATES: usize = 16;
+
+const COPY: u8 = 0;
+const INSERT: u8 = 1;
+
+/// Binary diff turning `base` into `target`, wrapped in an envelope recording the checksum of the base
+///
+/// The diff is made of copies of runs of the base and bytes inserted as they are, so a new version of a
+/// configuration file mostly made of the previous one takes a few bytes
+pub fn diff(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = MAGIC.to_vec();
+    delta.extend_from_slice(&crc32::checksum_ieee(base).to_be_bytes());
+    delta.extend_from_slice(&(target.len() as u32).to_be_bytes());
+
+    let mut runs: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for offset in 0..base.len().saturating_sub(MIN_COPY - 1) {
+        let candidates = runs.entry(&base[offset..offset + MIN_COPY]).or_default();
+        if candidates.len() < MAX_CANDIDATES {
+            candidates.push(offset);
+        }
+    }
+
+    let mut inserted = Vec::new();
+    let mut position = 0;
+
+    while position < target.len() {
+        let longest = target
+            .get(position..position + MIN_COPY)
+            .and_then(|run| runs.get(run))
+            .and_then(|candidates| {
+                candidates
+                    .iter()
+                    .map(|&offset| (offset, common_length(&base[offset..], &target[position..])))
+                    .max_by_key(|&(_, length)| length)
+            });
+
+        match longest {
+            Some((offset, length)) => {
+                push_insert(&mut delta, &mut inserted);
+                delta.push(COPY);
+                push_varint(&mut delta, offset as u64);
+                push_varint(&mut delta, length as u64);
+                position += length;
+            },
+            None => {
+                inserted.push(target[position]);
+                position += 1;
+            }
+        }
+    }
+
+    push_insert(&mut delta, &mut inserted);
+
+    delta
+}
+
+/// Whether a payload is a delta rather than a whole message
+pub fn is_delta(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// New version out of the base and a delta made against it
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    if !is_delta(delta) || delta.len() < HEADER_LENGTH {
+        return Err(DeltaError::InvalidDelta.into());
+    }
+
+    let checksum = u32::from_be_bytes([delta[4], delta[5], delta[6], delta[7]]);
+
+    if checksum != crc32::checksum_ieee(base) {
+        return Err(DeltaError::BaseMismatch.into());
+    }
+
+    let length = u32::from_be_bytes([delta[8], delta[9], delta[10], delta[11]]) as usize;
+
+    if length > Chunk::MAX_LENGTH as usize {
+        return Err(DeltaError::InvalidDelta.into());
+    }
+
+    // The length comes from the file, so the target only grows as copies and inserts fill it
+    let mut target = Vec::new();
+    let mut ops = &delta[HEADER_LENGTH..];
+
+    while let Some((&op, rest)) = ops.split_first() {
+        ops = rest;
+
+        let bytes = match op {
+            COPY => {
+                let offset = read_varint(&mut ops)? as usize;
+                let count = read_varint(&mut ops)? as usize;
+                base.get(offset..offset.saturating_add(count))
+            },
+            INSERT => {
+                let count = read_varint(&mut ops)? as usize;
+                let bytes = ops.get(..count);
+                ops = ops.get(count..).unwrap_or_default();
+                bytes
+            },
+            _ => None
+        };
+
+        match bytes {
+            Some(bytes) if target.len() + bytes.len() <= length => target.extend_from_slice(bytes),
+            _ => return Err(DeltaError::InvalidDelta.into())
+        }
+    }
+
+    if target.len() != length {
+        return Err(DeltaError::InvalidDelta.into());
+    }
+
+    Ok(target)
+}
+
+/// Latest version of the message hidden in chunks of a type, `None` when there is no such chunk
+///
+/// The first chunk of the type holds the whole message, and every delta chunk of the type after it a new version
+/// made against the version before. Other chunks of the type are left out, as decode always showed the first one
+pub fn latest(png: &Png, chunk_type: &str) -> Result<Option<Vec<u8>>> {
+    let mut latest: Option<Vec<u8>> = None;
+
+    for chunk in png.chunks().iter().filter(|chunk| chunk.chunk_type().to_string() == chunk_type) {
+        let data = compress::decompress(seal::strip(chunk.data())?)?;
+
+        latest = match latest {
+            None if is_delta(&data) => return Err(DeltaError::MissingBase.into()),
+            None => Some(envelope::unescape(&data).to_vec()),
+            Some(base) if is_delta(&data) => Some(apply(&base, &data)?),
+            Some(base) => Some(base)
+        };
+    }
+
+    Ok(latest)
+}
+
+/// Remove the message hidden in chunks of a type: its first chunk and every delta chunk applied to it by `latest`
+///
+/// Deltas left behind would have no version to apply to, and decode would fail on them. Returns the first chunk and
+/// the number of deltas removed along with it
+pub fn remove(png: &mut Png, chunk_type: &str) -> Result<(Chunk, usize)> {
+    let first = png.remove_chunk(chunk_type)?;
+
+    // Chunks whose payload can't be opened aren't deltas decode would apply, so they are left alone
+    let deltas: Vec<usize> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| chunk.chunk_type().to_string() == chunk_type)
+        .filter(|(_, chunk)| {
+            seal::strip(chunk.data())
+                .and_then(compress::decompress)
+                .is_ok_and(|data| is_delta(&data))
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    for index in deltas.iter().rev() {
+        png.remove_chunk_at(*index)?;
+    }
+
+    Ok((first, deltas.len()))
+}
+
+/// Index right after the last chunk of a type, where a delta against the latest version goes
+pub fn insert_index(png: &Png, chunk_type: &str) -> Option<usize> {
+    png.chunks().iter().rposition(|chunk| chunk.chunk_type().to_string() == chunk_type).map(|index| index + 1)
+}
+
+fn common_length(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn push_insert(delta: &mut Vec<u8>, inserted: &mut Vec<u8>) {
+    if !inserted.is_empty() {
+        delta.push(INSERT);
+        push_varint(delta, inserted.len() as u64);
+        delta.append(inserted);
+    }
+}
+
+/// LEB128, seven bits per byte with the high bit set on every byte but the last
+fn push_varint(delta: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        delta.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    delta.push(value as u8);
+}
+
+fn read_varint(ops: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = ops.split_first().ok_or(DeltaError::InvalidDelta)?;
+        *ops = rest;
+        value |= u64::from(byte & 0x7F) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(DeltaError::InvalidDelta.into())
+}
+
+/// Delta errors
+#[derive(Debug)]
+pub enum DeltaError {
+    /// Delta is cut short or refers to bytes its base doesn't have
+    InvalidDelta,
+
+    /// Delta was made against another version than the one it follows
+    BaseMismatch,
+
+    /// The first chunk of the type is a delta, with no version before it
+    MissingBase,
+
+    /// Deltas need chunks to follow one another, so lsb mode can't store them
+    LsbUnsupported
+}
+
+impl error::Error for DeltaError {}
+
+impl Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeltaError::InvalidDelta => write!(f, "Delta payload is damaged"),
+            DeltaError::BaseMismatch => write!(f, "Delta payload was made against another version of the message"),
+            DeltaError::MissingBase => write!(f, "Delta payload has no previous version to apply to"),
+            DeltaError::LsbUnsupported => write!(f, "Deltas can only be stored in chunks, not in lsb mode")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::Error;
+    use std::str::FromStr;
+
+    const CONFIG: &[u8] = br#"{"server": {"host": "localhost", "port": 8080, "workers": 4}, "log": {"level": "info"}}"#;
+    const UPDATED: &[u8] = br#"{"server": {"host": "localhost", "port": 9090, "workers": 4}, "log": {"level": "debug"}}"#;
+
+    fn chunk(data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str("ruSt").unwrap(), data)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let delta = diff(CONFIG, UPDATED);
+
+        assert!(is_delta(&delta));
+        assert!(delta.len() < UPDATED.len() / 2);
+        assert_eq!(apply(CONFIG, &delta).unwrap(), UPDATED);
+
+        for (base, target) in [(&b""[..], &b"new"[..]), (&b"old"[..], &b""[..]), (UPDATED, CONFIG)] {
+            assert_eq!(apply(base, &diff(base, target)).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn test_invalid_deltas() {
+        let delta = diff(CONFIG, UPDATED);
+
+        assert!(matches!(apply(UPDATED, &delta), Err(Error::Delta(DeltaError::BaseMismatch))));
+        assert!(matches!(apply(CONFIG, &delta[..delta.len() - 1]), Err(Error::Delta(DeltaError::InvalidDelta))));
+        assert!(matches!(apply(CONFIG, &delta[..HEADER_LENGTH - 1]), Err(Error::Delta(DeltaError::InvalidDelta))));
+    }
+
+    #[test]
+    fn test_latest() {
+        let mut png = Png::from_chunks(vec![chunk(CONFIG.to_vec())]);
+        assert_eq!(latest(&png, "ruSt").unwrap().unwrap(), CONFIG);
+        assert!(latest(&png, "teSt").unwrap().is_none());
+
+        png.append_chunk(chunk(diff(CONFIG, UPDATED)));
+        assert_eq!(latest(&png, "ruSt").unwrap().unwrap(), UPDATED);
+        assert_eq!(insert_index(&png, "ruSt"), Some(2));
+
+        // A delta with nothing before it can't be applied
+        let orphan = Png::from_chunks(vec![chunk(diff(CONFIG, UPDATED))]);
+        assert!(matches!(latest(&orphan, "ruSt"), Err(Error::Delta(DeltaError::MissingBase))));
+
+        // A version stored whole that starts like a delta is escaped, so it isn't applied as one
+        let lookalike = diff(CONFIG, UPDATED);
+        let escaped = Png::from_chunks(vec![chunk(envelope::escape(&lookalike))]);
+        assert_eq!(latest(&escaped, "ruSt").unwrap().unwrap(), lookalike);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut png = Png::from_chunks(vec![
+            chunk(CONFIG.to_vec()),
+            chunk(diff(CONFIG, UPDATED)),
+            chunk(b"unrelated".to_vec()),
+            chunk(diff(UPDATED, CONFIG))
+        ]);
+
+        let (first, deltas) = remove(&mut png, "ruSt").unwrap();
+
+        assert_eq!(first.data(), CONFIG);
+        assert_eq!(deltas, 2);
+        assert_eq!(latest(&png, "ruSt").unwrap().unwrap(), b"unrelated");
+    }
+
+    #[test]
+    fn test_huge_declared_length() {
+        let mut delta = MAGIC.to_vec();
+        delta.extend_from_slice(&crc32::checksum_ieee(CONFIG).to_be_bytes());
+        delta.extend_from_slice(&Chunk::MAX_LENGTH.to_be_bytes());
+        delta.extend_from_slice(&[INSERT, 2, b'h', b'i']);
+
+        assert!(matches!(apply(CONFIG, &delta), Err(Error::Delta(DeltaError::InvalidDelta))));
+    }
+}
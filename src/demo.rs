@@ -0,0 +1,79 @@
+use std::convert::TryFrom;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::image_data::{self, ImageHeader, Scanline};
+use crate::metadata::TextEntry;
+use crate::png::Png;
+use crate::Result;
+
+/// Size in pixels of the sample image
+pub const SAMPLE_WIDTH: u32 = 32;
+pub const SAMPLE_HEIGHT: u32 = 16;
+
+/// Chunk type the demo hides its message in
+pub const MESSAGE_CHUNK_TYPE: &str = "ruSt";
+
+/// What every step of the demo shows, in order
+pub const EXPLANATIONS: [&str; 4] = [
+    "A PNG file is an 8 byte signature followed by chunks. IHDR holds the image size and color type, \
+     IDAT the compressed pixels and IEND marks the end of the file. The tEXt chunk is optional metadata.",
+    "Messages are hidden in a chunk of their own, right before IEND. Its type ruSt starts with a lowercase letter, \
+     so it is ancillary and viewers skip it, and its second letter is lowercase too, so it is private and can't clash \
+     with a standard chunk. The image looks exactly the same.",
+    "Decoding finds the first chunk of the type and reads its data back. The CRC stored with every chunk proves the \
+     message wasn't damaged.",
+    "Now try it on your own images, or run `pngme wizard` to be guided through it:"
+];
+
+/// Small truecolor gradient with a title, generated so the demo needs no image of its own
+pub fn sample_png() -> Result<Png> {
+    let header = ImageHeader {
+        width: SAMPLE_WIDTH,
+        height: SAMPLE_HEIGHT,
+        bit_depth: 8,
+        color_type: 2,
+        compression_method: 0,
+        filter_method: 0,
+        interlace_method: 0
+    };
+
+    // Red grows from left to right, green from top to bottom, and blue stays constant
+    let scanlines: Vec<Scanline> = (0..SAMPLE_HEIGHT)
+        .map(|y| Scanline {
+            filter: 0,
+            data: (0..SAMPLE_WIDTH)
+                .flat_map(|x| [(x * 255 / (SAMPLE_WIDTH - 1)) as u8, (y * 255 / (SAMPLE_HEIGHT - 1)) as u8, 160])
+                .collect()
+        })
+        .collect();
+
+    let data = image_data::deflate(&image_data::filter(&header, &scanlines)?)?;
+    let title = TextEntry { keyword: String::from("Title"), text: String::from("pngme demo") };
+
+    Ok(Png::from_chunks(vec![
+        Chunk::new(ChunkType::try_from(*b"IHDR")?, header.as_bytes()),
+        title.to_chunk()?,
+        Chunk::new(ChunkType::try_from(*b"IDAT")?, data),
+        Chunk::new(ChunkType::try_from(*b"IEND")?, Vec::new())
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata;
+
+    #[test]
+    fn test_sample_png() {
+        let png = sample_png().unwrap();
+        let bytes = png.as_bytes();
+
+        // The sample is a valid image
+        let png = Png::try_from(&bytes[..]).unwrap();
+        let header = ImageHeader::from_png(&png).unwrap();
+        assert_eq!((header.width, header.height), (SAMPLE_WIDTH, SAMPLE_HEIGHT));
+        assert_eq!(image_data::rgba_pixels(&png).unwrap()[SAMPLE_WIDTH as usize - 1], [255, 0, 160, 255]);
+        assert_eq!(metadata::keyword_text(&png, "Title").unwrap(), Some(String::from("pngme demo")));
+    }
+}
@@ -0,0 +1,295 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::png::Png;
+use crate::settings::Settings;
+use crate::{Error, Result};
+
+/// Kind of warning, with a name and a stable code so it can be allowed in strict mode or ignored
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Rule {
+    /// Chunks follow the IEND chunk
+    TrailingData,
+
+    /// The file has no IEND chunk
+    MissingIend,
+
+    /// The first chunk isn't IHDR
+    MisplacedIhdr,
+
+    /// A command working on many files left one of them alone
+    SkippedFile,
+
+    /// The payload scanner flagged a payload without blocking it
+    FlaggedPayload,
+
+    /// A file with a .png extension doesn't start with the PNG signature
+    SignatureMismatch,
+
+    /// A file starting with the PNG signature lacks a .png extension
+    ExtensionMismatch,
+
+    /// A symbolic link in a directory wasn't followed
+    Symlink,
+
+    /// A fifo, socket or device in a directory wasn't read
+    SpecialFile,
+
+    /// A file in a directory is on another filesystem
+    OtherFilesystem,
+
+    /// The image was modified since a sealed message was hidden in it
+    ModifiedCarrier,
+
+    /// A compressed stream has no compression method or an unknown one
+    CorruptMethod,
+
+    /// The zlib header of a compressed stream is damaged or not allowed in PNG
+    CorruptZlibHeader,
+
+    /// The deflate data of a compressed stream can't be decompressed
+    CorruptDeflate,
+
+    /// The Adler-32 checksum of a compressed stream is missing or doesn't match
+    CorruptAdler
+}
+
+impl Rule {
+    pub const ALL: [Rule; 15] = [
+        Rule::TrailingData,
+        Rule::MissingIend,
+        Rule::MisplacedIhdr,
+        Rule::SkippedFile,
+        Rule::FlaggedPayload,
+        Rule::SignatureMismatch,
+        Rule::ExtensionMismatch,
+        Rule::Symlink,
+        Rule::SpecialFile,
+        Rule::OtherFilesystem,
+        Rule::ModifiedCarrier,
+        Rule::CorruptMethod,
+        Rule::CorruptZlibHeader,
+        Rule::CorruptDeflate,
+        Rule::CorruptAdler
+    ];
+
+    /// Code of the rule, never reused once assigned
+    pub fn code(&self) -> &'static str {
+        match self {
+            Rule::TrailingData => "PNGME001",
+            Rule::MissingIend => "PNGME002",
+            Rule::MisplacedIhdr => "PNGME003",
+            Rule::SkippedFile => "PNGME004",
+            Rule::FlaggedPayload => "PNGME005",
+            Rule::SignatureMismatch => "PNGME006",
+            Rule::ExtensionMismatch => "PNGME007",
+            Rule::Symlink => "PNGME008",
+            Rule::SpecialFile => "PNGME009",
+            Rule::OtherFilesystem => "PNGME010",
+            Rule::ModifiedCarrier => "PNGME011",
+            Rule::CorruptMethod => "PNGME012",
+            Rule::CorruptZlibHeader => "PNGME013",
+            Rule::CorruptDeflate => "PNGME014",
+            Rule::CorruptAdler => "PNGME015"
+        }
+    }
+
+    /// Name of the rule, given to `--allow` or `--ignore` just like its code
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::TrailingData => "trailing-data",
+            Rule::MissingIend => "missing-iend",
+            Rule::MisplacedIhdr => "misplaced-ihdr",
+            Rule::SkippedFile => "skipped-file",
+            Rule::FlaggedPayload => "flagged-payload",
+            Rule::SignatureMismatch => "signature-mismatch",
+            Rule::ExtensionMismatch => "extension-mismatch",
+            Rule::Symlink => "symlink",
+            Rule::SpecialFile => "special-file",
+            Rule::OtherFilesystem => "other-filesystem",
+            Rule::ModifiedCarrier => "modified-carrier",
+            Rule::CorruptMethod => "corrupt-method",
+            Rule::CorruptZlibHeader => "corrupt-zlib-header",
+            Rule::CorruptDeflate => "corrupt-deflate",
+            Rule::CorruptAdler => "corrupt-adler32"
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Rule::ALL
+            .iter()
+            .find(|rule| rule.name() == s || rule.code().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| DiagnosticsError::InvalidRule(s.to_string()).into())
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Whether warnings stop the command, and which ones are let through anyway
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Policy {
+    /// Every warning not allowed fails the command
+    pub strict: bool,
+
+    /// Rules only warned about in strict mode
+    pub allowed: Vec<Rule>,
+
+    /// Rules neither warned about nor failing the command, known to be acceptable
+    pub ignored: Vec<Rule>
+}
+
+impl Policy {
+    /// Whether warnings of the rule are silenced
+    pub fn ignores(&self, rule: Rule) -> bool {
+        self.ignored.contains(&rule)
+    }
+
+    /// Fail with the warning in strict mode unless its rule is allowed
+    pub fn check(&self, rule: Rule, message: &str) -> Result<()> {
+        match self.strict && !self.allowed.contains(&rule) {
+            true => Err(DiagnosticsError::Strict(rule, message.to_string()).into()),
+            false => Ok(())
+        }
+    }
+}
+
+/// Warn about the ways the chunks of a PNG file deviate from the specification without making it unreadable, or fail
+/// in strict mode
+pub fn check_structure(settings: &Settings, filepath: &Path, png: &Png) -> Result<()> {
+    let chunks = png.chunks();
+    let is = |index: usize, chunk_type: &str| chunks[index].chunk_type().to_string() == chunk_type;
+
+    if !chunks.is_empty() && !is(0, "IHDR") {
+        let message = format!("{}: the first chunk is {}, not IHDR", filepath.display(), chunks[0].chunk_type());
+        settings.warn(Rule::MisplacedIhdr, message)?;
+    }
+
+    match (0..chunks.len()).position(|index| is(index, "IEND")) {
+        None => settings.warn(Rule::MissingIend, format!("{}: there is no IEND chunk", filepath.display()))?,
+        Some(index) if index + 1 < chunks.len() => {
+            let count = chunks.len() - index - 1;
+            let message =
+                format!("{}: {} chunk{} after IEND", filepath.display(), count, if count == 1 { "" } else { "s" });
+            settings.warn(Rule::TrailingData, message)?
+        },
+        Some(_) => {}
+    }
+
+    Ok(())
+}
+
+/// Diagnostics errors
+#[derive(Debug)]
+pub enum DiagnosticsError {
+    /// Rule given to `--allow` doesn't exist
+    InvalidRule(String),
+
+    /// A warning was raised in strict mode
+    Strict(Rule, String)
+}
+
+impl error::Error for DiagnosticsError {}
+
+impl Display for DiagnosticsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticsError::InvalidRule(rule) => {
+                let names: Vec<&str> = Rule::ALL.iter().map(Rule::name).collect();
+                write!(f, "Invalid rule '{}', expected a code such as PNGME001 or one of {}", rule, names.join(", "))
+            },
+            DiagnosticsError::Strict(rule, message) => write!(
+                f,
+                "{} [{}] (strict mode, pass --allow {} to only warn)",
+                message,
+                rule.code(),
+                rule
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new())
+    }
+
+    #[test]
+    fn test_rule_from_str() {
+        for rule in Rule::ALL.iter() {
+            assert_eq!(Rule::from_str(rule.name()).unwrap(), *rule);
+            assert_eq!(Rule::from_str(rule.code()).unwrap(), *rule);
+        }
+
+        assert_eq!(Rule::from_str("pngme004").unwrap(), Rule::SkippedFile);
+        assert!(Rule::from_str("trailing").is_err());
+        assert!(Rule::from_str("PNGME999").is_err());
+    }
+
+    #[test]
+    fn test_codes_are_distinct() {
+        for (index, rule) in Rule::ALL.iter().enumerate() {
+            assert!(Rule::ALL[index + 1..].iter().all(|other| other.code() != rule.code()));
+        }
+    }
+
+    fn settings(policy: Policy) -> Settings {
+        Settings { policy, ..Settings::default() }
+    }
+
+    #[test]
+    fn test_policy() {
+        let lenient = settings(Policy::default());
+        assert!(lenient.warn(Rule::TrailingData, String::from("warning")).is_ok());
+
+        let strict =
+            settings(Policy { strict: true, allowed: vec![Rule::SkippedFile], ignored: vec![Rule::FlaggedPayload] });
+        assert!(strict.warn(Rule::SkippedFile, String::from("warning")).is_ok());
+        assert!(strict.warn(Rule::FlaggedPayload, String::from("warning")).is_ok());
+
+        let error = strict.warn(Rule::TrailingData, String::from("2 chunks after IEND")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2 chunks after IEND [PNGME001] (strict mode, pass --allow trailing-data to only warn)"
+        );
+    }
+
+    #[test]
+    fn test_check_structure() {
+        let path = Path::new("dice.png");
+        let valid = Png::from_chunks(vec![chunk("IHDR"), chunk("IEND")]);
+        let trailing = Png::from_chunks(vec![chunk("IHDR"), chunk("IEND"), chunk("ruSt")]);
+
+        let strict = settings(Policy { strict: true, ..Policy::default() });
+        assert!(check_structure(&strict, path, &valid).is_ok());
+        assert!(matches!(
+            check_structure(&strict, path, &trailing),
+            Err(Error::Diagnostics(DiagnosticsError::Strict(Rule::TrailingData, _)))
+        ));
+
+        let missing_iend = Png::from_chunks(vec![chunk("IHDR")]);
+        assert!(matches!(
+            check_structure(&strict, path, &missing_iend),
+            Err(Error::Diagnostics(DiagnosticsError::Strict(Rule::MissingIend, _)))
+        ));
+
+        // Allowed deviations are only warned about
+        let allowing = settings(Policy { strict: true, allowed: vec![Rule::TrailingData], ..Policy::default() });
+        assert!(check_structure(&allowing, path, &trailing).is_ok());
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+
+use crate::chunk::Chunk;
+use crate::locale::Locale;
+use crate::output::Record;
+use crate::png::Png;
+
+/// Chunk type, length and CRC of a chunk, enough to tell whether two chunks differ
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ChunkSummary {
+    pub chunk_type: String,
+    pub length: u32,
+    pub crc: u32
+}
+
+impl From<&Chunk> for ChunkSummary {
+    fn from(chunk: &Chunk) -> Self {
+        ChunkSummary {
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+            crc: chunk.crc()
+        }
+    }
+}
+
+impl ChunkSummary {
+    /// Chunk type, length and CRC as shown to a person, the length written as in the locale
+    pub fn describe(&self, locale: Locale) -> String {
+        format!("{} (length: {}, crc: {})", self.chunk_type, locale.number(self.length.into()), self.crc)
+    }
+}
+
+/// A difference between the chunks of two PNGs
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Change {
+    /// Chunk only found in the second PNG
+    Added(ChunkSummary),
+
+    /// Chunk only found in the first PNG
+    Removed(ChunkSummary),
+
+    /// Chunk of the same type found in both PNGs, with a different length or CRC
+    Modified(ChunkSummary, ChunkSummary),
+
+    /// Chunk found in both PNGs whose place among the other chunks changed, from its index in the first PNG to its
+    /// index in the second one
+    Moved { chunk: ChunkSummary, from: usize, to: usize }
+}
+
+impl Change {
+    /// Change as shown to a person, lengths written as in the locale
+    pub fn describe(&self, locale: Locale) -> String {
+        match self {
+            Change::Added(chunk) => format!("+ {}", chunk.describe(locale)),
+            Change::Removed(chunk) => format!("- {}", chunk.describe(locale)),
+            Change::Modified(before, after) => format!(
+                "~ {} (length: {} -> {}, crc: {} -> {})",
+                before.chunk_type,
+                locale.number(before.length.into()),
+                locale.number(after.length.into()),
+                before.crc,
+                after.crc
+            ),
+            Change::Moved { chunk, from, to } => {
+                format!("> {} moved from index {} to {}", chunk.chunk_type, from, to)
+            }
+        }
+    }
+}
+
+/// Chunks of two PNGs side by side, the first PNG's on the left
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Row {
+    pub alignment: Alignment,
+
+    /// Index and summary of the chunk of the first PNG, if the row has one
+    pub before: Option<(usize, ChunkSummary)>,
+
+    /// Index and summary of the chunk of the second PNG, if the row has one
+    pub after: Option<(usize, ChunkSummary)>
+}
+
+/// How the chunks of a row relate to each other
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Alignment {
+    /// Same chunk on both sides
+    Unchanged,
+
+    /// Chunk of the same type on both sides, with a different length or CRC
+    Modified,
+
+    /// Chunk only found in the first PNG
+    Removed,
+
+    /// Chunk only found in the second PNG
+    Added,
+
+    /// Chunk of the first PNG found elsewhere in the second one, at the given index
+    MovedTo(usize),
+
+    /// Chunk of the second PNG found elsewhere in the first one, at the given index
+    MovedFrom(usize)
+}
+
+impl Alignment {
+    fn marker(&self) -> char {
+        match self {
+            Alignment::Unchanged => '=',
+            Alignment::Modified => '~',
+            Alignment::Removed => '-',
+            Alignment::Added => '+',
+            Alignment::MovedTo(_) => '>',
+            Alignment::MovedFrom(_) => '<'
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Alignment::Unchanged => "unchanged",
+            Alignment::Modified => "modified",
+            Alignment::Removed => "removed",
+            Alignment::Added => "added",
+            Alignment::MovedTo(_) | Alignment::MovedFrom(_) => "moved"
+        }
+    }
+}
+
+/// Chunks of two PNGs matched with each other, each with its index in its PNG
+struct Matching {
+    before: Vec<(usize, ChunkSummary)>,
+    after: Vec<(usize, ChunkSummary)>,
+
+    /// Position in `after` of the chunk matching each chunk of `before`
+    partners: Vec<Option<usize>>,
+
+    /// Whether each chunk of `before` kept its place relative to the other matched chunks
+    anchored: Vec<bool>
+}
+
+/// Match the n-th chunk of a type in the first PNG with the n-th chunk of the same type in the second one
+///
+/// The largest set of matched chunks appearing in the same order in both PNGs stays in place, every other matched
+/// chunk is a move. Inserting a chunk shifts the index of every chunk after it, so indices alone would make them
+/// all look moved
+fn matching(before: &Png, after: &Png, ignored: &[&str]) -> Matching {
+    let before = summaries(before, ignored);
+    let after = summaries(after, ignored);
+
+    // Chunks of the second PNG still waiting for a match, grouped by type
+    let mut unmatched: HashMap<&str, Vec<usize>> = HashMap::new();
+
+    for (position, (_, chunk)) in after.iter().enumerate().rev() {
+        unmatched.entry(chunk.chunk_type.as_str()).or_default().push(position);
+    }
+
+    let partners: Vec<Option<usize>> = before
+        .iter()
+        .map(|(_, chunk)| unmatched.get_mut(chunk.chunk_type.as_str()).and_then(|positions| positions.pop()))
+        .collect();
+
+    let matched: Vec<usize> = partners.iter().flatten().copied().collect();
+    let mut in_order = longest_increasing(&matched).into_iter();
+    let anchored = partners.iter().map(|partner| partner.is_some() && in_order.next() == Some(true)).collect();
+
+    Matching { before, after, partners, anchored }
+}
+
+/// Compare the chunks of two PNGs, skipping the given chunk types
+///
+/// The n-th chunk of a type in the first PNG is compared with the n-th chunk of the same type in the second one.
+/// Removed and modified chunks are listed in the first PNG's order, followed by added chunks in the second PNG's order
+/// and then moved chunks
+pub fn diff(before: &Png, after: &Png, ignored: &[&str]) -> Vec<Change> {
+    let Matching { before, after, partners, anchored } = matching(before, after, ignored);
+
+    let mut changes = Vec::new();
+    let mut moves = Vec::new();
+    let mut matched = vec![false; after.len()];
+
+    for (((index, chunk), partner), anchored) in before.into_iter().zip(partners).zip(anchored) {
+        match partner {
+            Some(position) => {
+                matched[position] = true;
+                let (after_index, after_chunk) = &after[position];
+
+                if !anchored {
+                    moves.push(Change::Moved { chunk: chunk.clone(), from: index, to: *after_index });
+                }
+
+                if *after_chunk != chunk {
+                    changes.push(Change::Modified(chunk, after_chunk.clone()));
+                }
+            },
+            None => changes.push(Change::Removed(chunk))
+        }
+    }
+
+    changes.extend(
+        after
+            .into_iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|((_, chunk), _)| Change::Added(chunk))
+    );
+    changes.extend(moves);
+
+    changes
+}
+
+/// Every chunk of two PNGs, aligned so the chunks staying in place share a row
+///
+/// Between two such rows come the chunks of the first PNG that were removed or moved away, then the chunks of the
+/// second PNG that were added or moved there
+pub fn side_by_side(before: &Png, after: &Png, ignored: &[&str]) -> Vec<Row> {
+    let Matching { before, after, partners, anchored } = matching(before, after, ignored);
+
+    let mut origins = vec![None; after.len()];
+    for (position, partner) in partners.iter().enumerate() {
+        if let Some(partner) = partner {
+            origins[*partner] = Some(before[position].0);
+        }
+    }
+
+    let after_only = |position: usize| Row {
+        alignment: origins[position].map_or(Alignment::Added, Alignment::MovedFrom),
+        before: None,
+        after: Some(after[position].clone())
+    };
+
+    let mut rows = Vec::new();
+    let mut next = 0;
+
+    for (position, chunk) in before.iter().enumerate() {
+        let row = match partners[position] {
+            Some(partner) if anchored[position] => {
+                rows.extend((next..partner).map(after_only));
+                next = partner + 1;
+
+                let alignment = if after[partner].1 == chunk.1 { Alignment::Unchanged } else { Alignment::Modified };
+                Row { alignment, before: Some(chunk.clone()), after: Some(after[partner].clone()) }
+            },
+            Some(partner) => Row {
+                alignment: Alignment::MovedTo(after[partner].0),
+                before: Some(chunk.clone()),
+                after: None
+            },
+            None => Row { alignment: Alignment::Removed, before: Some(chunk.clone()), after: None }
+        };
+
+        rows.push(row);
+    }
+
+    rows.extend((next..after.len()).map(after_only));
+
+    rows
+}
+
+/// Record of a change, with the length and CRC before and after it, null where the chunk is missing
+pub fn change_record(change: &Change, locale: Locale) -> Record {
+    let (kind, before, after, indices) = match change {
+        Change::Added(chunk) => ("added", None, Some(chunk), None),
+        Change::Removed(chunk) => ("removed", Some(chunk), None, None),
+        Change::Modified(before, after) => ("modified", Some(before), Some(after), None),
+        Change::Moved { chunk, from, to } => ("moved", None, None, Some((chunk, *from, *to)))
+    };
+
+    let chunk_type = before.or(after).or(indices.map(|(chunk, _, _)| chunk)).map(|chunk| chunk.chunk_type.clone());
+
+    Record::new(change.describe(locale))
+        .field("change", kind)
+        .field("chunk_type", chunk_type)
+        .field("length_before", before.map(|chunk| chunk.length))
+        .field("length_after", after.map(|chunk| chunk.length))
+        .field("crc_before", before.map(|chunk| chunk.crc))
+        .field("crc_after", after.map(|chunk| chunk.crc))
+        .field("index_before", indices.map(|(_, from, _)| from))
+        .field("index_after", indices.map(|(_, _, to)| to))
+}
+
+/// Records of rows, the chunks of the first PNG padded to the same width so both columns line up
+pub fn row_records(rows: &[Row], locale: Locale) -> Vec<Record> {
+    let cell = |side: &Option<(usize, ChunkSummary)>, suffix: String| {
+        side.as_ref()
+            .map_or_else(String::new, |(index, chunk)| format!("[{}] {}{}", index, chunk.describe(locale), suffix))
+    };
+
+    let cells: Vec<(String, String)> = rows
+        .iter()
+        .map(|row| match row.alignment {
+            Alignment::MovedTo(to) => (cell(&row.before, format!(" moved to [{}]", to)), String::new()),
+            Alignment::MovedFrom(from) => (String::new(), cell(&row.after, format!(" moved from [{}]", from))),
+            _ => (cell(&row.before, String::new()), cell(&row.after, String::new()))
+        })
+        .collect();
+
+    let width = cells.iter().map(|(left, _)| left.len()).max().unwrap_or(0);
+
+    rows.iter()
+        .zip(cells)
+        .map(|(row, (left, right))| {
+            let text = format!("{:<width$} {} {}", left, row.alignment.marker(), right, width = width);
+            let index_before = match row.alignment {
+                Alignment::MovedFrom(from) => Some(from),
+                _ => row.before.as_ref().map(|(index, _)| *index)
+            };
+            let index_after = match row.alignment {
+                Alignment::MovedTo(to) => Some(to),
+                _ => row.after.as_ref().map(|(index, _)| *index)
+            };
+            let chunk_type = row.before.as_ref().or(row.after.as_ref()).map(|(_, chunk)| chunk.chunk_type.clone());
+
+            Record::new(text.trim_end().to_string())
+                .field("alignment", row.alignment.name())
+                .field("chunk_type", chunk_type)
+                .field("index_before", index_before)
+                .field("index_after", index_after)
+        })
+        .collect()
+}
+
+fn summaries(png: &Png, ignored: &[&str]) -> Vec<(usize, ChunkSummary)> {
+    png.chunks()
+        .iter()
+        .map(ChunkSummary::from)
+        .enumerate()
+        .filter(|(_, chunk)| !ignored.contains(&chunk.chunk_type.as_str()))
+        .collect()
+}
+
+/// Whether each value belongs to a longest strictly increasing subsequence, found by patience sorting
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // Position of the smallest last value of an increasing subsequence of each length
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+
+    for (position, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&tail| values[tail] < value);
+        previous[position] = length.checked_sub(1).map(|length| tails[length]);
+
+        if length == tails.len() {
+            tails.push(position);
+        } else {
+            tails[length] = position;
+        }
+    }
+
+    let mut kept = vec![false; values.len()];
+    let mut position = tails.last().copied();
+
+    while let Some(current) = position {
+        kept[current] = true;
+        position = previous[current];
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_identical_pngs() {
+        let png = Png::from_chunks(vec![chunk("IHDR", b"header"), chunk("IEND", b"")]);
+        let same = Png::from_chunks(vec![chunk("IHDR", b"header"), chunk("IEND", b"")]);
+
+        assert!(diff(&png, &same, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("IDAT", b"first"),
+            chunk("IDAT", b"second"),
+            chunk("IEND", b"")
+        ]);
+        let after = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("IDAT", b"first"),
+            chunk("IDAT", b"changed"),
+            chunk("ruSt", b"message"),
+            chunk("IEND", b"")
+        ]);
+
+        let changes = diff(&before, &after, &[]);
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Removed(ChunkSummary::from(&chunk("tEXt", b"Title\0Dice"))),
+                Change::Modified(
+                    ChunkSummary::from(&chunk("IDAT", b"second")),
+                    ChunkSummary::from(&chunk("IDAT", b"changed"))
+                ),
+                Change::Added(ChunkSummary::from(&chunk("ruSt", b"message")))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_ignoring_chunk_types() {
+        let before = Png::from_chunks(vec![chunk("IDAT", b"first"), chunk("IEND", b"")]);
+        let after = Png::from_chunks(vec![chunk("IDAT", b"second"), chunk("IDAT", b"third"), chunk("IEND", b"")]);
+
+        assert_eq!(diff(&before, &after, &[]).len(), 2);
+        assert!(diff(&before, &after, &["IDAT"]).is_empty());
+    }
+
+    #[test]
+    fn test_moves() {
+        let before = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("tIME", b"time"),
+            chunk("IDAT", b"data"),
+            chunk("IEND", b"")
+        ]);
+        let after = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("ruSt", b"message"),
+            chunk("tIME", b"time"),
+            chunk("IDAT", b"data"),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("IEND", b"")
+        ]);
+
+        // Only the chunk taken out of order moves, not the ones shifted by the added chunk
+        let moved = ChunkSummary::from(&chunk("tEXt", b"Title\0Dice"));
+        assert_eq!(
+            diff(&before, &after, &[]),
+            vec![
+                Change::Added(ChunkSummary::from(&chunk("ruSt", b"message"))),
+                Change::Moved { chunk: moved, from: 1, to: 4 }
+            ]
+        );
+
+        let alignments: Vec<Alignment> = side_by_side(&before, &after, &[]).iter().map(|row| row.alignment).collect();
+        assert_eq!(
+            alignments,
+            [
+                Alignment::Unchanged,
+                Alignment::MovedTo(4),
+                Alignment::Added,
+                Alignment::Unchanged,
+                Alignment::Unchanged,
+                Alignment::MovedFrom(1),
+                Alignment::Unchanged
+            ]
+        );
+    }
+
+    #[test]
+    fn test_row_records_line_up() {
+        let before =
+            Png::from_chunks(vec![chunk("IHDR", b"header"), chunk("tEXt", b"Title\0Dice"), chunk("IEND", b"")]);
+        let after = Png::from_chunks(vec![chunk("IHDR", b"header"), chunk("IEND", b""), chunk("tEXt", b"Title\0Dice")]);
+
+        let lines: Vec<String> = row_records(&side_by_side(&before, &after, &[]), Locale::C)
+            .iter()
+            .map(|record| record.text.clone())
+            .collect();
+        let column = lines[0].find(" = ").unwrap();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].ends_with("moved to [2] >"));
+        assert_eq!(lines[2].find(" = "), Some(column));
+        assert_eq!(lines[3].find(" < "), Some(column));
+        assert!(lines[3].ends_with("moved from [1]"));
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(longest_increasing(&[0, 3, 1, 2, 4]), [true, false, true, true, true]);
+        assert_eq!(longest_increasing(&[1, 0]), [false, true]);
+        assert!(longest_increasing(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_change_display() {
+        let change = Change::Modified(
+            ChunkSummary { chunk_type: String::from("IDAT"), length: 10, crc: 1 },
+            ChunkSummary { chunk_type: String::from("IDAT"), length: 12, crc: 2 }
+        );
+
+        assert_eq!(change.describe(Locale::C), "~ IDAT (length: 10 -> 12, crc: 1 -> 2)");
+    }
+}
@@ -0,0 +1,239 @@
+//! Differential tests comparing the parser with the png crate's decoder
+//!
+//! Every file of the corpus is decoded by both, along with mutations of it: truncations, removed or duplicated
+//! chunks and flipped bytes. Samples only one of them accepts, or whose IHDR fields differ, are reported.
+//! Known differences between the two are reported with their reason, and any other one fails the test.
+//! The corpus is dice.png plus the PNG files of the directory given in `PNGME_CORPUS`
+//!
+//! Run with `cargo test --features differential -- differential --nocapture`
+
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::iter;
+use std::path::PathBuf;
+
+use crate::chunk::{Chunk, ChunkError};
+use crate::error::PngmeError;
+use crate::image_data::{self, ImageHeader};
+use crate::png::Png;
+use crate::registry;
+use crate::Result;
+
+/// Images with more decoded data are only compared by header, since a mutated header can claim gigabytes
+const MAX_DATA_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Standard chunks allowed to appear more than once
+const REPEATABLE_CHUNKS: [&str; 7] = ["IDAT", "sPLT", "tEXt", "zTXt", "iTXt", "fcTL", "fdAT"];
+
+/// Width, height, bit depth, color type and interlacing, the IHDR fields both decoders report
+type Header = (u32, u32, u8, u8, bool);
+
+/// Decode a file fully with pngme's parser
+fn pngme_header(bytes: &[u8]) -> Result<Header> {
+    let png = Png::try_from(bytes)?;
+    let header = ImageHeader::from_png(&png)?;
+
+    if header.data_length().is_ok_and(|length| length <= MAX_DATA_LENGTH) {
+        image_data::unfilter(&header, &image_data::inflate(&png)?)?;
+    }
+
+    Ok((
+        header.width,
+        header.height,
+        header.bit_depth,
+        header.color_type,
+        header.interlace_method == 1
+    ))
+}
+
+/// Decode a file fully with the png crate
+fn reference_header(bytes: &[u8]) -> std::result::Result<Header, png_decoder::DecodingError> {
+    let mut reader = png_decoder::Decoder::new(Cursor::new(bytes)).read_info()?;
+
+    if reader.output_buffer_size() <= MAX_DATA_LENGTH {
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buffer)?;
+    }
+
+    // Like pngme, read the chunks after the image data up to IEND
+    reader.finish()?;
+
+    let info = reader.info();
+
+    Ok((
+        info.width,
+        info.height,
+        info.bit_depth as u8,
+        info.color_type as u8,
+        info.interlaced
+    ))
+}
+
+/// dice.png and the PNG files of the `PNGME_CORPUS` directory
+fn corpus() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("dice.png")];
+
+    if let Some(directory) = env::var_os("PNGME_CORPUS") {
+        let mut files: Vec<PathBuf> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")))
+            .collect();
+        files.sort();
+
+        paths.extend(files);
+    }
+
+    paths
+}
+
+/// Deterministic variations of a file, each with a description
+fn mutations(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut mutations = Vec::new();
+
+    // Bytes flipped without fixing the CRC, spread over the whole file
+    for offset in (0..bytes.len()).step_by((bytes.len() / 64).max(1)) {
+        let mut flipped = bytes.to_vec();
+        flipped[offset] ^= 0xFF;
+        mutations.push((format!("byte {} flipped", offset), flipped));
+    }
+
+    let png = match Png::try_from(bytes) {
+        Ok(png) => png,
+        Err(_) => return mutations
+    };
+
+    let mut offset = Png::STANDARD_HEADER.len();
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let name = format!("chunk {} ({})", index, chunk.chunk_type());
+
+        // Cut off at the start of the chunk, in its length, in its type and in its data
+        for cut in [0, 2, 6, 10].iter().map(|delta| offset + delta) {
+            if cut < bytes.len() {
+                mutations.push((format!("truncated at byte {}", cut), bytes[..cut].to_vec()));
+            }
+        }
+
+        let mut chunks = png.chunks().to_vec();
+        chunks.remove(index);
+        mutations.push((format!("{} removed", name), Png::from_chunks(chunks).as_bytes()));
+
+        let mut chunks = png.chunks().to_vec();
+        chunks.insert(index, chunk.clone());
+        mutations.push((format!("{} duplicated", name), Png::from_chunks(chunks).as_bytes()));
+
+        // Data bytes flipped with a matching CRC, so the chunk's contents are checked rather than its integrity
+        let length = chunk.data().len();
+        let mut positions = vec![0, length / 2, length.saturating_sub(1)];
+        positions.dedup();
+
+        for position in positions.into_iter().filter(|position| *position < length) {
+            let mut data = chunk.data().to_vec();
+            data[position] ^= 0xFF;
+
+            let mut chunks = png.chunks().to_vec();
+            chunks[index] = Chunk::new(chunk.chunk_type().clone(), data);
+            mutations.push((
+                format!("{} data byte {} flipped", name, position),
+                Png::from_chunks(chunks).as_bytes()
+            ));
+        }
+
+        offset += chunk.length() as usize + 12;
+    }
+
+    mutations
+}
+
+/// Reason pngme and the png crate are known to disagree on a sample, if they are
+fn known_disagreement(bytes: &[u8], pngme: &Result<Header>) -> Option<&'static str> {
+    match pngme {
+        Err(PngmeError::Chunk(ChunkError::InvalidCrc(_, _))) => {
+            Some("the png crate ignores the CRC of ancillary chunks")
+        },
+        Err(PngmeError::Io(_)) => Some("the png crate decodes image data that zlib rejects"),
+        Err(_) => None,
+        Ok(_) => {
+            let png = Png::try_from(bytes).ok()?;
+            let chunk_types: Vec<String> = png
+                .chunks()
+                .iter()
+                .map(|chunk| chunk.chunk_type().to_string())
+                .collect();
+
+            let repeated = chunk_types.iter().enumerate().any(|(index, chunk_type)| {
+                registry::describe(chunk_type).is_some()
+                    && !REPEATABLE_CHUNKS.contains(&chunk_type.as_str())
+                    && chunk_types[..index].contains(chunk_type)
+            });
+
+            if chunk_types.last().map(String::as_str) != Some("IEND") {
+                Some("pngme doesn't require an IEND chunk")
+            } else if repeated {
+                Some("pngme doesn't check which chunks may appear more than once")
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_differential() {
+    let mut samples = 0;
+    let mut known = Vec::new();
+    let mut disagreements = Vec::new();
+
+    for path in corpus() {
+        let bytes = fs::read(&path).unwrap();
+
+        for (mutation, bytes) in iter::once((String::from("original"), bytes.clone())).chain(mutations(&bytes)) {
+            let pngme = pngme_header(&bytes);
+            let reference = reference_header(&bytes);
+            samples += 1;
+
+            let agree = match (&pngme, &reference) {
+                (Ok(pngme), Ok(reference)) => pngme == reference,
+                (Err(_), Err(_)) => true,
+                _ => false
+            };
+
+            if agree {
+                continue;
+            }
+
+            let report = format!(
+                "{} ({}): pngme {:?}, png {:?}",
+                path.display(),
+                mutation,
+                pngme.as_ref().map_err(|error| error.to_string()),
+                reference.as_ref().map_err(|error| error.to_string())
+            );
+
+            // Headers that differ are never expected
+            let reason = match (&pngme, &reference) {
+                (Ok(_), Ok(_)) => None,
+                _ => known_disagreement(&bytes, &pngme)
+            };
+
+            match reason {
+                Some(reason) => known.push(format!("{}\n    known: {}", report, reason)),
+                None => disagreements.push(report)
+            }
+        }
+    }
+
+    for report in known.iter().chain(disagreements.iter()) {
+        println!("{}", report);
+    }
+
+    assert!(
+        disagreements.is_empty(),
+        "{} of {} samples disagree",
+        disagreements.len(),
+        samples
+    );
+}
@@ -0,0 +1,42 @@
+/// First byte of every envelope a payload can be wrapped in: a seal, compression or a delta
+///
+/// Envelopes start with this byte, P, a letter naming them and 0x1A, which no UTF-8 text starts with
+pub const LEAD: u8 = 0x8A;
+
+/// Start of a raw payload whose first byte happens to be `LEAD`, so it isn't opened as an envelope when read back
+const ESCAPE: [u8; 4] = [LEAD, b'P', b'R', 0x1A];
+
+/// Raw payload as it is hidden, escaped only when it starts like an envelope
+///
+/// Text never needs escaping, so messages hidden before escaping existed read back the same
+pub fn escape(payload: &[u8]) -> Vec<u8> {
+    match payload.first() {
+        Some(&LEAD) => ESCAPE.iter().chain(payload.iter()).copied().collect(),
+        _ => payload.to_vec()
+    }
+}
+
+/// Raw payload out of its escape, or as it is when it wasn't escaped
+pub fn unescape(data: &[u8]) -> &[u8] {
+    data.strip_prefix(&ESCAPE[..]).unwrap_or(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(b"message"), b"message");
+        assert_eq!(escape(b""), b"");
+
+        for payload in [&[LEAD][..], &[LEAD, b'P', b'Z', 0x1A, 1, 2], &ESCAPE] {
+            let escaped = escape(payload);
+
+            assert_eq!(&escaped[..ESCAPE.len()], ESCAPE);
+            assert_eq!(unescape(&escaped), payload);
+        }
+
+        assert_eq!(unescape(b"message"), b"message");
+    }
+}
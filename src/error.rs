@@ -0,0 +1,412 @@
+use std::array::TryFromSliceError;
+use std::error;
+use std::fmt::{self, Display};
+use std::io;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+use crate::access::AccessError;
+use crate::apng::ApngError;
+use crate::archive::ArchiveError;
+use crate::assertion::AssertionError;
+use crate::carrier::CarrierError;
+use crate::chunk::ChunkError;
+use crate::chunk_type::ChunkTypeError;
+use crate::compress::CompressError;
+use crate::delta::DeltaError;
+use crate::diagnostics::{DiagnosticsError, Rule};
+use crate::image_data::ImageDataError;
+use crate::locale::LocaleError;
+use crate::metadata::MetadataError;
+use crate::output::OutputError;
+use crate::png::PngError;
+use crate::parity::ParityError;
+use crate::profile::ProfileError;
+use crate::protect::ProtectError;
+use crate::quantize::QuantizeError;
+use crate::registry::RegistryError;
+use crate::rename::RenameError;
+use crate::repair::RepairError;
+use crate::scan::ScanError;
+use crate::seal::SealError;
+use crate::sidecar::SidecarError;
+use crate::signature::SignatureError;
+use crate::stego::StegoError;
+use crate::sync::SyncError;
+use crate::wizard::WizardError;
+use crate::zlib::ZlibError;
+
+/// Any error raised by pngme
+#[derive(Debug)]
+pub enum PngmeError {
+    Io(io::Error),
+    Access(AccessError),
+    Png(PngError),
+    Chunk(ChunkError),
+    ChunkType(ChunkTypeError),
+    ImageData(ImageDataError),
+    Apng(ApngError),
+    Metadata(MetadataError),
+    Signature(SignatureError),
+    Stego(StegoError),
+    Quantize(QuantizeError),
+    Registry(RegistryError),
+    Rename(RenameError),
+    Repair(RepairError),
+    Protect(ProtectError),
+    Sidecar(SidecarError),
+    Sync(SyncError),
+    Scan(ScanError),
+    Output(OutputError),
+    Carrier(CarrierError),
+    Manifest(serde_json::Error),
+    Assertion(AssertionError),
+    Wizard(WizardError),
+    Diagnostics(DiagnosticsError),
+    Compress(CompressError),
+    Delta(DeltaError),
+    Zlib(ZlibError),
+    Profile(ProfileError),
+    Parity(ParityError),
+    Seal(SealError),
+    Archive(ArchiveError),
+    Locale(LocaleError),
+
+    /// Bytes that should be UTF-8 text are not
+    Utf8(Utf8Error),
+
+    /// A fixed size field could not be read from a slice of bytes
+    Slice(TryFromSliceError)
+}
+
+/// Broad category of an error, each with its own process exit code
+///
+/// Exit code 1 is left to invalid command line arguments
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
+    /// Chunk, message, frame or metadata field is missing
+    NotFound,
+
+    /// File is not a PNG at all
+    NotPng,
+
+    /// File is a PNG but some of its chunks are malformed
+    Corrupt,
+
+    /// File could not be read or written
+    Io,
+
+    /// Message signature is missing or doesn't match
+    Verification,
+
+    /// Request can't be carried out with the given arguments
+    InvalidInput,
+
+    /// Data doesn't meet the expectation of an assertion
+    AssertionFailed,
+
+    /// A warning was raised in strict mode
+    Strict
+}
+
+impl ErrorKind {
+    /// Exit code of the process when it fails with an error of this kind
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::NotFound => 2,
+            ErrorKind::NotPng => 3,
+            ErrorKind::Corrupt => 4,
+            ErrorKind::Io => 5,
+            ErrorKind::Verification => 6,
+            ErrorKind::InvalidInput => 7,
+            ErrorKind::AssertionFailed => 8,
+            ErrorKind::Strict => 9
+        }
+    }
+
+    /// Code of errors of this kind, never reused once assigned, ending with the exit code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::NotFound => "PNGME102",
+            ErrorKind::NotPng => "PNGME103",
+            ErrorKind::Corrupt => "PNGME104",
+            ErrorKind::Io => "PNGME105",
+            ErrorKind::Verification => "PNGME106",
+            ErrorKind::InvalidInput => "PNGME107",
+            ErrorKind::AssertionFailed => "PNGME108",
+            ErrorKind::Strict => "PNGME109"
+        }
+    }
+
+    /// Name of the kind in JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::NotPng => "not-png",
+            ErrorKind::Corrupt => "corrupt",
+            ErrorKind::Io => "io",
+            ErrorKind::Verification => "verification",
+            ErrorKind::InvalidInput => "invalid-input",
+            ErrorKind::AssertionFailed => "assertion-failed",
+            ErrorKind::Strict => "strict"
+        }
+    }
+}
+
+impl PngmeError {
+    /// Category of the error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PngmeError::Io(_) | PngmeError::Access(_) => ErrorKind::Io,
+            PngmeError::Png(PngError::TooSmall)
+            | PngmeError::Png(PngError::InvalidSignature)
+            | PngmeError::Repair(RepairError::UnrecoverableSignature) => ErrorKind::NotPng,
+            PngmeError::Png(PngError::ChunkNotFound)
+            | PngmeError::Apng(ApngError::NotAnimated)
+            | PngmeError::Stego(StegoError::NoMessage)
+            | PngmeError::Rename(RenameError::MissingField(_))
+            | PngmeError::Repair(RepairError::MissingHeader(_))
+            | PngmeError::Archive(ArchiveError::MemberNotFound(_, _)) => ErrorKind::NotFound,
+            PngmeError::Chunk(_)
+            | PngmeError::ChunkType(_)
+            | PngmeError::ImageData(_)
+            | PngmeError::Metadata(MetadataError::InvalidText)
+            | PngmeError::Metadata(MetadataError::InvalidTime)
+            | PngmeError::Utf8(_)
+            | PngmeError::Slice(_)
+            | PngmeError::Apng(ApngError::MissingHeader)
+            | PngmeError::Apng(ApngError::InvalidAnimationControl)
+            | PngmeError::Apng(ApngError::InvalidFrameControl)
+            | PngmeError::Apng(ApngError::InvalidFrameData)
+            | PngmeError::Protect(ProtectError::InvalidProtectedChunk)
+            | PngmeError::Repair(RepairError::CorruptImageData(_))
+            | PngmeError::Repair(RepairError::NothingToSalvage)
+            | PngmeError::Repair(RepairError::OversizedImage(_, _))
+            | PngmeError::Sidecar(SidecarError::InvalidGamma)
+            | PngmeError::Sidecar(SidecarError::InvalidPhysicalDimensions)
+            | PngmeError::Compress(CompressError::InvalidEnvelope)
+            | PngmeError::Compress(CompressError::UnknownAlgorithm(_))
+            | PngmeError::Compress(CompressError::UnknownDictionary(_))
+            | PngmeError::Compress(CompressError::TooLarge)
+            | PngmeError::Delta(DeltaError::InvalidDelta)
+            | PngmeError::Delta(DeltaError::BaseMismatch)
+            | PngmeError::Delta(DeltaError::MissingBase)
+            | PngmeError::Zlib(ZlibError::CorruptStreams(_))
+            | PngmeError::Parity(_)
+            | PngmeError::Seal(SealError::InvalidSeal)
+            | PngmeError::Archive(ArchiveError::InvalidArchive(_, _)) => ErrorKind::Corrupt,
+            PngmeError::Assertion(AssertionError::NotEqual { .. })
+            | PngmeError::Assertion(AssertionError::NoMatch(_)) => ErrorKind::AssertionFailed,
+            PngmeError::Signature(SignatureError::MissingSignature)
+            | PngmeError::Signature(SignatureError::VerificationFailed)
+            | PngmeError::Scan(ScanError::Rejected(_, _))
+            | PngmeError::Protect(ProtectError::DecryptionFailed) => ErrorKind::Verification,
+            PngmeError::Diagnostics(DiagnosticsError::Strict(_, _)) => ErrorKind::Strict,
+            PngmeError::Png(_)
+            | PngmeError::Apng(_)
+            | PngmeError::Signature(_)
+            | PngmeError::Stego(_)
+            | PngmeError::Quantize(_)
+            | PngmeError::Registry(_)
+            | PngmeError::Rename(_)
+            | PngmeError::Metadata(_)
+            | PngmeError::Repair(_)
+            | PngmeError::Protect(_)
+            | PngmeError::Sidecar(_)
+            | PngmeError::Sync(_)
+            | PngmeError::Scan(_)
+            | PngmeError::Output(_)
+            | PngmeError::Carrier(_)
+            | PngmeError::Manifest(_)
+            | PngmeError::Assertion(_)
+            | PngmeError::Wizard(_)
+            | PngmeError::Diagnostics(_)
+            | PngmeError::Compress(_)
+            | PngmeError::Delta(_)
+            | PngmeError::Profile(_)
+            | PngmeError::Seal(_)
+            | PngmeError::Archive(_)
+            | PngmeError::Locale(_) => ErrorKind::InvalidInput
+        }
+    }
+
+    /// Exit code of the process when it fails with this error
+    pub fn exit_code(&self) -> i32 {
+        self.kind().exit_code()
+    }
+
+    /// Code of the error, which is that of the rule for a warning raised in strict mode and that of its kind otherwise
+    pub fn code(&self) -> &'static str {
+        match self {
+            PngmeError::Diagnostics(DiagnosticsError::Strict(rule, _)) => rule.code(),
+            error => error.kind().code()
+        }
+    }
+
+    fn inner(&self) -> &(dyn error::Error + 'static) {
+        match self {
+            PngmeError::Io(error) => error,
+            PngmeError::Access(error) => error,
+            PngmeError::Png(error) => error,
+            PngmeError::Chunk(error) => error,
+            PngmeError::ChunkType(error) => error,
+            PngmeError::ImageData(error) => error,
+            PngmeError::Apng(error) => error,
+            PngmeError::Metadata(error) => error,
+            PngmeError::Signature(error) => error,
+            PngmeError::Stego(error) => error,
+            PngmeError::Quantize(error) => error,
+            PngmeError::Registry(error) => error,
+            PngmeError::Rename(error) => error,
+            PngmeError::Repair(error) => error,
+            PngmeError::Protect(error) => error,
+            PngmeError::Sidecar(error) => error,
+            PngmeError::Sync(error) => error,
+            PngmeError::Scan(error) => error,
+            PngmeError::Output(error) => error,
+            PngmeError::Carrier(error) => error,
+            PngmeError::Manifest(error) => error,
+            PngmeError::Assertion(error) => error,
+            PngmeError::Wizard(error) => error,
+            PngmeError::Diagnostics(error) => error,
+            PngmeError::Compress(error) => error,
+            PngmeError::Delta(error) => error,
+            PngmeError::Zlib(error) => error,
+            PngmeError::Profile(error) => error,
+            PngmeError::Parity(error) => error,
+            PngmeError::Seal(error) => error,
+            PngmeError::Archive(error) => error,
+            PngmeError::Locale(error) => error,
+            PngmeError::Utf8(error) => error,
+            PngmeError::Slice(error) => error
+        }
+    }
+}
+
+impl error::Error for PngmeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(self.inner())
+    }
+}
+
+impl Display for PngmeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.inner())
+    }
+}
+
+macro_rules! impl_from {
+    ($($error:ty => $variant:ident),*) => {
+        $(
+            impl From<$error> for PngmeError {
+                fn from(error: $error) -> Self {
+                    PngmeError::$variant(error)
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(
+    io::Error => Io,
+    AccessError => Access,
+    PngError => Png,
+    ChunkError => Chunk,
+    ChunkTypeError => ChunkType,
+    ImageDataError => ImageData,
+    ApngError => Apng,
+    MetadataError => Metadata,
+    SignatureError => Signature,
+    StegoError => Stego,
+    QuantizeError => Quantize,
+    RegistryError => Registry,
+    RenameError => Rename,
+    RepairError => Repair,
+    ProtectError => Protect,
+    SidecarError => Sidecar,
+    SyncError => Sync,
+    ScanError => Scan,
+    OutputError => Output,
+    CarrierError => Carrier,
+    serde_json::Error => Manifest,
+    AssertionError => Assertion,
+    WizardError => Wizard,
+    DiagnosticsError => Diagnostics,
+    CompressError => Compress,
+    DeltaError => Delta,
+    ZlibError => Zlib,
+    ProfileError => Profile,
+    ParityError => Parity,
+    SealError => Seal,
+    ArchiveError => Archive,
+    LocaleError => Locale,
+    Utf8Error => Utf8,
+    TryFromSliceError => Slice
+);
+
+impl From<FromUtf8Error> for PngmeError {
+    fn from(error: FromUtf8Error) -> Self {
+        PngmeError::Utf8(error.utf8_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_error_kinds() {
+        assert_eq!(PngmeError::from(PngError::ChunkNotFound).kind(), ErrorKind::NotFound);
+        assert_eq!(PngmeError::from(PngError::InvalidSignature).kind(), ErrorKind::NotPng);
+        assert_eq!(PngmeError::from(ChunkError::InvalidCrc(1, 2)).kind(), ErrorKind::Corrupt);
+        assert_eq!(PngmeError::from(SignatureError::VerificationFailed).kind(), ErrorKind::Verification);
+        assert_eq!(PngmeError::from(SignatureError::InvalidKey).kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            PngmeError::from(io::Error::new(io::ErrorKind::NotFound, "missing")).kind(),
+            ErrorKind::Io
+        );
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let kinds = [
+            ErrorKind::NotFound,
+            ErrorKind::NotPng,
+            ErrorKind::Corrupt,
+            ErrorKind::Io,
+            ErrorKind::Verification,
+            ErrorKind::InvalidInput,
+            ErrorKind::AssertionFailed,
+            ErrorKind::Strict
+        ];
+
+        for (index, kind) in kinds.iter().enumerate() {
+            assert_ne!(kind.exit_code(), 0);
+            assert!(kinds[index + 1..].iter().all(|other| other.exit_code() != kind.exit_code()));
+            assert!(kinds[index + 1..].iter().all(|other| other.code() != kind.code()));
+            assert!(Rule::ALL.iter().all(|rule| rule.code() != kind.code()));
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(PngmeError::from(PngError::ChunkNotFound).code(), "PNGME102");
+        assert_eq!(PngmeError::from(ChunkError::InvalidCrc(1, 2)).code(), "PNGME104");
+
+        // Warnings raised in strict mode keep the code of their rule
+        let strict = PngmeError::from(DiagnosticsError::Strict(Rule::SkippedFile, String::from("skipped")));
+        assert_eq!(strict.code(), "PNGME004");
+        assert_eq!(strict.kind(), ErrorKind::Strict);
+    }
+
+    #[test]
+    fn test_error_source_and_display() {
+        let error = Png::try_from(&[0u8; 4][..]).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::NotPng);
+        assert_eq!(error.to_string(), PngError::TooSmall.to_string());
+        assert!(error::Error::source(&error).is_some());
+    }
+}
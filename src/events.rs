@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::png::Png;
+use crate::print::{self, ChunkFilter};
+use crate::{Error, Result};
+
+/// Version of the event stream, raised whenever an event or one of its fields is renamed or removed
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Destination of the event stream: stdout, stderr or a file path such as `/dev/fd/3`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EventTarget {
+    Stdout,
+    Stderr,
+    File(PathBuf)
+}
+
+impl FromStr for EventTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "stdout" => EventTarget::Stdout,
+            "stderr" => EventTarget::Stderr,
+            path => EventTarget::File(PathBuf::from(path))
+        })
+    }
+}
+
+/// Progress event written as a line of JSON, for programs wrapping the command line
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// The subcommand is about to run
+    Started { command: String },
+
+    /// A chunk was read from a PNG file
+    ChunkParsed {
+        file: PathBuf,
+        index: usize,
+        offset: usize,
+        chunk_type: String,
+        length: u32
+    },
+
+    /// Something went wrong without stopping the subcommand
+    Warning { code: String, rule: String, message: String },
+
+    /// The subcommand is done, with the error that stopped it and its code if any
+    Finished {
+        success: bool,
+        exit_code: i32,
+        code: Option<String>,
+        error: Option<String>
+    }
+}
+
+impl Event {
+    /// Event telling how the subcommand ended
+    pub fn finished(result: &Result<()>) -> Self {
+        match result {
+            Ok(()) => Event::Finished { success: true, exit_code: 0, code: None, error: None },
+            Err(error) => Event::Finished {
+                success: false,
+                exit_code: error.exit_code(),
+                code: Some(error.code().to_string()),
+                error: Some(error.to_string())
+            }
+        }
+    }
+}
+
+/// Event stream, written to the target given to `--events` or nowhere
+#[derive(Default)]
+pub struct Events {
+    sink: Option<Mutex<Box<dyn Write + Send>>>
+}
+
+impl Events {
+    /// Stream writing events to the given target
+    pub fn new(target: &EventTarget) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = match target {
+            EventTarget::Stdout => Box::new(io::stdout()),
+            EventTarget::Stderr => Box::new(io::stderr()),
+            EventTarget::File(path) => Box::new(File::create(path)?)
+        };
+
+        Ok(Events { sink: Some(Mutex::new(writer)) })
+    }
+
+    /// Write an event as a line of JSON, if an event stream was requested
+    ///
+    /// Events are best effort, so failing to write one never fails the subcommand
+    pub fn emit(&self, event: &Event) {
+        let sink = match &self.sink {
+            Some(sink) => sink,
+            None => return
+        };
+
+        if let (Ok(mut writer), Ok(line)) = (sink.lock(), serde_json::to_string(event)) {
+            let _ = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+        }
+    }
+
+    /// Write an event for every chunk of a PNG file
+    pub fn emit_chunks(&self, file: &Path, png: &Png) {
+        if self.sink.is_none() {
+            return;
+        }
+
+        for entry in print::entries(png, &ChunkFilter::default()) {
+            self.emit(&Event::ChunkParsed {
+                file: file.to_path_buf(),
+                index: entry.index,
+                offset: entry.offset,
+                chunk_type: entry.chunk.chunk_type().to_string(),
+                length: entry.chunk.length()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::PngError;
+
+    #[test]
+    fn test_event_target_from_str() {
+        assert_eq!(EventTarget::from_str("stdout").unwrap(), EventTarget::Stdout);
+        assert_eq!(EventTarget::from_str("stderr").unwrap(), EventTarget::Stderr);
+        assert_eq!(
+            EventTarget::from_str("/dev/fd/3").unwrap(),
+            EventTarget::File(PathBuf::from("/dev/fd/3"))
+        );
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::ChunkParsed {
+            file: PathBuf::from("dice.png"),
+            index: 0,
+            offset: 8,
+            chunk_type: String::from("IHDR"),
+            length: 13
+        };
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"chunk-parsed","file":"dice.png","index":0,"offset":8,"chunk_type":"IHDR","length":13}"#
+        );
+
+        let event = Event::Started { command: String::from("print") };
+        assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"event":"started","command":"print"}"#);
+
+        let event = Event::Warning {
+            code: String::from("PNGME004"),
+            rule: String::from("skipped-file"),
+            message: String::from("Skipped ./text.png: Invalid PNG signature")
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"warning","code":"PNGME004","rule":"skipped-file","message":"Skipped ./text.png: Invalid PNG signature"}"#
+        );
+    }
+
+    #[test]
+    fn test_finished_event() {
+        assert_eq!(
+            Event::finished(&Ok(())),
+            Event::Finished { success: true, exit_code: 0, code: None, error: None }
+        );
+
+        assert_eq!(
+            Event::finished(&Err(PngError::ChunkNotFound.into())),
+            Event::Finished {
+                success: false,
+                exit_code: 2,
+                code: Some(String::from("PNGME102")),
+                error: Some(String::from("Chunk not found"))
+            }
+        );
+    }
+}
@@ -0,0 +1,354 @@
+#[cfg(test)]
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::sync::{Mutex, MutexGuard};
+
+use crate::access::{self, AccessError};
+use crate::Result;
+
+/// Storage the commands read and write files through
+///
+/// Commands never touch the filesystem directly, so they can run against files kept in memory in tests, or against
+/// whatever storage an embedder supplies
+pub trait FileProvider {
+    /// Whole contents of a file
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// First bytes of a file, fewer when the file is shorter
+    fn read_prefix(&self, path: &Path, length: usize) -> Result<Vec<u8>> {
+        let mut bytes = self.read(path)?;
+        bytes.truncate(length);
+        Ok(bytes)
+    }
+
+    /// Create or overwrite a file
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()>;
+
+    /// Move a file, overwriting the destination if there is one
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Whether there is a file or directory at the path
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Files directly inside a directory along with those skipped, in no particular order
+    ///
+    /// Subdirectories are left out. Special files such as fifos are always skipped, since reading one may block forever
+    fn list(&self, directory: &Path, options: &ListOptions) -> Result<Vec<Listing>>;
+
+    /// Create a directory along with its missing parents
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Make sure a file can be overwritten, before any work is done on it
+    fn check_writable(&self, path: &Path) -> Result<()>;
+
+    /// Whether a file can be overwritten
+    fn is_writable(&self, path: &Path) -> bool {
+        self.check_writable(path).is_ok()
+    }
+}
+
+/// Which entries of a directory are listed as files
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ListOptions {
+    /// List the files symbolic links point to rather than skipping the links
+    pub follow_symlinks: bool,
+
+    /// Skip files on another filesystem than the directory
+    pub one_file_system: bool
+}
+
+/// Entry of a directory
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Listing {
+    File(PathBuf),
+    Skipped(PathBuf, Skip)
+}
+
+/// Why an entry of a directory isn't listed as a file
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Skip {
+    /// Symbolic link, while links aren't followed
+    Symlink,
+
+    /// Fifo, socket or device
+    Special,
+
+    /// File on another filesystem than the directory
+    OtherFilesystem
+}
+
+/// Files on the real filesystem
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskFiles;
+
+impl FileProvider for DiskFiles {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        Ok(fs::read(path)?)
+    }
+
+    fn read_prefix(&self, path: &Path, length: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(length);
+        File::open(path)?.take(length as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        Ok(fs::write(path, bytes)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        Ok(fs::rename(from, to)?)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn list(&self, directory: &Path, options: &ListOptions) -> Result<Vec<Listing>> {
+        let directory_device = device(&fs::metadata(directory)?);
+        let mut listings = Vec::new();
+
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            // Metadata of the entry itself, or of the file a followed link points to
+            let metadata = match (entry.file_type()?.is_symlink(), options.follow_symlinks) {
+                (true, false) => {
+                    listings.push(Listing::Skipped(path, Skip::Symlink));
+                    continue;
+                },
+                (true, true) => match fs::metadata(&path) {
+                    Ok(metadata) => metadata,
+                    // Broken links are left to fail when they are read
+                    Err(_) => {
+                        listings.push(Listing::File(path));
+                        continue;
+                    }
+                },
+                (false, _) => entry.metadata()?
+            };
+
+            let listing = if metadata.is_dir() {
+                continue;
+            } else if !metadata.is_file() {
+                Listing::Skipped(path, Skip::Special)
+            } else if options.one_file_system && device(&metadata) != directory_device {
+                Listing::Skipped(path, Skip::OtherFilesystem)
+            } else {
+                Listing::File(path)
+            };
+
+            listings.push(listing);
+        }
+
+        Ok(listings)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        Ok(fs::create_dir_all(path)?)
+    }
+
+    fn check_writable(&self, path: &Path) -> Result<()> {
+        access::check_writable(path)
+    }
+}
+
+/// Files kept in memory, for tests
+///
+/// Directories exist once created or once a file is written in them
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryFiles {
+    state: Mutex<MemoryState>
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    directories: BTreeSet<PathBuf>,
+    read_only: BTreeSet<PathBuf>
+}
+
+#[cfg(test)]
+impl MemoryFiles {
+    pub fn new() -> Self {
+        MemoryFiles::default()
+    }
+
+    /// Add a file, builder style
+    pub fn with_file(self, path: impl Into<PathBuf>, bytes: impl Into<Vec<u8>>) -> Self {
+        let path = path.into();
+        self.state().add_file(&path, bytes.into());
+        self
+    }
+
+    /// Refuse to overwrite a file, as if it were write-protected
+    pub fn set_read_only(&self, path: &Path) {
+        self.state().read_only.insert(path.to_path_buf());
+    }
+
+    /// Contents of a file, `None` when there is none at the path
+    pub fn get(&self, path: &Path) -> Option<Vec<u8>> {
+        self.state().files.get(path).cloned()
+    }
+
+    /// Paths of every file, sorted
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.state().files.keys().cloned().collect()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        // A panic while holding the lock can't leave the maps half updated
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+impl MemoryState {
+    fn add_file(&mut self, path: &Path, bytes: Vec<u8>) {
+        if let Some(parent) = path.parent() {
+            self.add_directory(parent);
+        }
+
+        self.files.insert(path.to_path_buf(), bytes);
+    }
+
+    fn add_directory(&mut self, path: &Path) {
+        for ancestor in path.ancestors() {
+            self.directories.insert(ancestor.to_path_buf());
+        }
+    }
+}
+
+#[cfg(test)]
+impl FileProvider for MemoryFiles {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.get(path).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound).into())
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        let mut state = self.state();
+
+        if state.read_only.contains(path) {
+            return Err(AccessError::ReadOnly(path.to_path_buf()).into());
+        }
+
+        state.add_file(path, bytes.to_vec());
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut state = self.state();
+        let bytes = state.files.remove(from).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+
+        if state.read_only.remove(from) {
+            state.read_only.insert(to.to_path_buf());
+        }
+
+        state.add_file(to, bytes);
+
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let state = self.state();
+        state.files.contains_key(path) || state.directories.contains(path)
+    }
+
+    fn list(&self, directory: &Path, _: &ListOptions) -> Result<Vec<Listing>> {
+        let state = self.state();
+
+        if !state.directories.contains(directory) {
+            return Err(io::Error::from(io::ErrorKind::NotFound).into());
+        }
+
+        Ok(state
+            .files
+            .keys()
+            .filter(|path| path.parent() == Some(directory))
+            .map(|path| Listing::File(path.clone()))
+            .collect())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.state().add_directory(path);
+        Ok(())
+    }
+
+    fn check_writable(&self, path: &Path) -> Result<()> {
+        let state = self.state();
+
+        if !state.files.contains_key(path) {
+            return Err(io::Error::from(io::ErrorKind::NotFound).into());
+        }
+
+        match state.read_only.contains(path) {
+            true => Err(AccessError::ReadOnly(path.to_path_buf()).into()),
+            false => Ok(())
+        }
+    }
+}
+
+/// Filesystem a file is on, unknown outside of Unix so every file counts as being on the same one
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_not_found(result: Result<impl std::fmt::Debug>) -> bool {
+        matches!(result, Err(crate::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound)
+    }
+
+    #[test]
+    fn test_memory_files() {
+        let files = MemoryFiles::new().with_file("images/dice.png", b"dice".to_vec());
+        let dice = Path::new("images/dice.png");
+
+        assert_eq!(files.read(dice).unwrap(), b"dice");
+        assert!(files.exists(Path::new("images")));
+        assert_eq!(
+            files.list(Path::new("images"), &ListOptions::default()).unwrap(),
+            vec![Listing::File(dice.to_path_buf())]
+        );
+
+        files.rename(dice, Path::new("images/renamed.png")).unwrap();
+        assert!(!files.exists(dice));
+        assert!(is_not_found(files.read(dice)));
+        assert_eq!(files.paths(), vec![PathBuf::from("images/renamed.png")]);
+
+        assert!(is_not_found(files.list(Path::new("frames"), &ListOptions::default())));
+        files.create_dir_all(Path::new("frames/apng")).unwrap();
+        assert!(files.list(Path::new("frames"), &ListOptions::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_files_read_only() {
+        let files = MemoryFiles::new().with_file("dice.png", b"dice".to_vec());
+        let dice = Path::new("dice.png");
+
+        assert!(files.is_writable(dice));
+        files.set_read_only(dice);
+
+        assert!(!files.is_writable(dice));
+        assert!(files.write(dice, b"changed").is_err());
+        assert_eq!(files.read(dice).unwrap(), b"dice");
+        assert!(!files.is_writable(Path::new("missing.png")));
+    }
+}
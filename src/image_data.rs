@@ -0,0 +1,747 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::convert::{TryFrom, TryInto};
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Maximum data length of the IDAT chunks written when replacing image data
+pub const IDAT_CHUNK_SIZE: usize = 8192;
+
+/// Longest decompressed image data, the same limit as any other compressed stream so a small file can't take
+/// gigabytes of memory
+pub const MAX_DATA_LENGTH: usize = Chunk::MAX_LENGTH as usize;
+
+/// Starting column, starting row, column step and row step of every Adam7 pass
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2)
+];
+
+/// Image header (IHDR) chunk data
+///
+/// See [PNG Chunks - IHDR](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR) for details
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8
+}
+
+impl ImageHeader {
+    /// Length of the IHDR chunk's data
+    pub const LENGTH: usize = 13;
+
+    /// Read the image header from the PNG's IHDR chunk
+    pub fn from_png(png: &Png) -> Result<Self> {
+        let ihdr = png
+            .chunk_by_type("IHDR")
+            .ok_or(ImageDataError::MissingHeader)?;
+
+        ImageHeader::try_from(ihdr.data())
+    }
+
+    /// Number of samples in each pixel
+    pub fn channels(&self) -> u8 {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1
+        }
+    }
+
+    /// Number of bits in each pixel
+    pub fn bits_per_pixel(&self) -> usize {
+        self.channels() as usize * self.bit_depth as usize
+    }
+
+    /// Distance in bytes to the corresponding byte of the previous pixel, as used by filters
+    pub fn filter_distance(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8).max(1)
+    }
+
+    /// Number of bytes in a scanline of the given width, without its filter type byte
+    pub fn row_bytes(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Width and height of every non empty reduced image in the image data
+    pub fn passes(&self) -> Vec<(u32, u32)> {
+        self.pass_layout()
+            .iter()
+            .map(|pass| (pass.width, pass.height))
+            .collect()
+    }
+
+    /// Position in the full image of every non empty reduced image in the image data
+    pub fn pass_layout(&self) -> Vec<Pass> {
+        if self.interlace_method == 0 {
+            return vec![Pass {
+                x: 0,
+                y: 0,
+                x_step: 1,
+                y_step: 1,
+                width: self.width,
+                height: self.height
+            }];
+        }
+
+        ADAM7_PASSES
+            .iter()
+            .map(|(x, y, x_step, y_step)| Pass {
+                x: *x,
+                y: *y,
+                x_step: *x_step,
+                y_step: *y_step,
+                width: (self.width + x_step - 1).saturating_sub(*x) / x_step,
+                height: (self.height + y_step - 1).saturating_sub(*y) / y_step
+            })
+            .filter(|pass| pass.width > 0 && pass.height > 0)
+            .collect()
+    }
+
+    /// Number of bytes of decompressed image data, filter type bytes included
+    ///
+    /// The dimensions come from the file, so images whose data can't be held in memory are refused
+    pub fn data_length(&self) -> Result<usize> {
+        self.pass_layout()
+            .iter()
+            .try_fold(0usize, |length, pass| {
+                (pass.width as usize)
+                    .checked_mul(self.bits_per_pixel())
+                    .map(|bits| bits.div_ceil(8) + 1)
+                    .and_then(|row| row.checked_mul(pass.height as usize))
+                    .and_then(|rows| rows.checked_add(length))
+            })
+            .filter(|length| *length <= MAX_DATA_LENGTH)
+            .ok_or_else(|| ImageDataError::TooLarge.into())
+    }
+
+    /// Raw value of the sample at the given index of an unfiltered scanline
+    pub fn sample(&self, row: &[u8], index: usize) -> u16 {
+        match self.bit_depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            depth => {
+                let bit = index * depth as usize;
+                let shift = 8 - depth as usize - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+            }
+        }
+    }
+
+    /// Scale a raw sample value to 8 bits
+    pub fn sample_to_u8(&self, value: u16) -> u8 {
+        match self.bit_depth {
+            16 => (value >> 8) as u8,
+            8 => value as u8,
+            depth => (value as u32 * 255 / ((1 << depth) - 1)) as u8
+        }
+    }
+
+    /// Image header as chunk data bytes
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(
+                [
+                    self.bit_depth,
+                    self.color_type,
+                    self.compression_method,
+                    self.filter_method,
+                    self.interlace_method
+                ]
+                .iter()
+            )
+            .copied()
+            .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for ImageHeader {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != ImageHeader::LENGTH {
+            return Err(ImageDataError::InvalidHeader.into());
+        }
+
+        let header = ImageHeader {
+            width: u32::from_be_bytes(bytes[0..4].try_into()?),
+            height: u32::from_be_bytes(bytes[4..8].try_into()?),
+            bit_depth: bytes[8],
+            color_type: bytes[9],
+            compression_method: bytes[10],
+            filter_method: bytes[11],
+            interlace_method: bytes[12]
+        };
+
+        // Only some bit depths are allowed for each color type
+        let valid_bit_depth = match header.color_type {
+            0 => [1, 2, 4, 8, 16].contains(&header.bit_depth),
+            3 => [1, 2, 4, 8].contains(&header.bit_depth),
+            2 | 4 | 6 => [8, 16].contains(&header.bit_depth),
+            _ => false
+        };
+
+        if !valid_bit_depth
+            || header.width == 0
+            || header.height == 0
+            || header.width > i32::MAX as u32
+            || header.height > i32::MAX as u32
+            || header.compression_method != 0
+            || header.filter_method != 0
+            || header.interlace_method > 1
+        {
+            return Err(ImageDataError::InvalidHeader.into());
+        }
+
+        Ok(header)
+    }
+}
+
+/// A reduced image of an interlaced image, or the whole image when it isn't interlaced
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Pass {
+    pub x: u32,
+    pub y: u32,
+    pub x_step: u32,
+    pub y_step: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+/// A single unfiltered scanline and the filter type it was stored with
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Scanline {
+    pub filter: u8,
+    pub data: Vec<u8>
+}
+
+/// Decompress the concatenated data of every IDAT chunk
+pub fn inflate(png: &Png) -> Result<Vec<u8>> {
+    let compressed: Vec<u8> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        .flat_map(|chunk| chunk.data().iter().copied())
+        .collect();
+
+    let mut data = Vec::new();
+    ZlibDecoder::new(&compressed[..]).take(MAX_DATA_LENGTH as u64 + 1).read_to_end(&mut data)?;
+
+    if data.len() > MAX_DATA_LENGTH {
+        return Err(ImageDataError::TooLarge.into());
+    }
+
+    Ok(data)
+}
+
+/// Compress image data into a zlib stream
+pub fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Undo the filter of every scanline in decompressed image data
+pub fn unfilter(header: &ImageHeader, data: &[u8]) -> Result<Vec<Scanline>> {
+    // Nothing is allocated for scanlines the data doesn't have
+    if data.len() < header.data_length()? {
+        return Err(ImageDataError::TruncatedData.into());
+    }
+
+    let distance = header.filter_distance();
+    let mut scanlines = Vec::new();
+    let mut offset = 0;
+
+    for (width, height) in header.passes() {
+        let row_bytes = header.row_bytes(width);
+
+        // The first scanline of every pass is filtered against a row of zeros
+        let mut previous = vec![0; row_bytes];
+
+        for _ in 0..height {
+            let end = offset + 1 + row_bytes;
+
+            if end > data.len() {
+                return Err(ImageDataError::TruncatedData.into());
+            }
+
+            let filter = data[offset];
+            let mut row = data[offset + 1..end].to_vec();
+            unfilter_row(filter, distance, &previous, &mut row)?;
+
+            previous = row.clone();
+            scanlines.push(Scanline { filter, data: row });
+            offset = end;
+        }
+    }
+
+    Ok(scanlines)
+}
+
+/// Filter every scanline with its own filter type into image data ready to be compressed
+pub fn filter(header: &ImageHeader, scanlines: &[Scanline]) -> Result<Vec<u8>> {
+    let distance = header.filter_distance();
+    let mut scanlines = scanlines.iter();
+    let mut data = Vec::new();
+
+    for (width, height) in header.passes() {
+        let mut previous = vec![0; header.row_bytes(width)];
+
+        for _ in 0..height {
+            let scanline = scanlines.next().ok_or(ImageDataError::TruncatedData)?;
+
+            data.push(scanline.filter);
+            data.extend(filter_row(scanline.filter, distance, &previous, &scanline.data)?);
+
+            previous = scanline.data.clone();
+        }
+    }
+
+    Ok(data)
+}
+
+/// Every pixel of the image as 8 bit RGBA, in raster order
+pub fn rgba_pixels(png: &Png) -> Result<Vec<[u8; 4]>> {
+    let header = ImageHeader::from_png(png)?;
+    let scanlines = unfilter(&header, &inflate(png)?)?;
+
+    let palette = png.chunk_by_type("PLTE").map(|chunk| chunk.data()).unwrap_or(&[]);
+    let transparency = png.chunk_by_type("tRNS").map(|chunk| chunk.data()).unwrap_or(&[]);
+
+    if header.color_type == 3 && palette.is_empty() {
+        return Err(ImageDataError::MissingPalette.into());
+    }
+
+    let channels = header.channels() as usize;
+    let count = (header.width as usize).checked_mul(header.height as usize).ok_or(ImageDataError::TooLarge)?;
+    let mut pixels = vec![[0; 4]; count];
+    let mut scanlines = scanlines.iter();
+
+    for pass in header.pass_layout() {
+        for row in 0..pass.height {
+            let scanline = scanlines.next().ok_or(ImageDataError::TruncatedData)?;
+            let y = (pass.y + row * pass.y_step) as usize;
+
+            for column in 0..pass.width as usize {
+                let x = pass.x as usize + column * pass.x_step as usize;
+                let samples: Vec<u16> = (0..channels)
+                    .map(|channel| header.sample(&scanline.data, column * channels + channel))
+                    .collect();
+
+                pixels[y * header.width as usize + x] =
+                    rgba_pixel(&header, &samples, palette, transparency)?;
+            }
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Copy of the PNG whose IDAT chunks hold the given uncompressed image data
+pub fn replace_image_data(png: &Png, data: &[u8]) -> Result<Png> {
+    let compressed = deflate(data)?;
+
+    let mut image_data = Vec::new();
+    for part in compressed.chunks(IDAT_CHUNK_SIZE) {
+        image_data.push(Chunk::new(ChunkType::from_str("IDAT")?, part.to_vec()));
+    }
+
+    // New IDAT chunks take the place of the first original IDAT chunk
+    let mut chunks = Vec::new();
+    let mut image_data = Some(image_data);
+
+    for chunk in png.chunks() {
+        if chunk.chunk_type().to_string() != "IDAT" {
+            chunks.push(chunk.clone());
+        } else if let Some(image_data) = image_data.take() {
+            chunks.extend(image_data);
+        }
+    }
+
+    if image_data.is_some() {
+        return Err(ImageDataError::MissingImageData.into());
+    }
+
+    Ok(Png::from_chunks(chunks))
+}
+
+fn rgba_pixel(header: &ImageHeader, samples: &[u16], palette: &[u8], transparency: &[u8]) -> Result<[u8; 4]> {
+    // tRNS holds a single transparent color for grayscale and truecolor images
+    let transparent_color: Vec<u16> = transparency
+        .chunks_exact(2)
+        .map(|value| u16::from_be_bytes([value[0], value[1]]))
+        .collect();
+    let alpha = if transparent_color == samples { 0 } else { 255 };
+
+    let pixel = match header.color_type {
+        0 => {
+            let gray = header.sample_to_u8(samples[0]);
+            [gray, gray, gray, alpha]
+        },
+        2 => [
+            header.sample_to_u8(samples[0]),
+            header.sample_to_u8(samples[1]),
+            header.sample_to_u8(samples[2]),
+            alpha
+        ],
+        3 => {
+            let index = samples[0] as usize;
+            let color = palette
+                .get(index * 3..index * 3 + 3)
+                .ok_or(ImageDataError::InvalidPaletteIndex(index))?;
+            [color[0], color[1], color[2], *transparency.get(index).unwrap_or(&255)]
+        },
+        4 => {
+            let gray = header.sample_to_u8(samples[0]);
+            [gray, gray, gray, header.sample_to_u8(samples[1])]
+        },
+        _ => [
+            header.sample_to_u8(samples[0]),
+            header.sample_to_u8(samples[1]),
+            header.sample_to_u8(samples[2]),
+            header.sample_to_u8(samples[3])
+        ]
+    };
+
+    Ok(pixel)
+}
+
+fn unfilter_row(filter: u8, distance: usize, previous: &[u8], row: &mut [u8]) -> Result<()> {
+    for i in 0..row.len() {
+        let left = if i >= distance { row[i - distance] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= distance { previous[i - distance] } else { 0 };
+
+        row[i] = row[i].wrapping_add(predictor(filter, left, up, up_left)?);
+    }
+
+    Ok(())
+}
+
+fn filter_row(filter: u8, distance: usize, previous: &[u8], row: &[u8]) -> Result<Vec<u8>> {
+    (0..row.len())
+        .map(|i| {
+            let left = if i >= distance { row[i - distance] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= distance { previous[i - distance] } else { 0 };
+
+            Ok(row[i].wrapping_sub(predictor(filter, left, up, up_left)?))
+        })
+        .collect()
+}
+
+/// Value predicted by a filter type from the neighbouring bytes
+fn predictor(filter: u8, left: u8, up: u8, up_left: u8) -> Result<u8> {
+    match filter {
+        0 => Ok(0),
+        1 => Ok(left),
+        2 => Ok(up),
+        3 => Ok(((left as u16 + up as u16) / 2) as u8),
+        4 => Ok(paeth(left, up, up_left)),
+        _ => Err(ImageDataError::InvalidFilter(filter).into())
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Image data errors
+#[derive(Debug)]
+pub enum ImageDataError {
+    /// The PNG has no IHDR chunk
+    MissingHeader,
+
+    /// IHDR chunk data is malformed or uses unknown methods
+    InvalidHeader,
+
+    /// The PNG has no IDAT chunk
+    MissingImageData,
+
+    /// Decompressed image data is shorter than the header requires
+    TruncatedData,
+
+    /// The image data, as decompressed or as the header requires it, is longer than `MAX_DATA_LENGTH`
+    TooLarge,
+
+    /// A scanline uses an unknown filter type
+    InvalidFilter(u8),
+
+    /// The palette based image has no PLTE chunk
+    MissingPalette,
+
+    /// A pixel refers to a color outside of the palette
+    InvalidPaletteIndex(usize)
+}
+
+impl error::Error for ImageDataError {}
+
+impl Display for ImageDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageDataError::MissingHeader => write!(f, "Missing IHDR chunk"),
+            ImageDataError::InvalidHeader => write!(f, "Invalid IHDR chunk"),
+            ImageDataError::MissingImageData => write!(f, "Missing IDAT chunk"),
+            ImageDataError::TruncatedData => write!(f, "Image data is truncated"),
+            ImageDataError::TooLarge => write!(f, "Image data is bigger than {} bytes", MAX_DATA_LENGTH),
+            ImageDataError::InvalidFilter(filter) => write!(f, "Invalid filter type {}", filter),
+            ImageDataError::MissingPalette => write!(f, "Missing PLTE chunk"),
+            ImageDataError::InvalidPaletteIndex(index) => {
+                write!(f, "Palette index {} is out of bounds", index)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Builds a PNG whose scanlines cycle through every filter type
+    pub fn testing_png(width: u32, height: u32, color_type: u8, bit_depth: u8, interlace_method: u8) -> Png {
+        let header = ImageHeader {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method
+        };
+
+        let scanlines: Vec<Scanline> = header
+            .passes()
+            .iter()
+            .flat_map(|(width, height)| (0..*height).map(move |_| header.row_bytes(*width)))
+            .enumerate()
+            .map(|(index, row_bytes)| Scanline {
+                filter: (index % 5) as u8,
+                data: (0..row_bytes).map(|i| (i * 7 + index * 13) as u8).collect()
+            })
+            .collect();
+
+        let data = deflate(&filter(&header, &scanlines).unwrap()).unwrap();
+
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), header.as_bytes()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), data),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())
+        ])
+    }
+
+    #[test]
+    fn test_data_length() {
+        for (color_type, bit_depth, interlace_method) in [(2, 8, 0), (0, 1, 0), (6, 16, 1), (3, 4, 1)] {
+            let png = testing_png(10, 7, color_type, bit_depth, interlace_method);
+            let header = ImageHeader::from_png(&png).unwrap();
+
+            assert_eq!(header.data_length().unwrap(), inflate(&png).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn test_image_header() {
+        let png = testing_png(10, 4, 6, 8, 0);
+        let header = ImageHeader::from_png(&png).unwrap();
+
+        assert_eq!(header.width, 10);
+        assert_eq!(header.channels(), 4);
+        assert_eq!(header.filter_distance(), 4);
+        assert_eq!(header.row_bytes(10), 40);
+    }
+
+    #[test]
+    fn test_invalid_image_header() {
+        let mut bytes = ImageHeader::from_png(&testing_png(10, 4, 2, 8, 0)).unwrap().as_bytes();
+        bytes[8] = 4;
+        assert!(ImageHeader::try_from(&bytes[..]).is_err());
+        assert!(ImageHeader::try_from(&bytes[..12]).is_err());
+    }
+
+    #[test]
+    fn test_packed_row_bytes() {
+        let png = testing_png(10, 4, 0, 1, 0);
+        let header = ImageHeader::from_png(&png).unwrap();
+
+        assert_eq!(header.row_bytes(10), 2);
+        assert_eq!(header.filter_distance(), 1);
+    }
+
+    #[test]
+    fn test_interlaced_passes() {
+        let png = testing_png(3, 3, 2, 8, 1);
+        let header = ImageHeader::from_png(&png).unwrap();
+
+        assert_eq!(header.passes(), vec![(1, 1), (1, 1), (2, 1), (1, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        for (color_type, bit_depth, interlace_method) in [(2, 8, 0), (6, 16, 0), (0, 2, 1), (4, 8, 1)].iter() {
+            let png = testing_png(13, 9, *color_type, *bit_depth, *interlace_method);
+            let header = ImageHeader::from_png(&png).unwrap();
+            let data = inflate(&png).unwrap();
+
+            let scanlines = unfilter(&header, &data).unwrap();
+            assert_eq!(filter(&header, &scanlines).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let header = ImageHeader::from_png(&png).unwrap();
+        let data = inflate(&png).unwrap();
+
+        assert!(unfilter(&header, &data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_huge_dimensions() {
+        // Header claims far more image data than the IDAT chunk holds
+        let header = ImageHeader {
+            width: 0x7fff_ffff,
+            height: 0x7fff_ffff,
+            bit_depth: 8,
+            color_type: 6,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0
+        };
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), header.as_bytes()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), deflate(&[0; 64]).unwrap()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())
+        ]);
+
+        assert!(matches!(header.data_length(), Err(Error::ImageData(ImageDataError::TooLarge))));
+        assert!(unfilter(&header, &inflate(&png).unwrap()).is_err());
+        assert!(rgba_pixels(&png).is_err());
+
+        let small = ImageHeader { width: 4, height: 100_000, ..header };
+        assert!(matches!(unfilter(&small, &[0; 64]), Err(Error::ImageData(ImageDataError::TruncatedData))));
+    }
+
+    #[test]
+    fn test_sample() {
+        let header = ImageHeader::from_png(&testing_png(4, 1, 0, 2, 0)).unwrap();
+
+        assert_eq!(header.sample(&[0b1101_0010], 0), 3);
+        assert_eq!(header.sample(&[0b1101_0010], 1), 1);
+        assert_eq!(header.sample(&[0b1101_0010], 3), 2);
+        assert_eq!(header.sample_to_u8(3), 255);
+        assert_eq!(header.sample_to_u8(1), 85);
+    }
+
+    #[test]
+    fn test_rgba_pixels() {
+        let png = testing_png(13, 9, 6, 8, 0);
+        let header = ImageHeader::from_png(&png).unwrap();
+        let scanlines = unfilter(&header, &inflate(&png).unwrap()).unwrap();
+
+        let pixels = rgba_pixels(&png).unwrap();
+
+        assert_eq!(pixels.len(), 13 * 9);
+        assert_eq!(pixels[14][..], scanlines[1].data[4..8]);
+    }
+
+    #[test]
+    fn test_rgba_pixels_interlaced() {
+        // Interlacing only changes the layout of the image data, not the pixels
+        let header = ImageHeader::from_png(&testing_png(13, 9, 2, 8, 0)).unwrap();
+        let pixels = rgba_pixels(&testing_png(13, 9, 2, 8, 0)).unwrap();
+
+        let interlaced = ImageHeader { interlace_method: 1, ..header };
+        let mut scanlines = Vec::new();
+
+        for pass in interlaced.pass_layout() {
+            for row in 0..pass.height {
+                let y = (pass.y + row * pass.y_step) as usize;
+                let data = (0..pass.width as usize)
+                    .flat_map(|column| {
+                        let x = pass.x as usize + column * pass.x_step as usize;
+                        pixels[y * 13 + x][..3].to_vec()
+                    })
+                    .collect();
+                scanlines.push(Scanline { filter: 4, data });
+            }
+        }
+
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), interlaced.as_bytes()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), deflate(&filter(&interlaced, &scanlines).unwrap()).unwrap()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())
+        ]);
+
+        assert_eq!(rgba_pixels(&png).unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_rgba_pixels_with_palette() {
+        let header = ImageHeader {
+            width: 2,
+            height: 1,
+            bit_depth: 8,
+            color_type: 3,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0
+        };
+
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), header.as_bytes()),
+            Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![10, 20, 30, 40, 50, 60]),
+            Chunk::new(ChunkType::from_str("tRNS").unwrap(), vec![128]),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), deflate(&[0, 1, 0]).unwrap()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())
+        ]);
+
+        assert_eq!(rgba_pixels(&png).unwrap(), vec![[40, 50, 60, 255], [10, 20, 30, 128]]);
+    }
+
+    #[test]
+    fn test_replace_image_data() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let data = vec![0; 4 * 31];
+
+        let replaced = replace_image_data(&png, &data).unwrap();
+
+        assert_eq!(inflate(&replaced).unwrap(), data);
+        assert_eq!(replaced.chunks().len(), 3);
+        assert_eq!(&replaced.chunks()[1].chunk_type().to_string(), "IDAT");
+    }
+}
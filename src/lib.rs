@@ -0,0 +1,54 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+
+mod access;
+mod apng;
+pub mod archive;
+mod arena;
+pub mod args;
+mod assertion;
+mod capabilities;
+mod carrier;
+pub mod chunk;
+mod chunk_type;
+pub mod commands;
+mod compress;
+mod delta;
+mod demo;
+pub mod diagnostics;
+mod diff;
+#[cfg(all(test, feature = "differential"))]
+mod differential;
+mod envelope;
+mod error;
+pub mod events;
+pub mod files;
+mod image_data;
+pub mod locale;
+mod manifest;
+mod metadata;
+pub mod output;
+mod parity;
+pub mod png;
+mod print;
+pub mod profile;
+mod protect;
+mod quantize;
+mod registry;
+mod rename;
+mod repair;
+mod scan;
+mod seal;
+pub mod settings;
+mod sidecar;
+mod signature;
+mod stego;
+mod sync;
+mod wizard;
+mod zlib;
+
+/// Holds any kind of error
+pub type Error = error::PngmeError;
+
+/// Holds a `Result` of any kind of error
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,289 @@
+use std::env;
+use std::error;
+use std::fmt::{self, Display};
+use std::iter;
+use std::str::FromStr;
+
+use crate::metadata::ModificationTime;
+use crate::{Error, Result};
+
+/// Environment variables naming the locale of numbers, in order of precedence
+const ENV_VARS: [&str; 3] = ["LC_ALL", "LC_NUMERIC", "LANG"];
+
+/// Order of the parts of a date
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum DateOrder {
+    YearMonthDay,
+    DayMonthYear,
+    MonthDayYear
+}
+
+/// How numbers and dates are written in a locale
+#[derive(Debug, PartialEq, Eq)]
+struct Conventions {
+    /// Languages, or languages and territories such as en_GB, following these conventions
+    names: &'static [&'static str],
+
+    /// Separator between groups of three digits
+    group: &'static str,
+
+    decimal: &'static str,
+
+    /// Goes between a number and the percent sign
+    percent: &'static str,
+
+    date_order: DateOrder,
+
+    /// Separator between the parts of a date
+    date: &'static str
+}
+
+/// Conventions of the C locale, which are those of the output of most tools: no grouping and ISO 8601 dates
+const C: Conventions = Conventions {
+    names: &["c", "posix"],
+    group: "",
+    decimal: ".",
+    percent: "",
+    date_order: DateOrder::YearMonthDay,
+    date: "-"
+};
+
+/// Conventions of every known locale besides C, territories before their language
+static CONVENTIONS: [Conventions; 9] = [
+    Conventions {
+        names: &["en_gb", "en_ie", "en_au", "en_nz"],
+        group: ",",
+        decimal: ".",
+        percent: "",
+        date_order: DateOrder::DayMonthYear,
+        date: "/"
+    },
+    Conventions {
+        names: &["en"],
+        group: ",",
+        decimal: ".",
+        percent: "",
+        date_order: DateOrder::MonthDayYear,
+        date: "/"
+    },
+    Conventions {
+        names: &["de_ch"],
+        group: "'",
+        decimal: ".",
+        percent: "\u{a0}",
+        date_order: DateOrder::DayMonthYear,
+        date: "."
+    },
+    Conventions {
+        names: &["de"],
+        group: ".",
+        decimal: ",",
+        percent: "\u{a0}",
+        date_order: DateOrder::DayMonthYear,
+        date: "."
+    },
+    Conventions {
+        names: &["fr"],
+        group: "\u{202f}",
+        decimal: ",",
+        percent: "\u{202f}",
+        date_order: DateOrder::DayMonthYear,
+        date: "/"
+    },
+    Conventions {
+        names: &["es", "it", "pt"],
+        group: ".",
+        decimal: ",",
+        percent: "\u{a0}",
+        date_order: DateOrder::DayMonthYear,
+        date: "/"
+    },
+    Conventions {
+        names: &["nl"],
+        group: ".",
+        decimal: ",",
+        percent: "",
+        date_order: DateOrder::DayMonthYear,
+        date: "-"
+    },
+    Conventions {
+        names: &["ja", "zh", "ko"],
+        group: ",",
+        decimal: ".",
+        percent: "",
+        date_order: DateOrder::YearMonthDay,
+        date: "/"
+    },
+    Conventions {
+        names: &["ru", "pl", "sv", "fi", "nb", "da", "cs"],
+        group: "\u{a0}",
+        decimal: ",",
+        percent: "\u{a0}",
+        date_order: DateOrder::DayMonthYear,
+        date: "."
+    }
+];
+
+/// Locale of the numbers, sizes and dates in text output, which JSON, CSV and CBOR output never depend on
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Locale {
+    conventions: &'static Conventions
+}
+
+impl FromStr for Locale {
+    type Err = Error;
+
+    /// Locale named like the LANG environment variable, such as de_DE.UTF-8, or as a language tag such as de-DE
+    fn from_str(s: &str) -> Result<Self> {
+        // The encoding and modifier don't change how numbers are written
+        let name = s.split(['.', '@']).next().unwrap_or_default().replace('-', "_").to_lowercase();
+        let language = name.split('_').next().unwrap_or_default();
+
+        iter::once(&C)
+            .chain(CONVENTIONS.iter())
+            .find(|conventions| conventions.names.contains(&name.as_str()))
+            .or_else(|| CONVENTIONS.iter().find(|conventions| conventions.names.contains(&language)))
+            .map(|conventions| Locale { conventions })
+            .ok_or_else(|| LocaleError::InvalidLocale(s.to_string()).into())
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::C
+    }
+}
+
+impl Locale {
+    pub const C: Locale = Locale { conventions: &C };
+
+    /// Locale named by the environment, or C when it names none or one without known conventions
+    pub fn from_env() -> Locale {
+        ENV_VARS
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(Locale::C)
+    }
+
+    /// Whole number with its digits grouped by three
+    pub fn number(&self, n: u64) -> String {
+        let digits = n.to_string();
+        let mut grouped = String::with_capacity(digits.len() * 2);
+
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                grouped.push_str(self.conventions.group);
+            }
+            grouped.push(digit);
+        }
+
+        grouped
+    }
+
+    /// Number with the given count of decimal places
+    pub fn decimal(&self, x: f64, places: usize) -> String {
+        let formatted = format!("{:.*}", places, x.abs());
+        let (whole, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let sign = if x.is_sign_negative() && formatted.bytes().any(|byte| matches!(byte, b'1'..=b'9')) {
+            "-"
+        } else {
+            ""
+        };
+
+        match (whole.parse(), fraction) {
+            (Ok(whole), "") => format!("{}{}", sign, self.number(whole)),
+            (Ok(whole), fraction) => format!("{}{}{}{}", sign, self.number(whole), self.conventions.decimal, fraction),
+            (Err(_), _) => formatted
+        }
+    }
+
+    /// Ratio such as 0.5 as a percentage with one decimal place, such as 50.0%
+    pub fn percent(&self, ratio: f64) -> String {
+        format!("{}{}%", self.decimal(ratio * 100.0, 1), self.conventions.percent)
+    }
+
+    /// Date and time of a tIME chunk, always in 24 hour time
+    pub fn timestamp(&self, time: &ModificationTime) -> String {
+        let (year, month, day) = (format!("{:04}", time.year), format!("{:02}", time.month), format!("{:02}", time.day));
+
+        let parts = match self.conventions.date_order {
+            DateOrder::YearMonthDay => [year, month, day],
+            DateOrder::DayMonthYear => [day, month, year],
+            DateOrder::MonthDayYear => [month, day, year]
+        };
+
+        format!("{} {:02}:{:02}:{:02}", parts.join(self.conventions.date), time.hour, time.minute, time.second)
+    }
+}
+
+/// Locale errors
+#[derive(Debug)]
+pub enum LocaleError {
+    /// Locale has no known conventions
+    InvalidLocale(String)
+}
+
+impl error::Error for LocaleError {}
+
+impl Display for LocaleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocaleError::InvalidLocale(locale) => {
+                write!(f, "Unknown locale '{}', expected a name such as C, en_US or de-DE", locale)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(name: &str) -> Locale {
+        Locale::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(locale("de_DE.UTF-8"), locale("de"));
+        assert_eq!(locale("de-AT"), locale("de"));
+        assert_eq!(locale("POSIX"), Locale::C);
+        assert_eq!(locale("en_GB.utf8@euro").number(1234), "1,234");
+        assert_ne!(locale("en_GB"), locale("en_US"));
+        assert!(matches!(Locale::from_str("tlh"), Err(Error::Locale(LocaleError::InvalidLocale(_)))));
+        assert!(Locale::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(Locale::C.number(1234567), "1234567");
+        assert_eq!(locale("en_US").number(1234567), "1,234,567");
+        assert_eq!(locale("de_DE").number(123456), "123.456");
+        assert_eq!(locale("de_DE").number(999), "999");
+        assert_eq!(locale("fr_FR").number(1000), "1\u{202f}000");
+
+        assert_eq!(locale("de_DE").decimal(1234.5, 2), "1.234,50");
+        assert_eq!(locale("en_US").decimal(-0.004, 2), "0.00");
+        assert_eq!(locale("en_US").decimal(-1234.0, 0), "-1,234");
+        assert_eq!(Locale::C.decimal(f64::NAN, 2), "NaN");
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(Locale::C.percent(0.5), "50.0%");
+        assert_eq!(locale("de").percent(0.125), "12,5\u{a0}%");
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let time = ModificationTime { year: 2021, month: 3, day: 9, hour: 14, minute: 5, second: 30 };
+
+        assert_eq!(Locale::C.timestamp(&time), "2021-03-09 14:05:30");
+        assert_eq!(locale("en_US").timestamp(&time), "03/09/2021 14:05:30");
+        assert_eq!(locale("en_GB").timestamp(&time), "09/03/2021 14:05:30");
+        assert_eq!(locale("de").timestamp(&time), "09.03.2021 14:05:30");
+        assert_eq!(locale("ja_JP").timestamp(&time), "2021/03/09 14:05:30");
+    }
+}
@@ -1,21 +1,29 @@
-#![allow(unused_imports)]
-#![allow(dead_code)]
+use std::process;
+use structopt::StructOpt;
 
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
+use pngme::archive::ArchiveFiles;
+use pngme::events::Event;
+use pngme::{args, commands, files};
 
-use structopt::StructOpt;
+fn main() {
+    let opt = args::Opt::from_args();
+    let format = opt.output_format();
 
-/// Holds any kind of error
-pub type Error = Box<dyn std::error::Error>;
+    let result = opt.settings().and_then(|settings| {
+        settings.events.emit(&Event::Started { command: opt.subcommand.name().to_string() });
 
-/// Holds a `Result` of any kind of error
-pub type Result<T> = std::result::Result<T, Error>;
+        let files = ArchiveFiles::new(&files::DiskFiles, opt.zip_member.clone());
+        let result = format
+            .renderer(opt.force_binary)
+            .and_then(|mut out| commands::run(opt.subcommand, &settings, &files, out.as_mut()));
 
-fn main() -> Result<()> {
-    let opt = args::Opt::from_args();
-    commands::run(opt.subcommand)
+        settings.events.emit(&Event::finished(&result));
+        result
+    });
+
+    // Every kind of error exits with its own code so scripts can tell them apart
+    if let Err(error) = result {
+        format.error(&error);
+        process::exit(error.exit_code());
+    }
 }
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::apng::FrameOptions;
+use crate::files::FileProvider;
+use crate::Result;
+
+/// Default file name of the manifest written next to exported frames
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Describes how a folder of PNG frames is assembled into an APNG
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Number of times to play the animation - 0 loops forever
+    #[serde(default)]
+    pub num_plays: u32,
+
+    /// Frames of the animation, in order
+    pub frames: Vec<ManifestFrame>
+}
+
+/// A single frame of a manifest
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFrame {
+    /// File path of the frame, relative to the manifest
+    pub file: PathBuf,
+
+    #[serde(flatten)]
+    pub options: FrameOptions
+}
+
+impl Manifest {
+    /// Read a manifest from a JSON file
+    pub fn read(files: &dyn FileProvider, path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice(&files.read(path)?)?)
+    }
+
+    /// Write the manifest to a JSON file
+    pub fn write(&self, files: &dyn FileProvider, path: &Path) -> Result<()> {
+        files.write(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// File paths of the frames, resolved against the manifest's directory
+    pub fn frame_paths(&self, manifest_path: &Path) -> Vec<PathBuf> {
+        let directory = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+
+        self.frames
+            .iter()
+            .map(|frame| directory.join(&frame.file))
+            .collect()
+    }
+
+    /// Playback options of every frame
+    pub fn frame_options(&self) -> Vec<FrameOptions> {
+        self.frames.iter().map(|frame| frame.options).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apng::{BlendOp, DisposeOp};
+
+    #[test]
+    fn test_manifest_from_json() {
+        let json = r#"{
+            "num_plays": 2,
+            "frames": [
+                { "file": "frame_000.png", "delay_num": 100, "delay_den": 1000 },
+                {
+                    "file": "frame_001.png",
+                    "delay_num": 1,
+                    "delay_den": 10,
+                    "x_offset": 4,
+                    "dispose_op": "previous",
+                    "blend_op": "over"
+                }
+            ]
+        }"#;
+
+        let manifest: Manifest = serde_json::from_str(json).unwrap();
+
+        assert_eq!(manifest.num_plays, 2);
+        assert_eq!(manifest.frames[0].options, FrameOptions::with_delay_ms(100));
+        assert_eq!(manifest.frames[1].options.x_offset, 4);
+        assert_eq!(manifest.frames[1].options.y_offset, 0);
+        assert_eq!(manifest.frames[1].options.dispose_op, DisposeOp::Previous);
+        assert_eq!(manifest.frames[1].options.blend_op, BlendOp::Over);
+    }
+
+    #[test]
+    fn test_manifest_invalid_op() {
+        let json = r#"{
+            "frames": [
+                { "file": "frame_000.png", "delay_num": 1, "delay_den": 1, "blend_op": "under" }
+            ]
+        }"#;
+
+        assert!(serde_json::from_str::<Manifest>(json).is_err());
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let manifest = Manifest {
+            num_plays: 0,
+            frames: vec![ManifestFrame {
+                file: PathBuf::from("frame_000.png"),
+                options: FrameOptions::with_delay_ms(40)
+            }]
+        };
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert_eq!(serde_json::from_str::<Manifest>(&json).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_frame_paths() {
+        let manifest = Manifest {
+            num_plays: 0,
+            frames: vec![ManifestFrame {
+                file: PathBuf::from("frame_000.png"),
+                options: FrameOptions::with_delay_ms(40)
+            }]
+        };
+
+        let paths = manifest.frame_paths(Path::new("frames/manifest.json"));
+        assert_eq!(paths, vec![PathBuf::from("frames/frame_000.png")]);
+    }
+}
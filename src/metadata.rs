@@ -0,0 +1,340 @@
+use flate2::read::ZlibDecoder;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Display};
+use std::io::Read;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Chunk types holding textual metadata
+pub const TEXT_CHUNK_TYPES: [&str; 3] = ["tEXt", "zTXt", "iTXt"];
+
+/// A keyword and its text, as stored in a tEXt, zTXt or iTXt chunk
+///
+/// See [PNG Chunks - Textual information](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.Anc-text) for details
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEntry {
+    pub keyword: String,
+    pub text: String
+}
+
+impl TryFrom<&Chunk> for TextEntry {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        let separator = data
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or(MetadataError::InvalidText)?;
+
+        let keyword = latin1(&data[..separator]);
+        let rest = &data[separator + 1..];
+
+        let text = match chunk.chunk_type().to_string().as_str() {
+            "tEXt" => latin1(rest),
+            "zTXt" => {
+                // Only zlib compression is defined
+                match rest.split_first() {
+                    Some((0, compressed)) => latin1(&decompress(compressed)?),
+                    _ => return Err(MetadataError::InvalidText.into())
+                }
+            },
+            "iTXt" => {
+                if rest.len() < 2 {
+                    return Err(MetadataError::InvalidText.into());
+                }
+
+                let (compressed, method) = (rest[0], rest[1]);
+
+                // Language tag and translated keyword precede the text
+                let mut fields = rest[2..].splitn(3, |byte| *byte == 0);
+                let text = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(_), Some(_), Some(text)) => text,
+                    _ => return Err(MetadataError::InvalidText.into())
+                };
+
+                let text = match (compressed, method) {
+                    (0, _) => text.to_vec(),
+                    (1, 0) => decompress(text)?,
+                    _ => return Err(MetadataError::InvalidText.into())
+                };
+
+                String::from_utf8(text)?
+            },
+            _ => return Err(MetadataError::InvalidText.into())
+        };
+
+        Ok(TextEntry { keyword, text })
+    }
+}
+
+impl TextEntry {
+    /// Keywords are 1 to 79 printable Latin-1 characters without leading, trailing or consecutive spaces
+    pub fn is_keyword_valid(keyword: &str) -> bool {
+        let printable = keyword
+            .chars()
+            .all(|c| (' '..='~').contains(&c) || ('\u{a1}'..='\u{ff}').contains(&c));
+
+        printable
+            && (1..=79).contains(&keyword.chars().count())
+            && !keyword.starts_with(' ')
+            && !keyword.ends_with(' ')
+            && !keyword.contains("  ")
+    }
+
+    /// Chunk holding the entry, a tEXt chunk when the text is Latin-1 and an iTXt chunk otherwise
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        if !TextEntry::is_keyword_valid(&self.keyword) {
+            return Err(MetadataError::UnsupportedKeyword(self.keyword.clone()).into());
+        }
+
+        let keyword = self.keyword.chars().map(|c| c as u8);
+
+        if self.text.chars().all(|c| c <= '\u{ff}') {
+            let data = keyword
+                .chain([0])
+                .chain(self.text.chars().map(|c| c as u8))
+                .collect();
+
+            return Ok(Chunk::new(ChunkType::try_from(*b"tEXt")?, data));
+        }
+
+        // Uncompressed, without language tag or translated keyword
+        let data = keyword
+            .chain([0, 0, 0, 0, 0])
+            .chain(self.text.bytes())
+            .collect();
+
+        Ok(Chunk::new(ChunkType::try_from(*b"iTXt")?, data))
+    }
+}
+
+/// Last modification time of the image, as stored in a tIME chunk
+///
+/// See [PNG Chunks - tIME](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.tIME) for details
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ModificationTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8
+}
+
+impl ModificationTime {
+    /// Length of the tIME chunk's data
+    pub const LENGTH: usize = 7;
+
+    /// Date formatted as YYYY-MM-DD
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl TryFrom<&[u8]> for ModificationTime {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != ModificationTime::LENGTH {
+            return Err(MetadataError::InvalidTime.into());
+        }
+
+        let time = ModificationTime {
+            year: u16::from_be_bytes([bytes[0], bytes[1]]),
+            month: bytes[2],
+            day: bytes[3],
+            hour: bytes[4],
+            minute: bytes[5],
+            second: bytes[6]
+        };
+
+        // Leap seconds are allowed
+        if !(1..=12).contains(&time.month)
+            || !(1..=31).contains(&time.day)
+            || time.hour > 23
+            || time.minute > 59
+            || time.second > 60
+        {
+            return Err(MetadataError::InvalidTime.into());
+        }
+
+        Ok(time)
+    }
+}
+
+impl Display for ModificationTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}",
+            self.date(),
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+/// Every textual metadata entry of a PNG, in chunk order
+pub fn text_entries(png: &Png) -> Result<Vec<TextEntry>> {
+    png.chunks()
+        .iter()
+        .filter(|chunk| TEXT_CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str()))
+        .map(TextEntry::try_from)
+        .collect()
+}
+
+/// Text of the first textual metadata entry with the given keyword
+pub fn keyword_text(png: &Png, keyword: &str) -> Result<Option<String>> {
+    Ok(text_entries(png)?
+        .into_iter()
+        .find(|entry| entry.keyword == keyword)
+        .map(|entry| entry.text))
+}
+
+/// Last modification time of a PNG if it has a tIME chunk
+pub fn modification_time(png: &Png) -> Result<Option<ModificationTime>> {
+    png.chunk_by_type("tIME")
+        .map(|chunk| ModificationTime::try_from(chunk.data()))
+        .transpose()
+}
+
+/// Decode ISO 8859-1 bytes, every byte maps to the code point of the same value
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| *byte as char).collect()
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+
+    Ok(decompressed)
+}
+
+/// Metadata errors
+#[derive(Debug)]
+pub enum MetadataError {
+    /// Textual metadata chunk is malformed
+    InvalidText,
+
+    /// tIME chunk is malformed
+    InvalidTime,
+
+    /// Keyword can't be stored in a textual metadata chunk
+    UnsupportedKeyword(String)
+}
+
+impl error::Error for MetadataError {}
+
+impl Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataError::InvalidText => write!(f, "Invalid textual metadata chunk"),
+            MetadataError::InvalidTime => write!(f, "Invalid tIME chunk"),
+            MetadataError::UnsupportedKeyword(keyword) => write!(
+                f,
+                "Invalid keyword '{}', expected 1 to 79 Latin-1 characters without extra spaces",
+                keyword
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::image_data;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    #[test]
+    fn test_text_entry_from_text() {
+        let entry = TextEntry::try_from(&chunk("tEXt", b"Title\0Caf\xe9".to_vec())).unwrap();
+
+        assert_eq!(entry.keyword, "Title");
+        assert_eq!(entry.text, "Café");
+    }
+
+    #[test]
+    fn test_text_entry_from_compressed_text() {
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(image_data::deflate(b"Compressed text").unwrap());
+
+        let entry = TextEntry::try_from(&chunk("zTXt", data)).unwrap();
+
+        assert_eq!(entry.keyword, "Comment");
+        assert_eq!(entry.text, "Compressed text");
+    }
+
+    #[test]
+    fn test_text_entry_from_international_text() {
+        let entry = TextEntry::try_from(&chunk("iTXt", "Title\0\0\0pt\0Título\0Férias".as_bytes().to_vec())).unwrap();
+        assert_eq!(entry.keyword, "Title");
+        assert_eq!(entry.text, "Férias");
+
+        let mut data = b"Title\0\x01\0\0\0".to_vec();
+        data.extend(image_data::deflate("Férias".as_bytes()).unwrap());
+
+        let entry = TextEntry::try_from(&chunk("iTXt", data)).unwrap();
+        assert_eq!(entry.text, "Férias");
+    }
+
+    #[test]
+    fn test_invalid_text_entry() {
+        assert!(TextEntry::try_from(&chunk("tEXt", b"Title".to_vec())).is_err());
+        assert!(TextEntry::try_from(&chunk("zTXt", b"Title\0\x01abc".to_vec())).is_err());
+        assert!(TextEntry::try_from(&chunk("iTXt", b"Title\0\0\0en".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_text_entry_to_chunk() {
+        let entry = TextEntry { keyword: String::from("Title"), text: String::from("Café") };
+        let chunk = entry.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(TextEntry::try_from(&chunk).unwrap(), entry);
+
+        let entry = TextEntry { keyword: String::from("Title"), text: String::from("Praça 東京") };
+        let chunk = entry.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+        assert_eq!(TextEntry::try_from(&chunk).unwrap(), entry);
+
+        for keyword in ["", " Title", "Two  spaces", "東京", &"k".repeat(80)] {
+            let entry = TextEntry { keyword: keyword.to_string(), text: String::new() };
+            assert!(entry.to_chunk().is_err());
+        }
+    }
+
+    #[test]
+    fn test_modification_time() {
+        let time = ModificationTime::try_from(&[0x07, 0xE5, 3, 9, 14, 5, 30][..]).unwrap();
+
+        assert_eq!(time.date(), "2021-03-09");
+        assert_eq!(time.to_string(), "2021-03-09 14:05:30");
+
+        assert!(ModificationTime::try_from(&[0x07, 0xE5, 13, 9, 14, 5, 30][..]).is_err());
+        assert!(ModificationTime::try_from(&[0x07, 0xE5, 3, 9][..]).is_err());
+    }
+
+    #[test]
+    fn test_png_metadata() {
+        let png = Png::from_chunks(vec![
+            chunk("tEXt", b"Author\0Luis".to_vec()),
+            chunk("tIME", vec![0x07, 0xE5, 3, 9, 14, 5, 30]),
+            chunk("tEXt", b"Title\0Dice".to_vec())
+        ]);
+
+        assert_eq!(text_entries(&png).unwrap().len(), 2);
+        assert_eq!(keyword_text(&png, "Title").unwrap(), Some(String::from("Dice")));
+        assert_eq!(keyword_text(&png, "Comment").unwrap(), None);
+        assert_eq!(modification_time(&png).unwrap().unwrap().date(), "2021-03-09");
+    }
+}
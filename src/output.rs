@@ -0,0 +1,513 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{json, Value};
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+
+use crate::diagnostics::Rule;
+use crate::{Error, Result};
+
+/// Version of the fields of records in json, csv and cbor output, raised whenever a field is renamed or removed
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Format the results of a command are shown in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OutputFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+
+    /// One JSON object per result and line
+    Json,
+
+    /// Comma separated values, with a header line whenever the fields change
+    Csv,
+
+    /// Sequence of CBOR maps, one per result, with bytes stored as they are rather than as hex
+    Cbor,
+
+    /// Nothing, only the exit code tells how the command went
+    Quiet
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 5] =
+        [OutputFormat::Text, OutputFormat::Json, OutputFormat::Csv, OutputFormat::Cbor, OutputFormat::Quiet];
+
+    /// Name given to `--format`
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Quiet => "quiet"
+        }
+    }
+
+    /// Renderer writing results in this format to stdout
+    ///
+    /// Binary CBOR would garble a terminal, so it's refused when stdout is one unless `force_binary` is set
+    pub fn renderer(self, force_binary: bool) -> Result<Box<dyn OutputRenderer>> {
+        Ok(match self {
+            OutputFormat::Text => Box::new(TextRenderer::new(io::stdout())),
+            OutputFormat::Json => Box::new(JsonRenderer::new(io::stdout())),
+            OutputFormat::Csv => Box::new(CsvRenderer::new(io::stdout())),
+            OutputFormat::Cbor if io::stdout().is_terminal() && !force_binary => {
+                return Err(OutputError::BinaryTerminal.into())
+            },
+            OutputFormat::Cbor => Box::new(CborRenderer::new(io::stdout())),
+            OutputFormat::Quiet => Box::new(QuietRenderer)
+        })
+    }
+
+    /// Show a warning along with the code of its rule, as a JSON object on stdout with json output and on stderr
+    /// otherwise
+    pub fn warning(&self, rule: Rule, message: &str) {
+        match self {
+            OutputFormat::Json => println!("{}", warning_json(rule, message)),
+            _ => eprintln!("{} [{}]", sanitize(message), rule.code())
+        }
+    }
+
+    /// Show the error a command failed with, as a JSON object on stdout with json output and on stderr otherwise
+    pub fn error(&self, error: &Error) {
+        match self {
+            OutputFormat::Json => println!("{}", error_json(error)),
+            _ => eprintln!("Error: {}", sanitize(&error.to_string()))
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        OutputFormat::ALL
+            .iter()
+            .find(|format| format.name() == s)
+            .copied()
+            .ok_or_else(|| OutputError::InvalidFormat(s.to_string()).into())
+    }
+}
+
+fn warning_json(rule: Rule, message: &str) -> Value {
+    json!({ "warning": { "code": rule.code(), "rule": rule.name(), "message": message } })
+}
+
+fn error_json(error: &Error) -> Value {
+    json!({ "error": { "code": error.code(), "kind": error.kind().name(), "message": error.to_string() } })
+}
+
+/// One result of a command, such as a chunk or a change, as a human readable text and as named fields
+#[derive(Debug, PartialEq, Clone)]
+pub struct Record {
+    pub text: String,
+
+    /// Values in the order they are shown
+    pub fields: Vec<(String, Field)>
+}
+
+/// Value of a field of a record
+#[derive(Debug, PartialEq, Clone)]
+pub enum Field {
+    Value(Value),
+
+    /// Raw bytes, such as chunk data, written as a byte string by binary formats and as hex by the others
+    Bytes(Vec<u8>)
+}
+
+impl Record {
+    /// Create a record without any field yet
+    pub fn new(text: impl Into<String>) -> Self {
+        Record {
+            text: text.into(),
+            fields: Vec::new()
+        }
+    }
+
+    /// Add a named value to the record
+    pub fn field(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.fields.push((name.to_string(), Field::Value(value.into())));
+        self
+    }
+
+    /// Add named raw bytes to the record
+    pub fn bytes(mut self, name: &str, bytes: impl Into<Vec<u8>>) -> Self {
+        self.fields.push((name.to_string(), Field::Bytes(bytes.into())));
+        self
+    }
+}
+
+/// Shows the results of commands
+///
+/// Commands describe every result as a `Record`, so supporting another format only takes a new renderer
+pub trait OutputRenderer {
+    /// Show one result of a command
+    fn record(&mut self, record: &Record) -> Result<()>;
+
+    /// Show a message meant for people, such as "Nothing to repair", which isn't a result itself
+    fn note(&mut self, message: &str) -> Result<()>;
+}
+
+/// Text safe to write to a terminal, with control characters escaped
+///
+/// Hidden messages and metadata come from untrusted files, and ANSI escape sequences, carriage returns or
+/// bidirectional overrides in them could rewrite what the terminal shows. Line breaks and tabs are kept
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\n' | '\t' => sanitized.push(c),
+            '\r' => sanitized.push_str("\\r"),
+            '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' => {
+                sanitized.push_str(&format!("\\u{{{:x}}}", c as u32))
+            },
+            c if c.is_control() => sanitized.push_str(&format!("\\x{:02x}", c as u32)),
+            c => sanitized.push(c)
+        }
+    }
+
+    sanitized
+}
+
+/// Writes the text of records and notes, with control characters escaped
+pub struct TextRenderer<W: Write> {
+    writer: W
+}
+
+impl<W: Write> TextRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        TextRenderer { writer }
+    }
+}
+
+impl<W: Write> OutputRenderer for TextRenderer<W> {
+    fn record(&mut self, record: &Record) -> Result<()> {
+        self.note(&record.text)
+    }
+
+    fn note(&mut self, message: &str) -> Result<()> {
+        writeln!(self.writer, "{}", sanitize(message))?;
+        Ok(())
+    }
+}
+
+/// Writes the fields of every record as a JSON object on a line of its own, skipping notes
+pub struct JsonRenderer<W: Write> {
+    writer: W
+}
+
+impl<W: Write> JsonRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        JsonRenderer { writer }
+    }
+}
+
+impl<W: Write> OutputRenderer for JsonRenderer<W> {
+    fn record(&mut self, record: &Record) -> Result<()> {
+        let fields = Fields { fields: &record.fields, binary: false };
+
+        writeln!(self.writer, "{}", serde_json::to_string(&fields)?)?;
+        Ok(())
+    }
+
+    fn note(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the fields of every record as a CBOR map, one after the other, skipping notes
+///
+/// The output is a CBOR sequence as defined by RFC 8742, without anything between the maps
+pub struct CborRenderer<W: Write> {
+    writer: W
+}
+
+impl<W: Write> CborRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        CborRenderer { writer }
+    }
+}
+
+impl<W: Write> OutputRenderer for CborRenderer<W> {
+    fn record(&mut self, record: &Record) -> Result<()> {
+        let fields = Fields { fields: &record.fields, binary: true };
+
+        ciborium::ser::into_writer(&fields, &mut self.writer).map_err(|error| match error {
+            ciborium::ser::Error::Io(error) => Error::from(error),
+            ciborium::ser::Error::Value(message) => OutputError::Encoding(message).into()
+        })
+    }
+
+    fn note(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Fields of a record serialized as a map, keeping their order
+///
+/// Bytes are serialized as they are in binary formats, and as a hex string otherwise
+struct Fields<'a> {
+    fields: &'a [(String, Field)],
+    binary: bool
+}
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+
+        for (name, field) in self.fields {
+            match field {
+                Field::Value(value) => map.serialize_entry(name, value)?,
+                Field::Bytes(bytes) if self.binary => map.serialize_entry(name, &byte_string(bytes))?,
+                Field::Bytes(bytes) => map.serialize_entry(name, &hex(bytes))?
+            }
+        }
+
+        map.end()
+    }
+}
+
+/// Bytes serialized as a byte string rather than a sequence of numbers
+fn byte_string(bytes: &[u8]) -> impl Serialize + '_ {
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    Bytes(bytes)
+}
+
+/// Lowercase hex digits of some bytes
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Writes the fields of every record as comma separated values, skipping notes
+///
+/// A header line with the field names comes before the first record and any record with other fields
+pub struct CsvRenderer<W: Write> {
+    writer: W,
+    header: Option<Vec<String>>
+}
+
+impl<W: Write> CsvRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        CsvRenderer { writer, header: None }
+    }
+}
+
+impl<W: Write> OutputRenderer for CsvRenderer<W> {
+    fn record(&mut self, record: &Record) -> Result<()> {
+        let names: Vec<String> = record.fields.iter().map(|(name, _)| name.clone()).collect();
+
+        if self.header.as_ref() != Some(&names) {
+            let header: Vec<String> = names.iter().map(|name| csv_cell(name)).collect();
+            writeln!(self.writer, "{}", header.join(","))?;
+            self.header = Some(names);
+        }
+
+        let values: Vec<String> = record
+            .fields
+            .iter()
+            .map(|(_, field)| match field {
+                Field::Value(Value::Null) => String::new(),
+                Field::Value(Value::String(text)) => csv_cell(text),
+                Field::Value(value) => csv_cell(&value.to_string()),
+                Field::Bytes(bytes) => hex(bytes)
+            })
+            .collect();
+
+        writeln!(self.writer, "{}", values.join(","))?;
+        Ok(())
+    }
+
+    fn note(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Shows nothing at all
+pub struct QuietRenderer;
+
+impl OutputRenderer for QuietRenderer {
+    fn record(&mut self, _: &Record) -> Result<()> {
+        Ok(())
+    }
+
+    fn note(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// CSV cell holding the text, quoted when it contains a separator, quote or line break
+fn csv_cell(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string()
+    }
+}
+
+/// Output errors
+#[derive(Debug)]
+pub enum OutputError {
+    /// Output format is none of text, json, csv, cbor or quiet
+    InvalidFormat(String),
+
+    /// A record can't be encoded in the output format
+    Encoding(String),
+
+    /// Binary output would be written to a terminal
+    BinaryTerminal
+}
+
+impl error::Error for OutputError {}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputError::InvalidFormat(format) => write!(
+                f,
+                "Invalid output format '{}', expected text, json, csv, cbor or quiet",
+                format
+            ),
+            OutputError::Encoding(message) => write!(f, "Failed to encode output: {}", message),
+            OutputError::BinaryTerminal => write!(
+                f,
+                "Refusing to write cbor output to a terminal, redirect it to a file or pass --force-binary"
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record::new("+ tEXt").field("chunk_type", "tEXt").field("change", "added").field("length", 12),
+            Record::new("- ruSt").field("chunk_type", "ruSt").field("change", "removed").field("length", 5),
+            Record::new("Hello, \"world\"").field("message", "Hello, \"world\"").bytes("data", b"Hi".to_vec())
+        ]
+    }
+
+    fn render(renderer: &mut dyn OutputRenderer) {
+        for record in records() {
+            renderer.record(&record).unwrap();
+        }
+
+        renderer.note("Nothing else").unwrap();
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("Hello\n\tworld"), "Hello\n\tworld");
+        assert_eq!(sanitize("\x1b[2J\x1b]0;owned\x07"), "\\x1b[2J\\x1b]0;owned\\x07");
+        assert_eq!(sanitize("fake\rreal \u{9b}31m"), "fake\\rreal \\x9b31m");
+        assert_eq!(sanitize("evil\u{202E}gnp.exe"), "evil\\u{202e}gnp.exe");
+        assert_eq!(sanitize("Grüße 🦀"), "Grüße 🦀");
+    }
+
+    #[test]
+    fn test_text_renderer_escapes_control_characters() {
+        let mut output = Vec::new();
+        TextRenderer::new(&mut output).record(&Record::new("Message: \x1b[31mred")).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "Message: \\x1b[31mred\n");
+    }
+
+    #[test]
+    fn test_text_renderer() {
+        let mut output = Vec::new();
+        render(&mut TextRenderer::new(&mut output));
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "+ tEXt\n- ruSt\nHello, \"world\"\nNothing else\n"
+        );
+    }
+
+    #[test]
+    fn test_json_renderer() {
+        let mut output = Vec::new();
+        render(&mut JsonRenderer::new(&mut output));
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"chunk_type\":\"tEXt\",\"change\":\"added\",\"length\":12}\n\
+             {\"chunk_type\":\"ruSt\",\"change\":\"removed\",\"length\":5}\n\
+             {\"message\":\"Hello, \\\"world\\\"\",\"data\":\"4869\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_renderer() {
+        let mut output = Vec::new();
+        render(&mut CsvRenderer::new(&mut output));
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chunk_type,change,length\ntEXt,added,12\nruSt,removed,5\nmessage,data\n\"Hello, \"\"world\"\"\",4869\n"
+        );
+    }
+
+    #[test]
+    fn test_cbor_renderer() {
+        use ciborium::value::Value as Cbor;
+
+        let mut output = Vec::new();
+        render(&mut CborRenderer::new(&mut output));
+
+        let mut reader = &output[..];
+        let mut maps = Vec::new();
+
+        while !reader.is_empty() {
+            maps.push(ciborium::de::from_reader::<Cbor, _>(&mut reader).unwrap());
+        }
+
+        assert_eq!(maps.len(), 3);
+        assert_eq!(
+            maps[0],
+            Cbor::Map(vec![
+                (Cbor::from("chunk_type"), Cbor::from("tEXt")),
+                (Cbor::from("change"), Cbor::from("added")),
+                (Cbor::from("length"), Cbor::from(12))
+            ])
+        );
+        assert_eq!(
+            maps[2],
+            Cbor::Map(vec![
+                (Cbor::from("message"), Cbor::from("Hello, \"world\"")),
+                (Cbor::from("data"), Cbor::Bytes(b"Hi".to_vec()))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_warning_and_error_json() {
+        assert_eq!(
+            warning_json(Rule::SkippedFile, "Skipped a.png").to_string(),
+            r#"{"warning":{"code":"PNGME004","message":"Skipped a.png","rule":"skipped-file"}}"#
+        );
+
+        let error = Error::from(OutputError::InvalidFormat(String::from("yaml")));
+        assert_eq!(error_json(&error)["error"]["code"], "PNGME107");
+        assert_eq!(error_json(&error)["error"]["kind"], "invalid-input");
+        assert_eq!(error_json(&error)["error"]["message"], error.to_string());
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!(OutputFormat::from_str("csv").unwrap(), OutputFormat::Csv);
+        assert_eq!(OutputFormat::from_str("quiet").unwrap(), OutputFormat::Quiet);
+        assert_eq!(OutputFormat::from_str("cbor").unwrap(), OutputFormat::Cbor);
+        assert!(OutputFormat::from_str("yaml").is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Display};
+
+use crc::crc32;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sha2::{Digest, Sha256};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{ChunkPosition, Png};
+use crate::stego::{self, Mode};
+use crate::Result;
+
+/// Start of a parity file, telling it apart from a PNG or a payload
+const MAGIC: [u8; 4] = [0x8A, b'P', b'R', 0x1A];
+
+/// Magic, mode, chunk type, payload length, shard size, shard counts and payload digest
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 4 + 4 + 4 + 1 + 1 + 32;
+
+/// Reed-Solomon over GF(256) can't have more than 256 shards, data and parity together
+pub const MAX_DATA_SHARDS: usize = 128;
+
+/// Smallest shard, so small payloads aren't split into shards mostly made of checksums
+const MIN_SHARD_SIZE: usize = 64;
+
+/// Parity data of a hidden payload, kept next to the carrier rather than in it
+///
+/// The payload is split into data shards, each with a checksum telling whether it survived, and as many parity
+/// shards are computed from them. Any set of intact shards as large as the number of data shards rebuilds the
+/// payload, so the parity shards alone are enough once the payload is gone from the carrier
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Parity {
+    /// Where the payload is hidden
+    pub mode: Mode,
+
+    /// Chunk type of the payload's chunk, unused in lsb mode
+    pub chunk_type: ChunkType,
+
+    /// Length of the payload
+    pub length: usize,
+
+    /// SHA-256 of the payload, checked once it is rebuilt
+    digest: [u8; 32],
+
+    shard_size: usize,
+
+    /// Checksum of each data shard
+    checksums: Vec<u32>,
+
+    /// Parity shards, each with its checksum
+    parity: Vec<(u32, Vec<u8>)>
+}
+
+/// Payload rebuilt from what is left of it in a carrier and its parity file
+#[derive(Debug)]
+pub struct Recovery {
+    /// Carrier with the payload as it was hidden
+    pub png: Png,
+
+    /// Number of data shards the payload is split into
+    pub shards: usize,
+
+    /// Number of data shards that were damaged or missing and had to be rebuilt
+    pub rebuilt: usize,
+
+    /// Whether the carrier already held the payload as it was hidden
+    pub intact: bool
+}
+
+impl Parity {
+    /// Parity data of a payload hidden in the given mode, in a chunk of the given type in chunk mode
+    pub fn new(payload: &[u8], mode: Mode, chunk_type: ChunkType) -> Result<Parity> {
+        let count = payload.len().div_ceil(MIN_SHARD_SIZE).clamp(1, MAX_DATA_SHARDS);
+        let shard_size = payload.len().div_ceil(count).max(1);
+
+        let mut shards = split(payload, count, shard_size);
+        let checksums = shards.iter().map(|shard| crc32::checksum_ieee(shard)).collect();
+
+        shards.extend((0..count).map(|_| vec![0; shard_size]));
+        codec(count)?.encode(&mut shards).map_err(|_| ParityError::InvalidParityFile)?;
+
+        let parity = shards.split_off(count).into_iter().map(|shard| (crc32::checksum_ieee(&shard), shard));
+
+        Ok(Parity {
+            mode,
+            chunk_type,
+            length: payload.len(),
+            digest: Sha256::digest(payload).into(),
+            shard_size,
+            checksums,
+            parity: parity.collect()
+        })
+    }
+
+    /// Rebuild the payload out of what is left of it in the carrier, and hide it there again
+    ///
+    /// In chunk mode, the chunk of the type closest to the payload is restored, or a new one is inserted before IEND
+    /// when the chunk is gone. In lsb mode, the payload is hidden in the pixels again
+    pub fn recover(&self, png: &Png) -> Result<Recovery> {
+        let (index, survivor) = match self.mode {
+            Mode::Chunk => self.closest_chunk(png),
+            Mode::Lsb => (None, stego::extract(png).ok())
+        };
+
+        let count = self.checksums.len();
+        let mut shards: Vec<Option<Vec<u8>>> = match &survivor {
+            Some(survivor) => split(survivor, count, self.shard_size)
+                .into_iter()
+                .zip(&self.checksums)
+                .map(|(shard, checksum)| checked(shard, *checksum))
+                .collect(),
+            None => vec![None; count]
+        };
+        shards.extend(self.parity.iter().map(|(checksum, shard)| checked(shard.clone(), *checksum)));
+
+        let rebuilt = shards[..count].iter().filter(|shard| shard.is_none()).count();
+        let intact_parity = shards[count..].iter().filter(|shard| shard.is_some()).count();
+
+        if rebuilt > intact_parity {
+            return Err(ParityError::TooDamaged { missing: rebuilt, parity: intact_parity }.into());
+        }
+
+        codec(count)?.reconstruct_data(&mut shards).map_err(|_| ParityError::InvalidParityFile)?;
+
+        let mut payload: Vec<u8> = shards.into_iter().take(count).flatten().flatten().collect();
+        payload.truncate(self.length);
+
+        if <[u8; 32]>::from(Sha256::digest(&payload)) != self.digest {
+            return Err(ParityError::DigestMismatch.into());
+        }
+
+        let intact = survivor.as_deref() == Some(&payload[..]);
+
+        let png = match (self.mode, index) {
+            (Mode::Chunk, Some(index)) => {
+                let mut chunks = png.chunks().to_vec();
+                chunks[index] = Chunk::new(self.chunk_type.clone(), payload);
+                Png::from_chunks(chunks)
+            },
+            (Mode::Chunk, None) => {
+                let mut png = png.clone();
+                let index = png.position_index(&ChunkPosition::BeforeIend)?;
+                png.insert_chunk(index, Chunk::new(self.chunk_type.clone(), payload))?;
+                png
+            },
+            (Mode::Lsb, _) => stego::embed(png, &payload)?
+        };
+
+        Ok(Recovery { png, shards: count, rebuilt, intact })
+    }
+
+    /// Index and data of the chunk of the type sharing the most shards with the payload, if there is such a chunk
+    fn closest_chunk(&self, png: &Png) -> (Option<usize>, Option<Vec<u8>>) {
+        let chunk_type = self.chunk_type.to_string();
+        let intact = |data: &[u8]| {
+            split(data, self.checksums.len(), self.shard_size)
+                .iter()
+                .zip(&self.checksums)
+                .filter(|(shard, checksum)| crc32::checksum_ieee(shard) == **checksum)
+                .count()
+        };
+
+        png.chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type().to_string() == chunk_type)
+            .max_by_key(|(index, chunk)| (intact(chunk.data()), std::cmp::Reverse(*index)))
+            .map_or((None, None), |(index, chunk)| (Some(index), Some(chunk.data().to_vec())))
+    }
+
+    /// Parity file holding the parity data
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(match self.mode {
+            Mode::Chunk => 0,
+            Mode::Lsb => 1
+        });
+        bytes.extend_from_slice(&self.chunk_type.bytes());
+        bytes.extend_from_slice(&(self.length as u32).to_be_bytes());
+        bytes.extend_from_slice(&(self.shard_size as u32).to_be_bytes());
+        bytes.push(self.checksums.len() as u8);
+        bytes.push(self.parity.len() as u8);
+        bytes.extend_from_slice(&self.digest);
+
+        for checksum in &self.checksums {
+            bytes.extend_from_slice(&checksum.to_be_bytes());
+        }
+
+        for (checksum, shard) in &self.parity {
+            bytes.extend_from_slice(&checksum.to_be_bytes());
+            bytes.extend_from_slice(shard);
+        }
+
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Parity {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(&MAGIC) || bytes.len() < HEADER_LENGTH {
+            return Err(ParityError::InvalidParityFile.into());
+        }
+
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+        };
+
+        let mode = match bytes[4] {
+            0 => Mode::Chunk,
+            1 => Mode::Lsb,
+            _ => return Err(ParityError::InvalidParityFile.into())
+        };
+        let chunk_type = ChunkType::try_from([bytes[5], bytes[6], bytes[7], bytes[8]])
+            .map_err(|_| ParityError::InvalidParityFile)?;
+        let length = u32_at(9) as usize;
+        let shard_size = u32_at(13) as usize;
+        let (count, parity_count) = (bytes[17] as usize, bytes[18] as usize);
+
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&bytes[19..HEADER_LENGTH]);
+
+        let expected = HEADER_LENGTH + count * 4 + parity_count * (4 + shard_size);
+
+        if count == 0
+            || count > MAX_DATA_SHARDS
+            || parity_count != count
+            || count * shard_size < length
+            || bytes.len() != expected
+        {
+            return Err(ParityError::InvalidParityFile.into());
+        }
+
+        let checksums = (0..count).map(|shard| u32_at(HEADER_LENGTH + shard * 4)).collect();
+        let parity = bytes[HEADER_LENGTH + count * 4..]
+            .chunks(4 + shard_size)
+            .map(|shard| (u32::from_be_bytes([shard[0], shard[1], shard[2], shard[3]]), shard[4..].to_vec()))
+            .collect();
+
+        Ok(Parity { mode, chunk_type, length, digest, shard_size, checksums, parity })
+    }
+}
+
+/// Shard if it matches its checksum, `None` if it was damaged
+fn checked(shard: Vec<u8>, checksum: u32) -> Option<Vec<u8>> {
+    Some(shard).filter(|shard| crc32::checksum_ieee(shard) == checksum)
+}
+
+fn codec(count: usize) -> Result<ReedSolomon> {
+    Ok(ReedSolomon::new(count, count).map_err(|_| ParityError::InvalidParityFile)?)
+}
+
+/// Data split into shards of the same size, padded with zeros and cut to the room the shards have
+fn split(data: &[u8], count: usize, shard_size: usize) -> Vec<Vec<u8>> {
+    let mut padded = data[..data.len().min(count * shard_size)].to_vec();
+    padded.resize(count * shard_size, 0);
+
+    padded.chunks(shard_size).map(<[u8]>::to_vec).collect()
+}
+
+/// Parity errors
+#[derive(Debug)]
+pub enum ParityError {
+    /// Parity file is cut short or isn't a parity file
+    InvalidParityFile,
+
+    /// More data shards are damaged than there are intact parity shards to rebuild them
+    TooDamaged { missing: usize, parity: usize },
+
+    /// Rebuilt payload isn't the one the parity file was made for
+    DigestMismatch
+}
+
+impl error::Error for ParityError {}
+
+impl Display for ParityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParityError::InvalidParityFile => write!(f, "Parity file is damaged or isn't a parity file"),
+            ParityError::TooDamaged { missing, parity } => write!(
+                f,
+                "Payload can't be rebuilt: {} shards are damaged but only {} parity shards are intact",
+                missing, parity
+            ),
+            ParityError::DigestMismatch => write!(f, "Rebuilt payload doesn't match the parity file's checksum")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::tests::testing_png;
+    use crate::Error;
+    use std::str::FromStr;
+
+    fn payload() -> Vec<u8> {
+        (0..1000u32).map(|byte| (byte * 7 % 251) as u8).collect()
+    }
+
+    fn carrier(payload: &[u8]) -> Png {
+        let mut png = testing_png(4, 4, 2, 8, 0);
+        let index = png.position_index(&ChunkPosition::BeforeIend).unwrap();
+        png.insert_chunk(index, Chunk::new(ChunkType::from_str("ruSt").unwrap(), payload.to_vec())).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_parity_file_round_trip() {
+        let parity = Parity::new(&payload(), Mode::Chunk, ChunkType::from_str("ruSt").unwrap()).unwrap();
+        let bytes = parity.as_bytes();
+
+        assert_eq!(Parity::try_from(&bytes[..]).unwrap(), parity);
+        assert!(Parity::try_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Parity::try_from(&payload()[..]).is_err());
+    }
+
+    #[test]
+    fn test_recover_stripped_chunk() {
+        let payload = payload();
+        let parity = Parity::new(&payload, Mode::Chunk, ChunkType::from_str("ruSt").unwrap()).unwrap();
+
+        let mut stripped = carrier(&payload);
+        stripped.remove_chunk("ruSt").unwrap();
+
+        let recovery = parity.recover(&stripped).unwrap();
+        assert_eq!((recovery.rebuilt, recovery.shards, recovery.intact), (16, 16, false));
+        assert_eq!(recovery.png, carrier(&payload));
+
+        assert!(parity.recover(&carrier(&payload)).unwrap().intact);
+    }
+
+    #[test]
+    fn test_recover_damaged_chunk() {
+        let payload = payload();
+        let parity = Parity::new(&payload, Mode::Chunk, ChunkType::from_str("ruSt").unwrap()).unwrap();
+
+        let mut damaged = payload.clone();
+        damaged[10] ^= 0xFF;
+        damaged.truncate(900);
+
+        let recovery = parity.recover(&carrier(&damaged)).unwrap();
+        assert_eq!(recovery.rebuilt, 3);
+        assert_eq!(recovery.png, carrier(&payload));
+    }
+
+    #[test]
+    fn test_recover_with_damaged_parity() {
+        let payload = payload();
+        let mut parity = Parity::new(&payload, Mode::Chunk, ChunkType::from_str("ruSt").unwrap()).unwrap();
+        parity.parity[0].1[0] ^= 0xFF;
+
+        // Every parity shard is needed once the chunk is gone
+        let mut stripped = carrier(&payload);
+        stripped.remove_chunk("ruSt").unwrap();
+        assert!(matches!(
+            parity.recover(&stripped),
+            Err(Error::Parity(ParityError::TooDamaged { missing: 16, parity: 15 }))
+        ));
+
+        // A mostly intact chunk needs few of them
+        let mut damaged = payload.clone();
+        damaged[0] ^= 0xFF;
+        assert_eq!(parity.recover(&carrier(&damaged)).unwrap().png, carrier(&payload));
+    }
+
+    #[test]
+    fn test_recover_lsb() {
+        let payload = b"hidden in the pixels".to_vec();
+        let parity = Parity::new(&payload, Mode::Lsb, ChunkType::from_str("ruSt").unwrap()).unwrap();
+        let cover = testing_png(16, 16, 2, 8, 0);
+
+        let recovery = parity.recover(&cover).unwrap();
+        assert_eq!(stego::extract(&recovery.png).unwrap(), payload);
+        assert!(parity.recover(&recovery.png).unwrap().intact);
+    }
+}
@@ -1,43 +1,189 @@
 use std::fmt::{self, Display};
 use std::convert::{TryFrom, TryInto};
 use std::error;
+use std::iter::FromIterator;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::{Error, Result};
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkTypes};
+use crate::chunk_type::ChunkType;
 
 /// A full and valid PNG composed of Chunks
-#[derive(Debug, PartialEq, Eq)]
+///
+/// Clones share their chunks, so a parsed PNG can be handed to many threads without copying it. The chunks are only
+/// copied when a clone is modified while others still share them
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct Png {
-    chunks: Vec<Chunk>
+    chunks: Arc<Vec<Chunk>>
 }
 
 impl Png {
     /// The standard PNG header
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
+    /// Create a PNG without any chunks, to be filled with `append_chunk` or `insert_chunk`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     /// Create a PNG from a vector of chunks
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+        Self { chunks: Arc::new(chunks) }
+    }
+
+    /// Chunks to modify, copied first if another clone shares them
+    fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        Arc::make_mut(&mut self.chunks)
     }
 
     /// Add a chunk to the PNG
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        self.chunks_mut().push(chunk);
     }
 
-    /// Remove a chunk from the PNG if chunk_type has any match
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        let index = self.chunks
+    /// Insert a chunk in the PNG at the given index
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds(index).into());
+        }
+
+        self.chunks_mut().insert(index, chunk);
+
+        Ok(())
+    }
+
+    /// Find the index of the first chunk with a given chunk_type
+    pub fn chunk_index(&self, chunk_type: &str) -> Option<usize> {
+        self.chunks
             .iter()
             .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Resolve a chunk position into an index where a chunk can be inserted
+    pub fn position_index(&self, position: &ChunkPosition) -> Result<usize> {
+        match position {
+            ChunkPosition::AfterIhdr => self
+                .chunk_index("IHDR")
+                .map(|index| index + 1)
+                .ok_or_else(|| PngError::ChunkNotFound.into()),
+            // Fall back to the end of the PNG if it has no IEND chunk
+            ChunkPosition::BeforeIend => Ok(self
+                .chunk_index("IEND")
+                .unwrap_or(self.chunks.len())),
+            // IHDR must stay the first chunk, so nothing goes before or at its index
+            ChunkPosition::Index(index) if self.chunk_index("IHDR").is_some_and(|ihdr| *index <= ihdr) => {
+                Err(PngError::IndexBeforeIhdr(*index).into())
+            },
+            ChunkPosition::Index(index) if *index <= self.chunks.len() => Ok(*index),
+            ChunkPosition::Index(index) => Err(PngError::IndexOutOfBounds(*index).into())
+        }
+    }
+
+    /// Remove a chunk from the PNG if chunk_type has any match
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        let index = self
+            .chunk_index(chunk_type)
             .ok_or(PngError::ChunkNotFound)?;
 
-        let removed = self.chunks.remove(index);
+        let removed = self.chunks_mut().remove(index);
 
         Ok(removed)
     }
 
+    /// Remove the chunk at the given index
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk> {
+        if index >= self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds(index).into());
+        }
+
+        Ok(self.chunks_mut().remove(index))
+    }
+
+    /// Replace the first chunk with the same chunk_type as the given chunk, returning the replaced chunk
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Result<Chunk> {
+        let index = self
+            .chunk_index(&chunk.chunk_type().to_string())
+            .ok_or(PngError::ChunkNotFound)?;
+
+        let replaced = std::mem::replace(&mut self.chunks_mut()[index], chunk);
+
+        Ok(replaced)
+    }
+
+    /// Merge every run of consecutive IDAT chunks into as few chunks as possible, each holding at most max_size bytes
+    ///
+    /// Some encoders write thousands of tiny IDAT chunks, which makes parsing slow. The image data itself is unchanged
+    pub fn coalesce_idat(&mut self, max_size: usize) -> Result<()> {
+        if max_size == 0 || max_size > Chunk::MAX_LENGTH as usize {
+            return Err(PngError::InvalidChunkSize(max_size).into());
+        }
+
+        let idat = ChunkType::from_str("IDAT")?;
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+
+        // Data of the run of IDAT chunks being merged, if any
+        let mut run: Option<Vec<u8>> = None;
+
+        for chunk in std::mem::take(self.chunks_mut()) {
+            if *chunk.chunk_type() == idat {
+                run.get_or_insert_with(Vec::new).extend_from_slice(chunk.data());
+                continue;
+            }
+
+            if let Some(data) = run.take() {
+                chunks.extend(split_data(&idat, &data, max_size));
+            }
+
+            chunks.push(chunk);
+        }
+
+        if let Some(data) = run {
+            chunks.extend(split_data(&idat, &data, max_size));
+        }
+
+        self.chunks = Arc::new(chunks);
+
+        Ok(())
+    }
+
+    /// Parse a PNG whose chunks point into the arena holding the whole file, instead of each owning a copy of its data
+    pub fn from_arena(arena: Arc<[u8]>, types: ChunkTypes) -> Result<Png> {
+        Png::parse(&arena, |offset| Chunk::from_arena(&arena, offset, types))
+    }
+
+    /// Parse the bytes of a PNG file, accepting the given chunk types
+    pub fn from_bytes(bytes: &[u8], types: ChunkTypes) -> Result<Png> {
+        Png::parse(bytes, |offset| Chunk::from_bytes(&bytes[offset..], types))
+    }
+
+    /// Check the signature of the bytes, then parse every chunk with the given function from the offset it starts at
+    fn parse(bytes: &[u8], mut chunk_at: impl FnMut(usize) -> Result<Chunk>) -> Result<Png> {
+        // Throw error if bytes array is too small
+        if bytes.len() < Png::STANDARD_HEADER.len() {
+            return Err(PngError::TooSmall.into());
+        }
+
+        // Separate PNG signature from chunks
+        let signature: [u8; 8] = bytes[..8].try_into()?;
+
+        if signature != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature.into());
+        }
+
+        let mut chunks = Vec::new();
+        let mut index = Png::STANDARD_HEADER.len();
+
+        while index < bytes.len() {
+            let chunk = chunk_at(index)?;
+            index += chunk.length() as usize + 12; // 12 are the bytes regarding the chunk's metadata
+            
+            chunks.push(chunk);
+        }
+
+        Ok(Png::from_chunks(chunks))
+    }
+
     /// Get standard PNG header
     pub fn header(&self) -> &[u8; 8] {
         &Png::STANDARD_HEADER
@@ -70,37 +216,34 @@ impl Png {
     }
 }
 
+/// Chunks of the given type holding the data in pieces of at most max_size bytes, with at least one chunk
+fn split_data(chunk_type: &ChunkType, data: &[u8], max_size: usize) -> Vec<Chunk> {
+    if data.is_empty() {
+        return vec![Chunk::new(chunk_type.clone(), Vec::new())];
+    }
+
+    data.chunks(max_size)
+        .map(|part| Chunk::new(chunk_type.clone(), part.to_vec()))
+        .collect()
+}
+
 impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Png> {
-        // Throw error if bytes array is too small
-        if bytes.len() < Png::STANDARD_HEADER.len() {
-            return Err(Box::new(PngError::TooSmall));
-        }
-
-        // Separate PNG signature from chunks
-        let (signature, bytes) = bytes.split_at(8);
-        let signature: [u8; 8] = signature.try_into()?;
-
-        if signature != Png::STANDARD_HEADER {
-            return Err(Box::new(PngError::InvalidSignature));
-        }
-
-        let mut chunks = Vec::new();
-        let mut index = 0;
+        Png::from_bytes(bytes, ChunkTypes::Valid)
+    }
+}
 
-        while index < bytes.len() {
-            let bytes_slice = &bytes[index..];
-            let chunk = Chunk::try_from(bytes_slice)?;
-            index += (chunk.length() + 12) as usize; // 12 are the bytes regarding the chunk's metadata
-            
-            chunks.push(chunk);
-        }
+impl FromIterator<Chunk> for Png {
+    fn from_iter<I: IntoIterator<Item = Chunk>>(iter: I) -> Self {
+        Png::from_chunks(iter.into_iter().collect())
+    }
+}
 
-        Ok(Png {
-            chunks
-        })
+impl Extend<Chunk> for Png {
+    fn extend<I: IntoIterator<Item = Chunk>>(&mut self, iter: I) {
+        self.chunks_mut().extend(iter);
     }
 }
 
@@ -110,11 +253,48 @@ impl Display for Png {
     }
 }
 
+/// Where a new chunk should be inserted in a PNG
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChunkPosition {
+    /// Right after the IHDR chunk
+    AfterIhdr,
+
+    /// Right before the IEND chunk
+    BeforeIend,
+
+    /// At a specific index in the chunk list
+    Index(usize)
+}
+
+impl FromStr for ChunkPosition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "after-ihdr" => Ok(ChunkPosition::AfterIhdr),
+            "before-iend" => Ok(ChunkPosition::BeforeIend),
+            _ => {
+                // Positions such as "index:3" refer to a specific index
+                let index = s
+                    .strip_prefix("index:")
+                    .and_then(|index| index.parse().ok())
+                    .ok_or_else(|| PngError::InvalidPosition(s.to_string()))?;
+
+                Ok(ChunkPosition::Index(index))
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum PngError {
     TooSmall,
     ChunkNotFound,
-    InvalidSignature
+    InvalidSignature,
+    IndexOutOfBounds(usize),
+    IndexBeforeIhdr(usize),
+    InvalidPosition(String),
+    InvalidChunkSize(usize)
 }
 
 impl error::Error for PngError {}
@@ -124,7 +304,22 @@ impl Display for PngError {
         match self {
             PngError::ChunkNotFound => write!(f, "Chunk not found"),
             PngError::InvalidSignature => write!(f, "Invalid PNG signature"),
-            PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file")
+            PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file"),
+            PngError::IndexOutOfBounds(index) => write!(f, "Chunk index {} is out of bounds", index),
+            PngError::IndexBeforeIhdr(index) => {
+                write!(f, "Chunk index {} is at or before the IHDR chunk, which must stay first", index)
+            },
+            PngError::InvalidPosition(position) => write!(
+                f,
+                "Invalid chunk position '{}', expected after-ihdr, before-iend or index:<n>",
+                position
+            ),
+            PngError::InvalidChunkSize(size) => write!(
+                f,
+                "Invalid chunk size {}, expected between 1 and {}",
+                size,
+                Chunk::MAX_LENGTH
+            )
         }
     }
 }
@@ -136,6 +331,7 @@ mod tests {
     use crate::chunk::Chunk;
     use std::convert::TryFrom;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
@@ -166,6 +362,31 @@ mod tests {
         assert_eq!(png.chunks().len(), 3);
     }
 
+    #[test]
+    fn test_new_and_default() {
+        let png = Png::new();
+
+        assert!(png.chunks().is_empty());
+        assert_eq!(png, Png::default());
+        assert_eq!(png.as_bytes(), Png::STANDARD_HEADER.to_vec());
+    }
+
+    #[test]
+    fn test_from_iterator() {
+        let png: Png = testing_chunks().into_iter().collect();
+
+        assert_eq!(png, testing_png());
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut png = Png::new();
+        png.extend(testing_chunks());
+
+        assert_eq!(png, testing_png());
+        assert_eq!(Png::try_from(&png.as_bytes()[..]).unwrap(), png);
+    }
+
     #[test]
     fn test_valid_from_bytes() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -225,6 +446,111 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_truncated_png() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.truncate(bytes.len() - 10);
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_from_arena() {
+        let bytes = testing_png().as_bytes();
+        let png = Png::from_arena(Arc::from(&bytes[..]), ChunkTypes::Valid).unwrap();
+
+        assert_eq!(png, testing_png());
+        assert_eq!(png.as_bytes(), bytes);
+
+        let mut truncated = bytes.clone();
+        truncated.truncate(bytes.len() - 10);
+        assert!(Png::from_arena(Arc::from(&truncated[..]), ChunkTypes::Valid).is_err());
+        assert!(Png::from_arena(Arc::from(&bytes[1..]), ChunkTypes::Valid).is_err());
+    }
+
+    #[test]
+    fn test_clones_share_chunks() {
+        let png = testing_png();
+        let mut clone = png.clone();
+        assert!(Arc::ptr_eq(&png.chunks, &clone.chunks));
+
+        // Modifying a clone copies the chunks, leaving the original as it was
+        clone.append_chunk(chunk_from_strings("ruSt", "added").unwrap());
+        assert!(!Arc::ptr_eq(&png.chunks, &clone.chunks));
+        assert_eq!(png, testing_png());
+        assert_eq!(clone.chunks().len(), png.chunks().len() + 1);
+
+        // Chunks no longer shared are modified in place
+        let chunks = Arc::as_ptr(&clone.chunks);
+        clone.remove_chunk("ruSt").unwrap();
+        assert_eq!(Arc::as_ptr(&clone.chunks), chunks);
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let png = testing_png();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let png = png.clone();
+                std::thread::spawn(move || png.chunk_by_type("FrSt").map(|chunk| chunk.data().to_vec()))
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(b"I am the first chunk".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_coalesce_idat() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "ab").unwrap(),
+            chunk_from_strings("IDAT", "cde").unwrap(),
+            chunk_from_strings("IDAT", "").unwrap(),
+            chunk_from_strings("IDAT", "fgh").unwrap(),
+            chunk_from_strings("tEXt", "Title\0Dice").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        png.coalesce_idat(4).unwrap();
+
+        let chunks: Vec<(String, String)> = png
+            .chunks()
+            .iter()
+            .map(|chunk| (chunk.chunk_type().to_string(), chunk.data_as_string().unwrap()))
+            .collect();
+
+        let expected: Vec<(String, String)> = vec![
+            ("IHDR", "header"),
+            ("IDAT", "abcd"),
+            ("IDAT", "efgh"),
+            ("tEXt", "Title\0Dice"),
+            ("IEND", "")
+        ]
+        .into_iter()
+        .map(|(chunk_type, data)| (chunk_type.to_string(), data.to_string()))
+        .collect();
+
+        assert_eq!(chunks, expected);
+    }
+
+    #[test]
+    fn test_coalesce_empty_idat() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IDAT", "").unwrap(),
+            chunk_from_strings("IDAT", "").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        png.coalesce_idat(8192).unwrap();
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(png.chunks()[0].length(), 0);
+
+        assert!(png.coalesce_idat(0).is_err());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -259,6 +585,86 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_non_ascii_chunk() {
+        let mut chunks = testing_chunks();
+        chunks.push(Chunk::new(ChunkType::try_from([0x89, 0x50, 0x4E, 0x47]).unwrap(), vec![1, 2, 3]));
+        let bytes = Png::from_chunks(chunks).as_bytes();
+
+        // Only parsed when any chunk type is accepted
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+        assert!(Png::from_arena(Arc::from(&bytes[..]), ChunkTypes::Valid).is_err());
+
+        let mut png = Png::from_bytes(&bytes, ChunkTypes::Any).unwrap();
+        assert_eq!(png.chunks()[3].chunk_type().to_string(), "0x89504E47");
+
+        let chunk_type = ChunkType::from_str("0x89504e47").unwrap();
+        png.remove_chunk(&chunk_type.to_string()).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let replaced = png.replace_chunk(chunk_from_strings("miDl", "I replaced a chunk").unwrap()).unwrap();
+
+        assert_eq!(replaced.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(png.chunk_index("miDl"), Some(1));
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "I replaced a chunk");
+        assert_eq!(png.chunks().len(), 3);
+
+        assert!(png.replace_chunk(chunk_from_strings("TeSt", "Message").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        assert_eq!(png.chunk_index("TeSt"), Some(1));
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_insert_chunk_out_of_bounds() {
+        let mut png = testing_png();
+        let result = png.insert_chunk(4, chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_position_index() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let last = png.chunks().len() - 1;
+
+        assert_eq!(png.position_index(&ChunkPosition::AfterIhdr).unwrap(), 1);
+        assert_eq!(png.position_index(&ChunkPosition::BeforeIend).unwrap(), last);
+        assert_eq!(png.position_index(&ChunkPosition::Index(2)).unwrap(), 2);
+        assert!(png.position_index(&ChunkPosition::Index(last + 2)).is_err());
+
+        // Nothing may be inserted before IHDR
+        assert!(matches!(
+            png.position_index(&ChunkPosition::Index(0)),
+            Err(Error::Png(PngError::IndexBeforeIhdr(0)))
+        ));
+        assert_eq!(png.position_index(&ChunkPosition::Index(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_position_index_without_iend() {
+        let png = testing_png();
+        assert_eq!(png.position_index(&ChunkPosition::BeforeIend).unwrap(), 3);
+        assert!(png.position_index(&ChunkPosition::AfterIhdr).is_err());
+    }
+
+    #[test]
+    fn test_chunk_position_from_str() {
+        assert_eq!(ChunkPosition::from_str("after-ihdr").unwrap(), ChunkPosition::AfterIhdr);
+        assert_eq!(ChunkPosition::from_str("before-iend").unwrap(), ChunkPosition::BeforeIend);
+        assert_eq!(ChunkPosition::from_str("index:3").unwrap(), ChunkPosition::Index(3));
+        assert!(ChunkPosition::from_str("index:").is_err());
+        assert!(ChunkPosition::from_str("middle").is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -269,6 +675,7 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
+        #[allow(clippy::iter_cloned_collect)]
         let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
         assert_eq!(actual, expected);
     }
@@ -0,0 +1,303 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::chunk::Chunk;
+use crate::output::Record;
+use crate::locale::Locale;
+use crate::metadata::ModificationTime;
+use crate::png::Png;
+use crate::registry;
+
+/// Number of bytes shown per line of a hex dump
+const HEX_LINE_LENGTH: usize = 16;
+
+/// Number of bytes shown in a truncated hex dump
+pub const HEX_DUMP_LIMIT: usize = 256;
+
+/// Which chunks of a PNG are printed
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ChunkFilter {
+    /// Chunk type patterns where `*` matches any characters and `?` a single one, every chunk matches when empty
+    pub types: Vec<String>,
+
+    /// Only critical chunks when true, only ancillary chunks when false
+    pub critical: Option<bool>
+}
+
+impl ChunkFilter {
+    /// Whether a chunk passes the filter
+    pub fn matches(&self, chunk: &Chunk) -> bool {
+        let chunk_type = chunk.chunk_type().to_string();
+
+        let type_matches = self.types.is_empty()
+            || self.types.iter().any(|pattern| glob_matches(pattern, &chunk_type));
+
+        let criticality_matches = self
+            .critical
+            .is_none_or(|critical| chunk.chunk_type().is_critical() == critical);
+
+        type_matches && criticality_matches
+    }
+}
+
+/// How the data of a chunk is shown
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DataFormat {
+    /// Data is not shown
+    Hidden,
+
+    /// Data as a list of byte values
+    List,
+
+    /// Hex dump with offsets, showing at most the given number of bytes when there is a limit
+    Hex(Option<usize>)
+}
+
+/// A chunk of a PNG along with its position in the file
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ChunkEntry<'a> {
+    /// Index of the chunk in the PNG
+    pub index: usize,
+
+    /// Offset in bytes of the chunk from the start of the file
+    pub offset: usize,
+
+    pub chunk: &'a Chunk
+}
+
+/// Every chunk of a PNG passing the filter, along with its index and file offset
+pub fn entries<'a>(png: &'a Png, filter: &ChunkFilter) -> Vec<ChunkEntry<'a>> {
+    let mut offset = Png::STANDARD_HEADER.len();
+    let mut entries = Vec::new();
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if filter.matches(chunk) {
+            entries.push(ChunkEntry { index, offset, chunk });
+        }
+
+        // Length, chunk type and CRC take 12 bytes besides the data
+        offset += chunk.length() as usize + 12;
+    }
+
+    entries
+}
+
+/// Human readable description of a chunk, followed by its data in the given format, with numbers written as in the
+/// locale
+///
+/// Data listed as bytes is decoded instead when the chunk type is registered with a decoder and the data is well formed
+pub fn format_entry(entry: &ChunkEntry, data_format: DataFormat, locale: Locale) -> String {
+    let chunk_type = entry.chunk.chunk_type();
+
+    let flags = [
+        if chunk_type.is_critical() { "critical" } else { "ancillary" },
+        if chunk_type.is_public() { "public" } else { "private" },
+        if chunk_type.is_safe_to_copy() { "safe to copy" } else { "unsafe to copy" }
+    ];
+
+    let mut output = format!(
+        "[{}] offset: {}, chunk type: {} ({}), length: {}, crc: {}, flags: {}",
+        entry.index,
+        locale.number(entry.offset as u64),
+        chunk_type,
+        registry::describe_any(chunk_type),
+        locale.number(entry.chunk.length().into()),
+        entry.chunk.crc(),
+        flags.join(", ")
+    );
+
+    match data_format {
+        DataFormat::Hidden => {},
+        DataFormat::List => match display_value(entry.chunk, locale) {
+            Some(value) => {
+                let _ = write!(output, "\n  value: {}", value);
+            },
+            None => {
+                let _ = write!(output, "\n  data: {:?}", entry.chunk.data());
+            }
+        },
+        DataFormat::Hex(limit) => {
+            for line in hex_dump(entry.chunk.data(), limit, locale).lines() {
+                let _ = write!(output, "\n  {}", line);
+            }
+        }
+    }
+
+    output
+}
+
+/// Decoded value of a chunk as shown to a person, with the dates of tIME chunks written as in the locale
+fn display_value(chunk: &Chunk, locale: Locale) -> Option<String> {
+    match chunk.chunk_type().to_string().as_str() {
+        "tIME" => ModificationTime::try_from(chunk.data()).ok().map(|time| locale.timestamp(&time)),
+        _ => registry::decode(chunk)
+    }
+}
+
+/// Record of a chunk, whose fields hold its decoded value and its whole data unless it is hidden
+pub fn entry_record(entry: &ChunkEntry, data_format: DataFormat, locale: Locale) -> Record {
+    let chunk_type = entry.chunk.chunk_type();
+
+    let record = Record::new(format_entry(entry, data_format, locale))
+        .field("index", entry.index)
+        .field("offset", entry.offset)
+        .field("chunk_type", chunk_type.to_string())
+        .field("description", registry::describe_any(chunk_type))
+        .field("length", entry.chunk.length())
+        .field("crc", entry.chunk.crc())
+        .field("critical", chunk_type.is_critical())
+        .field("public", chunk_type.is_public())
+        .field("safe_to_copy", chunk_type.is_safe_to_copy());
+
+    match data_format {
+        DataFormat::Hidden => record,
+        _ => {
+            let value = registry::decode(entry.chunk).map_or(serde_json::Value::Null, serde_json::Value::from);
+
+            record.field("value", value).bytes("data", entry.chunk.data())
+        }
+    }
+}
+
+/// Hex dump of some bytes with the offset of every line, showing at most `limit` bytes when given
+pub fn hex_dump(data: &[u8], limit: Option<usize>, locale: Locale) -> String {
+    let shown = &data[..limit.unwrap_or(data.len()).min(data.len())];
+    let mut output = String::new();
+
+    for (line, bytes) in shown.chunks(HEX_LINE_LENGTH).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+
+        let _ = writeln!(
+            output,
+            "{:08x}  {:<width$}  |{}|",
+            line * HEX_LINE_LENGTH,
+            hex.join(" "),
+            ascii,
+            width = HEX_LINE_LENGTH * 3 - 1
+        );
+    }
+
+    if shown.len() < data.len() {
+        let _ = writeln!(output, "... {} more bytes", locale.number((data.len() - shown.len()) as u64));
+    }
+
+    output
+}
+
+/// Whether a chunk type matches a pattern where `*` matches any characters and `?` a single one
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('*', rest)) => (0..=text.len()).any(|skip| matches(rest, &text[skip..])),
+            Some(('?', rest)) => !text.is_empty() && matches(rest, &text[1..]),
+            Some((c, rest)) => text.first() == Some(c) && matches(rest, &text[1..])
+        }
+    }
+
+    matches(&pattern, &text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("zTXt", b"Comment\0\0"),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IEND", &[])
+        ])
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("IDAT", "IDAT"));
+        assert!(glob_matches("*TXt", "zTXt"));
+        assert!(glob_matches("?TXt", "iTXt"));
+        assert!(glob_matches("*", "ruSt"));
+        assert!(!glob_matches("idat", "IDAT"));
+        assert!(!glob_matches("?TXt", "tEXt"));
+    }
+
+    #[test]
+    fn test_entries_offsets() {
+        let png = testing_png();
+        let offsets: Vec<usize> = entries(&png, &ChunkFilter::default())
+            .iter()
+            .map(|entry| entry.offset)
+            .collect();
+
+        assert_eq!(offsets, vec![8, 33, 55, 76, 91]);
+    }
+
+    #[test]
+    fn test_entries_filtered() {
+        let png = testing_png();
+
+        let filter = ChunkFilter { types: vec![String::from("*TXt"), String::from("tEXt")], critical: None };
+        let indices: Vec<usize> = entries(&png, &filter).iter().map(|entry| entry.index).collect();
+        assert_eq!(indices, vec![1, 2]);
+
+        let filter = ChunkFilter { types: vec![], critical: Some(true) };
+        assert_eq!(entries(&png, &filter).len(), 3);
+
+        let filter = ChunkFilter { types: vec![], critical: Some(false) };
+        assert_eq!(entries(&png, &filter).len(), 2);
+    }
+
+    #[test]
+    fn test_format_entry() {
+        let png = testing_png();
+        let entries = entries(&png, &ChunkFilter::default());
+
+        let output = format_entry(&entries[3], DataFormat::Hidden, Locale::C);
+        assert!(output.starts_with("[3] offset: 76, chunk type: IDAT (Image data), length: 3"));
+        assert!(output.ends_with("flags: critical, public, unsafe to copy"));
+
+        let output = format_entry(&entries[3], DataFormat::List, Locale::C);
+        assert!(output.ends_with("\n  data: [1, 2, 3]"));
+
+        // Known chunks are decoded, unless their data is shown as a hex dump
+        let output = format_entry(&entries[1], DataFormat::List, Locale::C);
+        assert!(output.ends_with("\n  value: Title: Dice"));
+
+        let output = format_entry(&entries[1], DataFormat::Hex(None), Locale::C);
+        assert!(output.ends_with("|Title.Dice|"));
+
+        // IEND has no data to show
+        let output = format_entry(&entries[4], DataFormat::Hex(None), Locale::C);
+        assert!(output.ends_with("length: 0, crc: 2923585666, flags: critical, public, unsafe to copy"));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let data: Vec<u8> = (0..20).map(|i| b'a' + i).collect();
+
+        assert_eq!(
+            hex_dump(&data, None, Locale::C),
+            "00000000  61 62 63 64 65 66 67 68 69 6a 6b 6c 6d 6e 6f 70  |abcdefghijklmnop|\n\
+             00000010  71 72 73 74                                      |qrst|\n"
+        );
+
+        assert_eq!(
+            hex_dump(&data, Some(2), Locale::C),
+            "00000000  61 62                                            |ab|\n... 18 more bytes\n"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::image_data::{self, ImageDataError, ImageHeader, Scanline};
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Largest IDAT chunk written by the web-safe profile
+const WEB_SAFE_IDAT_SIZE: usize = 1 << 20;
+
+/// Set of constraints on the PNG files written, for consumers that only accept a subset of PNG
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Profile {
+    /// What the pickiest image hosts and social platforms accept: 8 bit non-interlaced image data, chunks in the
+    /// standard order, IDAT chunks merged up to 1 MiB, and only the ancillary chunks that are safe to copy
+    WebSafe
+}
+
+impl FromStr for Profile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Profile::ALL
+            .iter()
+            .find(|profile| profile.name() == s)
+            .copied()
+            .ok_or_else(|| ProfileError::InvalidProfile(s.to_string()).into())
+    }
+}
+
+impl Profile {
+    pub const ALL: [Profile; 1] = [Profile::WebSafe];
+
+    /// Name given to `--profile`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::WebSafe => "web-safe"
+        }
+    }
+
+    /// Whether chunks of the type are kept by the profile
+    pub fn keeps(&self, chunk_type: &ChunkType) -> bool {
+        match self {
+            Profile::WebSafe => chunk_type.is_critical() || chunk_type.is_safe_to_copy()
+        }
+    }
+
+    /// Copy of the PNG conforming to the profile, unchanged if it already does
+    pub fn apply(&self, png: &Png) -> Result<Png> {
+        match self {
+            Profile::WebSafe => {
+                let mut png = reorder(&to_8_bit_progressive(png)?)?;
+                png.coalesce_idat(WEB_SAFE_IDAT_SIZE)?;
+
+                Ok(png)
+            }
+        }
+    }
+}
+
+/// Copy of the PNG conforming to the profile if there is one, or the PNG as it is without one
+pub fn apply(profile: Option<Profile>, png: &Png) -> Result<Png> {
+    match profile {
+        Some(profile) => profile.apply(png),
+        None => Ok(png.clone())
+    }
+}
+
+/// Fail when the profile would drop chunks of the type, so a message written to one isn't lost
+pub fn check_chunk_type(profile: Option<Profile>, chunk_type: &ChunkType) -> Result<()> {
+    match profile {
+        Some(profile) if !profile.keeps(chunk_type) => {
+            Err(ProfileError::DroppedChunkType(profile, chunk_type.to_string()).into())
+        },
+        _ => Ok(())
+    }
+}
+
+/// Copy of the PNG whose image data is 8 bit and not interlaced, scaling samples of other bit depths
+fn to_8_bit_progressive(png: &Png) -> Result<Png> {
+    let header = ImageHeader::from_png(png)?;
+
+    if header.bit_depth == 8 && header.interlace_method == 0 {
+        return Ok(png.clone());
+    }
+
+    let target = ImageHeader { bit_depth: 8, interlace_method: 0, ..header };
+    let channels = header.channels() as usize;
+    let scanlines = image_data::unfilter(&header, &image_data::inflate(png)?)?;
+
+    // Samples widened to 8 bits may not fit where the original ones did
+    target.data_length()?;
+    let mut rows = vec![vec![0; target.row_bytes(header.width)]; header.height as usize];
+    let mut scanlines = scanlines.iter();
+
+    // Every pixel of every pass goes back to its place in the full image
+    for pass in header.pass_layout() {
+        for row in 0..pass.height {
+            let scanline = scanlines.next().ok_or(ImageDataError::TruncatedData)?;
+            let y = (pass.y + row * pass.y_step) as usize;
+
+            for column in 0..pass.width as usize {
+                let x = pass.x as usize + column * pass.x_step as usize;
+
+                for channel in 0..channels {
+                    let value = header.sample(&scanline.data, column * channels + channel);
+
+                    // Palette indices are kept as they are, only samples are scaled
+                    rows[y][x * channels + channel] = match header.color_type {
+                        3 => value as u8,
+                        _ => header.sample_to_u8(value)
+                    };
+                }
+            }
+        }
+    }
+
+    let scanlines: Vec<Scanline> = rows.into_iter().map(|data| Scanline { filter: 0, data }).collect();
+    let mut converted = image_data::replace_image_data(png, &image_data::filter(&target, &scanlines)?)?;
+    converted.replace_chunk(Chunk::new(ChunkType::try_from(*b"IHDR")?, target.as_bytes()))?;
+
+    Ok(converted)
+}
+
+/// Copy of the PNG with its chunks in the standard order, without the ancillary chunks that aren't safe to copy
+///
+/// Chunks that aren't safe to copy, such as tRNS or gAMA, depend on the image data, so they are dropped along
+/// with the animation chunks of an APNG. Ancillary chunks go between PLTE and the image data, where every chunk kept
+/// is allowed, and chunks after IEND are moved before it
+fn reorder(png: &Png) -> Result<Png> {
+    let is = |chunk: &Chunk, chunk_type: &str| chunk.chunk_type().to_string() == chunk_type;
+    let chunks = png.chunks();
+
+    let ancillary = chunks.iter().filter(|chunk| {
+        !["IHDR", "PLTE", "IDAT", "IEND"].iter().any(|chunk_type| is(chunk, chunk_type))
+            && Profile::WebSafe.keeps(chunk.chunk_type())
+    });
+
+    let ordered: Vec<Chunk> = chunks
+        .iter()
+        .filter(|chunk| is(chunk, "IHDR") || is(chunk, "PLTE"))
+        .chain(ancillary)
+        .chain(chunks.iter().filter(|chunk| is(chunk, "IDAT")))
+        .cloned()
+        .chain(std::iter::once(Chunk::new(ChunkType::try_from(*b"IEND")?, Vec::new())))
+        .collect();
+
+    Ok(Png::from_chunks(ordered))
+}
+
+/// Profile errors
+#[derive(Debug)]
+pub enum ProfileError {
+    /// Profile isn't web-safe
+    InvalidProfile(String),
+
+    /// Profile drops chunks of this type, so a message written to one would be lost
+    DroppedChunkType(Profile, String)
+}
+
+impl error::Error for ProfileError {}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::InvalidProfile(profile) => write!(f, "Invalid profile '{}', expected web-safe", profile),
+            ProfileError::DroppedChunkType(profile, chunk_type) => write!(
+                f,
+                "The {} profile drops {} chunks as they aren't safe to copy, use a chunk type ending in a lowercase letter",
+                profile.name(),
+                chunk_type
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::tests::testing_png;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_web_safe_image_data() {
+        let progressive = testing_png(9, 7, 2, 8, 0);
+
+        for (bit_depth, interlace_method) in [(16, 0), (16, 1), (8, 1)] {
+            let png = testing_png(9, 7, 2, bit_depth, interlace_method);
+            let web_safe = Profile::WebSafe.apply(&png).unwrap();
+            let header = ImageHeader::from_png(&web_safe).unwrap();
+
+            assert_eq!((header.bit_depth, header.interlace_method), (8, 0));
+            assert_eq!(image_data::rgba_pixels(&web_safe).unwrap(), image_data::rgba_pixels(&png).unwrap());
+        }
+
+        // A file that already conforms keeps its image data
+        let web_safe = Profile::WebSafe.apply(&progressive).unwrap();
+        assert_eq!(image_data::inflate(&web_safe).unwrap(), image_data::inflate(&progressive).unwrap());
+    }
+
+    #[test]
+    fn test_web_safe_low_bit_depth_palette() {
+        let mut png = testing_png(5, 3, 3, 2, 0);
+        png.insert_chunk(1, chunk("PLTE", &[0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255])).unwrap();
+        let web_safe = Profile::WebSafe.apply(&png).unwrap();
+
+        assert_eq!(ImageHeader::from_png(&web_safe).unwrap().bit_depth, 8);
+        assert_eq!(image_data::rgba_pixels(&web_safe).unwrap(), image_data::rgba_pixels(&png).unwrap());
+    }
+
+    #[test]
+    fn test_web_safe_chunks() {
+        let mut png = testing_png(4, 4, 2, 8, 0);
+        png.insert_chunk(1, chunk("gAMA", &[0, 0, 177, 143])).unwrap();
+        png.insert_chunk(1, chunk("pHYs", &[0, 0, 0x0B, 0x13, 0, 0, 0x0B, 0x13, 1])).unwrap();
+        png.append_chunk(chunk("tEXt", b"After\0IEND"));
+        png.insert_chunk(png.chunk_index("IDAT").unwrap() + 1, chunk("ruSt", b"secret")).unwrap();
+
+        let web_safe = Profile::WebSafe.apply(&png).unwrap();
+        assert_eq!(chunk_types(&web_safe), ["IHDR", "pHYs", "ruSt", "tEXt", "IDAT", "IEND"]);
+
+        // Applying the profile again changes nothing
+        assert_eq!(Profile::WebSafe.apply(&web_safe).unwrap(), web_safe);
+    }
+
+    #[test]
+    fn test_web_safe_keeps() {
+        for (chunk_type, kept) in [("ruSt", true), ("ruST", false), ("RUST", true), ("tIME", false)] {
+            assert_eq!(Profile::WebSafe.keeps(&ChunkType::from_str(chunk_type).unwrap()), kept);
+        }
+    }
+
+    #[test]
+    fn test_profile_from_str() {
+        assert_eq!(Profile::from_str("web-safe").unwrap(), Profile::WebSafe);
+        assert!(Profile::from_str("web").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::{Digest, Sha256};
+use std::convert::{TryFrom, TryInto};
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{Png, PngError};
+use crate::Result;
+
+/// Chunk type wrapping an encrypted chunk - ancillary, private and safe to copy
+pub const PROTECTED_CHUNK_TYPE: &str = "prOt";
+
+/// Version of the protected chunk layout, stored as its first byte
+const VERSION: u8 = 1;
+
+/// Length of the random salt the key is derived with
+const SALT_LENGTH: usize = 16;
+
+/// Length of the AES-GCM nonce
+const NONCE_LENGTH: usize = 12;
+
+/// Number of PBKDF2 rounds deriving the key from the password
+const ROUNDS: u32 = 100_000;
+
+/// Encrypt every chunk of the given types, wrapping each one in a protected chunk at the same position
+///
+/// Returns the protected PNG along with the number of chunks that were encrypted. A deterministic run derives the salt
+/// and nonce from the password and the chunk instead of drawing them at random, so the same chunks always encrypt the
+/// same way
+pub fn protect(png: &Png, chunk_types: &[ChunkType], password: &str, deterministic: bool) -> Result<(Png, usize)> {
+    // Decoders can't skip encrypted critical chunks, so the image would become unreadable
+    if let Some(chunk_type) = chunk_types
+        .iter()
+        .find(|chunk_type| chunk_type.is_critical() || chunk_type.to_string() == PROTECTED_CHUNK_TYPE)
+    {
+        return Err(ProtectError::UnprotectableChunk(chunk_type.to_string()).into());
+    }
+
+    let mut count = 0;
+    let mut chunks = Vec::new();
+
+    for chunk in png.chunks() {
+        if chunk_types.contains(chunk.chunk_type()) {
+            chunks.push(seal(chunk, password, deterministic)?);
+            count += 1;
+        } else {
+            chunks.push(chunk.clone());
+        }
+    }
+
+    if count == 0 {
+        return Err(PngError::ChunkNotFound.into());
+    }
+
+    Ok((Png::from_chunks(chunks), count))
+}
+
+/// Decrypt every protected chunk, restoring the original chunk at the same position
+///
+/// Returns the restored PNG along with the number of chunks that were decrypted
+pub fn unprotect(png: &Png, password: &str) -> Result<(Png, usize)> {
+    let mut count = 0;
+    let mut chunks = Vec::new();
+
+    for chunk in png.chunks() {
+        if chunk.chunk_type().to_string() == PROTECTED_CHUNK_TYPE {
+            chunks.push(open(chunk, password)?);
+            count += 1;
+        } else {
+            chunks.push(chunk.clone());
+        }
+    }
+
+    if count == 0 {
+        return Err(PngError::ChunkNotFound.into());
+    }
+
+    Ok((Png::from_chunks(chunks), count))
+}
+
+/// Encrypt a chunk's type and data into a protected chunk
+///
+/// The protected chunk's data is the version, salt, nonce and then the ciphertext with its tag
+fn seal(chunk: &Chunk, password: &str, deterministic: bool) -> Result<Chunk> {
+    // The original chunk type is encrypted along with the data so it isn't revealed either
+    let plaintext: Vec<u8> = chunk
+        .chunk_type()
+        .bytes()
+        .iter()
+        .chain(chunk.data().iter())
+        .copied()
+        .collect();
+
+    let mut salt = [0; SALT_LENGTH];
+    let mut nonce = Nonce::default();
+
+    // A nonce is only ever reused along with the salt, and so the key, for the very same chunk
+    if deterministic {
+        let digest = Sha256::new().chain_update(password).chain_update(&plaintext).finalize();
+        salt.copy_from_slice(&digest[..SALT_LENGTH]);
+        nonce.copy_from_slice(&digest[SALT_LENGTH..SALT_LENGTH + NONCE_LENGTH]);
+    } else {
+        OsRng.fill_bytes(&mut salt);
+        nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    }
+
+    let cipher = Aes256Gcm::new(&derive_key(password, &salt));
+
+    let ciphertext = cipher
+        .encrypt(&nonce, &plaintext[..])
+        .map_err(|_| ProtectError::EncryptionFailed)?;
+
+    let data: Vec<u8> = [VERSION]
+        .iter()
+        .chain(salt.iter())
+        .chain(nonce.iter())
+        .chain(ciphertext.iter())
+        .copied()
+        .collect();
+
+    Ok(Chunk::new(ChunkType::from_str(PROTECTED_CHUNK_TYPE)?, data))
+}
+
+/// Decrypt a protected chunk back into the original chunk
+fn open(chunk: &Chunk, password: &str) -> Result<Chunk> {
+    let data = chunk.data();
+
+    if data.len() < 1 + SALT_LENGTH + NONCE_LENGTH || data[0] != VERSION {
+        return Err(ProtectError::InvalidProtectedChunk.into());
+    }
+
+    let (salt, rest) = data[1..].split_at(SALT_LENGTH);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+
+    let cipher = Aes256Gcm::new(&derive_key(password, salt));
+
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| ProtectError::DecryptionFailed)?;
+
+    if plaintext.len() < 4 {
+        return Err(ProtectError::InvalidProtectedChunk.into());
+    }
+
+    let (chunk_type, data) = plaintext.split_at(4);
+    let chunk_type: [u8; 4] = chunk_type.try_into()?;
+
+    Ok(Chunk::new(ChunkType::try_from(chunk_type)?, data.to_vec()))
+}
+
+/// Derive an AES-256 key from a password and salt
+fn derive_key(password: &str, salt: &[u8]) -> Key<Aes256Gcm> {
+    let mut key = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, ROUNDS, &mut key);
+
+    key.into()
+}
+
+/// Chunk protection errors
+#[derive(Debug)]
+pub enum ProtectError {
+    /// Chunk type is critical or already protected
+    UnprotectableChunk(String),
+
+    /// The chunk could not be encrypted
+    EncryptionFailed,
+
+    /// Wrong password, or the protected chunk was tampered with
+    DecryptionFailed,
+
+    /// Protected chunk data is too short or has an unknown version
+    InvalidProtectedChunk
+}
+
+impl error::Error for ProtectError {}
+
+impl Display for ProtectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtectError::UnprotectableChunk(chunk_type) => write!(
+                f,
+                "Chunk type {} can't be protected since it is critical or already protected",
+                chunk_type
+            ),
+            ProtectError::EncryptionFailed => write!(f, "Chunk could not be encrypted"),
+            ProtectError::DecryptionFailed => write!(
+                f,
+                "Chunk could not be decrypted, the password is wrong or the chunk was tampered with"
+            ),
+            ProtectError::InvalidProtectedChunk => write!(f, "Invalid protected chunk")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PngmeError;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("tEXt", b"Author\0Jane Doe"),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("tEXt", b"Location\0Home"),
+            chunk("IEND", &[])
+        ])
+    }
+
+    fn types(chunk_types: &[&str]) -> Vec<ChunkType> {
+        chunk_types.iter().map(|chunk_type| ChunkType::from_str(chunk_type).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_protect_and_unprotect() {
+        let png = testing_png();
+
+        let (protected, count) = protect(&png, &types(&["tEXt"]), "secret", false).unwrap();
+        assert_eq!(count, 2);
+
+        let chunk_types: Vec<String> = protected
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(chunk_types, vec!["IHDR", "prOt", "IDAT", "prOt", "IEND"]);
+
+        let (restored, count) = unprotect(&protected, "secret").unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(restored, png);
+    }
+
+    #[test]
+    fn test_deterministic_protect() {
+        let png = testing_png();
+
+        let (first, _) = protect(&png, &types(&["tEXt"]), "secret", true).unwrap();
+        let (second, _) = protect(&png, &types(&["tEXt"]), "secret", true).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(unprotect(&first, "secret").unwrap().0, png);
+
+        // Chunks holding other data get another salt and nonce
+        assert_ne!(first.chunks()[1].data()[1..29], first.chunks()[3].data()[1..29]);
+
+        let (random, _) = protect(&png, &types(&["tEXt"]), "secret", false).unwrap();
+        assert_ne!(first, random);
+    }
+
+    #[test]
+    fn test_unprotect_wrong_password() {
+        let (protected, _) = protect(&testing_png(), &types(&["tEXt"]), "secret", false).unwrap();
+
+        assert!(matches!(
+            unprotect(&protected, "guess"),
+            Err(PngmeError::Protect(ProtectError::DecryptionFailed))
+        ));
+    }
+
+    #[test]
+    fn test_protect_refused() {
+        assert!(matches!(
+            protect(&testing_png(), &types(&["IDAT"]), "secret", false),
+            Err(PngmeError::Protect(ProtectError::UnprotectableChunk(_)))
+        ));
+
+        assert!(matches!(
+            protect(&testing_png(), &types(&["eXIf"]), "secret", false),
+            Err(PngmeError::Png(PngError::ChunkNotFound))
+        ));
+
+        assert!(unprotect(&testing_png(), "secret").is_err());
+    }
+}
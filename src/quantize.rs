@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::image_data::{self, ImageHeader, Scanline};
+use crate::png::Png;
+use crate::Result;
+
+/// Ancillary chunks whose contents depend on the color type and become invalid once it changes
+const COLOR_TYPE_DEPENDENT_CHUNKS: [&str; 5] = ["PLTE", "tRNS", "bKGD", "sBIT", "hIST"];
+
+/// Converts a truecolor PNG to a palette based one holding at most the given number of colors
+pub fn quantize(png: &Png, colors: usize, dither: bool) -> Result<Png> {
+    if !(2..=256).contains(&colors) {
+        return Err(QuantizeError::InvalidColorCount(colors).into());
+    }
+
+    let header = ImageHeader::from_png(png)?;
+
+    if header.color_type != 2 && header.color_type != 6 {
+        return Err(QuantizeError::NotTruecolor.into());
+    }
+
+    let pixels = image_data::rgba_pixels(png)?;
+
+    // Transparent colors go first so the tRNS chunk stays as short as possible
+    let mut palette = median_cut(&pixels, colors);
+    palette.sort_by_key(|color| color[3]);
+
+    let indices = map_pixels(&pixels, header.width as usize, &palette, dither);
+
+    let bit_depth = match palette.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8
+    };
+
+    let indexed_header = ImageHeader {
+        bit_depth,
+        color_type: 3,
+        interlace_method: 0,
+        ..header
+    };
+
+    // Palette images are best left unfiltered
+    let scanlines: Vec<Scanline> = indices
+        .chunks(header.width as usize)
+        .map(|row| Scanline {
+            filter: 0,
+            data: pack_row(row, bit_depth)
+        })
+        .collect();
+
+    let plte: Vec<u8> = palette.iter().flat_map(|color| color[..3].to_vec()).collect();
+    let trns: Vec<u8> = palette
+        .iter()
+        .map(|color| color[3])
+        .take_while(|alpha| *alpha < 255)
+        .collect();
+
+    // Palette and transparency come right before the image data
+    let mut chunks = Vec::new();
+    let mut palette_written = false;
+
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type().to_string();
+
+        if chunk_type == "IHDR" {
+            chunks.push(Chunk::new(ChunkType::from_str("IHDR")?, indexed_header.as_bytes()));
+            continue;
+        }
+
+        if COLOR_TYPE_DEPENDENT_CHUNKS.contains(&chunk_type.as_str()) {
+            continue;
+        }
+
+        if chunk_type == "IDAT" && !palette_written {
+            chunks.push(Chunk::new(ChunkType::from_str("PLTE")?, plte.clone()));
+
+            if !trns.is_empty() {
+                chunks.push(Chunk::new(ChunkType::from_str("tRNS")?, trns.clone()));
+            }
+            palette_written = true;
+        }
+
+        chunks.push(chunk.clone());
+    }
+
+    let data = image_data::filter(&indexed_header, &scanlines)?;
+    image_data::replace_image_data(&Png::from_chunks(chunks), &data)
+}
+
+/// Builds a palette by repeatedly splitting the box of colors with the widest channel at its median
+fn median_cut(pixels: &[[u8; 4]], colors: usize) -> Vec<[u8; 4]> {
+    let mut histogram: HashMap<[u8; 4], usize> = HashMap::new();
+
+    for pixel in pixels {
+        *histogram.entry(*pixel).or_insert(0) += 1;
+    }
+
+    // Sorting keeps the palette deterministic
+    let mut histogram: Vec<([u8; 4], usize)> = histogram.into_iter().collect();
+    histogram.sort_unstable();
+
+    let mut boxes = vec![histogram];
+
+    while boxes.len() < colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (index, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+
+        let (index, channel) = match widest {
+            Some((index, channel, _)) => (index, channel),
+            None => break
+        };
+
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_by_key(|(color, _)| color[channel]);
+
+        // Split where half of the box's pixels are on each side
+        let total: usize = lower.iter().map(|(_, count)| count).sum();
+        let mut accumulated = 0;
+        let split = lower
+            .iter()
+            .position(|(_, count)| {
+                accumulated += count;
+                accumulated * 2 >= total
+            })
+            .map(|position| position + 1)
+            .unwrap_or(1)
+            .clamp(1, lower.len() - 1);
+
+        let upper = lower.split_off(split);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| average(colors)).collect()
+}
+
+/// Channel with the widest range of values in a box of colors, and its range
+fn widest_channel(colors: &[([u8; 4], usize)]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let min = colors.iter().map(|(color, _)| color[channel]).min().unwrap_or(0);
+            let max = colors.iter().map(|(color, _)| color[channel]).max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+/// Average color of a box of colors, weighted by their pixel count
+fn average(colors: &[([u8; 4], usize)]) -> [u8; 4] {
+    let total: usize = colors.iter().map(|(_, count)| count).sum();
+    let mut average = [0; 4];
+
+    for (channel, value) in average.iter_mut().enumerate() {
+        let sum: usize = colors
+            .iter()
+            .map(|(color, count)| color[channel] as usize * count)
+            .sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+
+    average
+}
+
+/// Palette index of every pixel, optionally spreading the error with Floyd-Steinberg dithering
+fn map_pixels(pixels: &[[u8; 4]], width: usize, palette: &[[u8; 4]], dither: bool) -> Vec<u8> {
+    let mut indices = Vec::with_capacity(pixels.len());
+    let mut cache: HashMap<[i32; 4], u8> = HashMap::new();
+
+    // Errors are stored 16 times larger, with a column of padding on both sides
+    let mut current = vec![[0i32; 4]; width + 2];
+    let mut next = vec![[0i32; 4]; width + 2];
+
+    for row in pixels.chunks(width) {
+        for (x, pixel) in row.iter().enumerate() {
+            let mut target = [0i32; 4];
+
+            for channel in 0..4 {
+                target[channel] = (pixel[channel] as i32 + current[x + 1][channel] / 16).clamp(0, 255);
+            }
+
+            let index = *cache
+                .entry(target)
+                .or_insert_with(|| nearest(palette, &target));
+            indices.push(index);
+
+            if dither {
+                let color = palette[index as usize];
+
+                for channel in 0..4 {
+                    let error = target[channel] - color[channel] as i32;
+                    current[x + 2][channel] += error * 7;
+                    next[x][channel] += error * 3;
+                    next[x + 1][channel] += error * 5;
+                    next[x + 2][channel] += error;
+                }
+            }
+        }
+
+        current = next;
+        next = vec![[0i32; 4]; width + 2];
+    }
+
+    indices
+}
+
+/// Index of the palette color closest to the target color
+fn nearest(palette: &[[u8; 4]], target: &[i32; 4]) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, color)| {
+            (0..4)
+                .map(|channel| (color[channel] as i32 - target[channel]).pow(2))
+                .sum::<i32>()
+        })
+        .map(|(index, _)| index as u8)
+        .unwrap_or(0)
+}
+
+/// Packs palette indices into a scanline of the given bit depth
+fn pack_row(indices: &[u8], bit_depth: u8) -> Vec<u8> {
+    let depth = bit_depth as usize;
+    let mut row = vec![0; (indices.len() * depth).div_ceil(8)];
+
+    for (i, index) in indices.iter().enumerate() {
+        let bit = i * depth;
+        row[bit / 8] |= index << (8 - depth - bit % 8);
+    }
+
+    row
+}
+
+/// Quantization errors
+#[derive(Debug)]
+pub enum QuantizeError {
+    /// Number of colors must be between 2 and 256
+    InvalidColorCount(usize),
+
+    /// Only truecolor images can be quantized
+    NotTruecolor
+}
+
+impl error::Error for QuantizeError {}
+
+impl Display for QuantizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuantizeError::InvalidColorCount(colors) => write!(
+                f,
+                "Expected between 2 and 256 colors but received {}",
+                colors
+            ),
+            QuantizeError::NotTruecolor => write!(f, "Only truecolor images can be quantized")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::tests::testing_png;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_median_cut_keeps_few_colors() {
+        let pixels = vec![[255, 0, 0, 255], [0, 255, 0, 255], [255, 0, 0, 255]];
+        let mut palette = median_cut(&pixels, 256);
+        palette.sort_unstable();
+
+        assert_eq!(palette, vec![[0, 255, 0, 255], [255, 0, 0, 255]]);
+    }
+
+    #[test]
+    fn test_median_cut_limits_colors() {
+        let pixels: Vec<[u8; 4]> = (0..=255).map(|value| [value, value / 2, 255 - value, 255]).collect();
+        let palette = median_cut(&pixels, 16);
+
+        assert_eq!(palette.len(), 16);
+    }
+
+    #[test]
+    fn test_pack_row() {
+        assert_eq!(pack_row(&[1, 0, 1, 1, 0, 0, 0, 0, 1], 1), vec![0b1011_0000, 0b1000_0000]);
+        assert_eq!(pack_row(&[3, 1, 2], 2), vec![0b1101_1000]);
+        assert_eq!(pack_row(&[15, 1], 4), vec![0xF1]);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let png = testing_png(20, 20, 6, 8, 1);
+        let quantized = quantize(&png, 16, false).unwrap();
+
+        let header = ImageHeader::from_png(&quantized).unwrap();
+        assert_eq!(header.color_type, 3);
+        assert_eq!(header.bit_depth, 4);
+        assert_eq!(header.interlace_method, 0);
+        assert!(quantized.chunk_by_type("PLTE").unwrap().data().len() <= 16 * 3);
+
+        let colors: HashSet<[u8; 4]> = image_data::rgba_pixels(&quantized).unwrap().into_iter().collect();
+        assert!(colors.len() <= 16);
+    }
+
+    #[test]
+    fn test_quantize_with_dithering() {
+        let png = testing_png(20, 20, 2, 8, 0);
+        let quantized = quantize(&png, 4, true).unwrap();
+
+        assert_eq!(image_data::rgba_pixels(&quantized).unwrap().len(), 400);
+        assert!(quantized.chunk_by_type("tRNS").is_none());
+    }
+
+    #[test]
+    fn test_quantize_chunk_order() {
+        let png = testing_png(20, 20, 6, 8, 0);
+        let quantized = quantize(&png, 256, false).unwrap();
+
+        let chunk_types: Vec<String> = quantized
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(chunk_types[..2], ["IHDR", "PLTE"]);
+        assert_eq!(chunk_types.last().unwrap(), "IEND");
+    }
+
+    #[test]
+    fn test_quantize_invalid_input() {
+        assert!(quantize(&testing_png(20, 20, 2, 8, 0), 1, false).is_err());
+        assert!(quantize(&testing_png(20, 20, 2, 8, 0), 257, false).is_err());
+        assert!(quantize(&testing_png(20, 20, 0, 8, 0), 16, false).is_err());
+    }
+}
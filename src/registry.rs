@@ -0,0 +1,284 @@
+use std::convert::{TryFrom, TryInto};
+use std::error;
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::image_data::ImageHeader;
+use crate::metadata::{ModificationTime, TextEntry};
+use crate::Result;
+
+/// A registered chunk type, with a short description and a decoder of its data when it has a simple textual form
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkHandler {
+    pub chunk_type: &'static str,
+    pub description: &'static str,
+
+    /// Human readable value of the chunk's data, `None` when the data is malformed
+    pub decode: Option<fn(&Chunk) -> Option<String>>
+}
+
+/// Chunk types defined by the PNG specification, its extensions and APNG
+///
+/// See [PNG Chunks](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html) and
+/// [APNG Specification](https://wiki.mozilla.org/APNG_Specification) for details
+pub const KNOWN_CHUNKS: [ChunkHandler; 33] = [
+    ChunkHandler { chunk_type: "IHDR", description: "Image header", decode: Some(decode_header) },
+    ChunkHandler { chunk_type: "PLTE", description: "Palette", decode: Some(decode_palette) },
+    ChunkHandler { chunk_type: "IDAT", description: "Image data", decode: None },
+    ChunkHandler { chunk_type: "IEND", description: "Image trailer", decode: None },
+    ChunkHandler { chunk_type: "tRNS", description: "Transparency", decode: None },
+    ChunkHandler { chunk_type: "cHRM", description: "Primary chromaticities and white point", decode: None },
+    ChunkHandler { chunk_type: "gAMA", description: "Image gamma", decode: Some(decode_gamma) },
+    ChunkHandler { chunk_type: "iCCP", description: "Embedded ICC profile", decode: None },
+    ChunkHandler { chunk_type: "sBIT", description: "Significant bits", decode: None },
+    ChunkHandler { chunk_type: "sRGB", description: "Standard RGB color space", decode: Some(decode_rendering_intent) },
+    ChunkHandler { chunk_type: "cICP", description: "Coding-independent code points", decode: None },
+    ChunkHandler { chunk_type: "mDCV", description: "Mastering display color volume", decode: None },
+    ChunkHandler { chunk_type: "cLLI", description: "Content light level information", decode: None },
+    ChunkHandler { chunk_type: "tEXt", description: "Textual data", decode: Some(decode_text) },
+    ChunkHandler { chunk_type: "zTXt", description: "Compressed textual data", decode: Some(decode_text) },
+    ChunkHandler { chunk_type: "iTXt", description: "International textual data", decode: Some(decode_text) },
+    ChunkHandler { chunk_type: "bKGD", description: "Background color", decode: None },
+    ChunkHandler { chunk_type: "hIST", description: "Image histogram", decode: None },
+    ChunkHandler { chunk_type: "pHYs", description: "Physical pixel dimensions", decode: Some(decode_physical_dimensions) },
+    ChunkHandler { chunk_type: "sPLT", description: "Suggested palette", decode: None },
+    ChunkHandler { chunk_type: "eXIf", description: "Exif metadata", decode: None },
+    ChunkHandler { chunk_type: "tIME", description: "Last modification time", decode: Some(decode_time) },
+    ChunkHandler { chunk_type: "acTL", description: "Animation control", decode: Some(decode_animation_control) },
+    ChunkHandler { chunk_type: "fcTL", description: "Frame control", decode: None },
+    ChunkHandler { chunk_type: "fdAT", description: "Frame data", decode: None },
+    ChunkHandler { chunk_type: "oFFs", description: "Image offset", decode: None },
+    ChunkHandler { chunk_type: "pCAL", description: "Pixel calibration", decode: None },
+    ChunkHandler { chunk_type: "sCAL", description: "Physical scale", decode: None },
+    ChunkHandler { chunk_type: "gIFg", description: "GIF graphic control extension", decode: None },
+    ChunkHandler { chunk_type: "gIFx", description: "GIF application extension", decode: None },
+    ChunkHandler { chunk_type: "sTER", description: "Stereo image indicator", decode: None },
+    ChunkHandler { chunk_type: "dSIG", description: "Digital signature", decode: None },
+    ChunkHandler { chunk_type: "fRAc", description: "Fractal image parameters", decode: None }
+];
+
+/// Handler of a registered chunk type
+pub fn handler(chunk_type: &str) -> Option<&'static ChunkHandler> {
+    KNOWN_CHUNKS.iter().find(|handler| handler.chunk_type == chunk_type)
+}
+
+/// Description of a registered chunk type
+pub fn describe(chunk_type: &str) -> Option<&'static str> {
+    handler(chunk_type).map(|handler| handler.description)
+}
+
+/// Human readable value of a chunk's data, when its type has a decoder and the data is well formed
+pub fn decode(chunk: &Chunk) -> Option<String> {
+    handler(&chunk.chunk_type().to_string())
+        .and_then(|handler| handler.decode)
+        .and_then(|decode| decode(chunk))
+}
+
+/// Description of any chunk type, falling back to what its name tells about it
+pub fn describe_any(chunk_type: &ChunkType) -> String {
+    match describe(&chunk_type.to_string()) {
+        Some(description) => description.to_string(),
+        None if chunk_type.is_critical() => String::from("Unknown critical chunk"),
+        None if chunk_type.is_public() => String::from("Unknown public chunk"),
+        None => String::from("Private chunk")
+    }
+}
+
+/// Make sure a message can be written in a chunk of the given type without corrupting the image
+///
+/// Decoders reject images with unknown critical chunks, and registered chunks are interpreted by them
+pub fn check_writable(chunk_type: &ChunkType) -> Result<()> {
+    let name = chunk_type.to_string();
+
+    if chunk_type.is_critical() || describe(&name).is_some() {
+        return Err(RegistryError::ProtectedChunkType(name).into());
+    }
+
+    Ok(())
+}
+
+/// Size, bit depth, color type and interlacing of an IHDR chunk, such as "640x480, 8 bit truecolor with alpha"
+fn decode_header(chunk: &Chunk) -> Option<String> {
+    let header = ImageHeader::try_from(chunk.data()).ok()?;
+
+    let color_type = match header.color_type {
+        0 => "grayscale",
+        2 => "truecolor",
+        3 => "indexed color",
+        4 => "grayscale with alpha",
+        _ => "truecolor with alpha"
+    };
+
+    let interlacing = match header.interlace_method {
+        0 => "",
+        _ => ", interlaced"
+    };
+
+    Some(format!(
+        "{}x{}, {} bit {}{}",
+        header.width, header.height, header.bit_depth, color_type, interlacing
+    ))
+}
+
+/// Number of entries of a PLTE chunk
+fn decode_palette(chunk: &Chunk) -> Option<String> {
+    let length = chunk.data().len();
+
+    // Every entry takes 3 bytes, for red, green and blue
+    match (length % 3, length / 3) {
+        (0, 1..=256) => Some(format!("{} colors", length / 3)),
+        _ => None
+    }
+}
+
+/// Rendering intent of an sRGB chunk
+fn decode_rendering_intent(chunk: &Chunk) -> Option<String> {
+    let intent = match chunk.data() {
+        [0] => "perceptual",
+        [1] => "relative colorimetric",
+        [2] => "saturation",
+        [3] => "absolute colorimetric",
+        _ => return None
+    };
+
+    Some(format!("{} rendering intent", intent))
+}
+
+/// File gamma of a gAMA chunk, which stores it times 100000
+fn decode_gamma(chunk: &Chunk) -> Option<String> {
+    let gamma: [u8; 4] = chunk.data().try_into().ok()?;
+
+    Some(format!("gamma {}", u32::from_be_bytes(gamma) as f64 / 100_000.0))
+}
+
+/// Keyword and text of a tEXt, zTXt or iTXt chunk
+fn decode_text(chunk: &Chunk) -> Option<String> {
+    let entry = TextEntry::try_from(chunk).ok()?;
+
+    Some(format!("{}: {}", entry.keyword, entry.text))
+}
+
+/// Resolution of a pHYs chunk, in dots per inch when the unit is the metre and as an aspect ratio otherwise
+fn decode_physical_dimensions(chunk: &Chunk) -> Option<String> {
+    let data = chunk.data();
+
+    if data.len() != 9 {
+        return None;
+    }
+
+    let x = u32::from_be_bytes(data[0..4].try_into().ok()?);
+    let y = u32::from_be_bytes(data[4..8].try_into().ok()?);
+
+    match data[8] {
+        0 => Some(format!("{}:{} pixel aspect ratio", x, y)),
+        1 => {
+            // A metre is 39.37 inches
+            let dpi = |pixels_per_metre: u32| (pixels_per_metre as f64 * 0.0254).round();
+
+            Some(match x == y {
+                true => format!("{} pixels per metre ({} dpi)", x, dpi(x)),
+                false => format!("{}x{} pixels per metre ({}x{} dpi)", x, y, dpi(x), dpi(y))
+            })
+        },
+        _ => None
+    }
+}
+
+/// Date and time of a tIME chunk
+fn decode_time(chunk: &Chunk) -> Option<String> {
+    ModificationTime::try_from(chunk.data()).ok().map(|time| time.to_string())
+}
+
+/// Number of frames and plays of an acTL chunk, where 0 plays means the animation loops forever
+fn decode_animation_control(chunk: &Chunk) -> Option<String> {
+    let data = chunk.data();
+
+    if data.len() != 8 {
+        return None;
+    }
+
+    let frames = u32::from_be_bytes(data[0..4].try_into().ok()?);
+
+    match u32::from_be_bytes(data[4..8].try_into().ok()?) {
+        0 => Some(format!("{} frames, looping forever", frames)),
+        plays => Some(format!("{} frames, played {} times", frames, plays))
+    }
+}
+
+/// Chunk registry errors
+#[derive(Debug)]
+pub enum RegistryError {
+    /// Chunk type is critical or registered, so writing it could corrupt the image
+    ProtectedChunkType(String)
+}
+
+impl error::Error for RegistryError {}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegistryError::ProtectedChunkType(chunk_type) => write!(
+                f,
+                "Refusing to write chunk type {} since it is critical or registered, use --force to write it anyway",
+                chunk_type
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe("IHDR"), Some("Image header"));
+        assert_eq!(describe("fcTL"), Some("Frame control"));
+        assert_eq!(describe("ruSt"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let chunk = |chunk_type: &str, data: &[u8]| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec());
+
+        assert_eq!(
+            decode(&chunk("IHDR", &[0, 0, 2, 128, 0, 0, 1, 224, 8, 6, 0, 0, 1])),
+            Some(String::from("640x480, 8 bit truecolor with alpha, interlaced"))
+        );
+        assert_eq!(decode(&chunk("PLTE", &[0; 48])), Some(String::from("16 colors")));
+        assert_eq!(decode(&chunk("sRGB", &[0])), Some(String::from("perceptual rendering intent")));
+        assert_eq!(decode(&chunk("gAMA", &[0, 0, 177, 143])), Some(String::from("gamma 0.45455")));
+        assert_eq!(decode(&chunk("tEXt", b"Title\0Dice")), Some(String::from("Title: Dice")));
+        assert_eq!(
+            decode(&chunk("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1])),
+            Some(String::from("2835 pixels per metre (72 dpi)"))
+        );
+        assert_eq!(decode(&chunk("pHYs", &[0, 0, 0, 2, 0, 0, 0, 1, 0])), Some(String::from("2:1 pixel aspect ratio")));
+        assert_eq!(decode(&chunk("tIME", &[0x07, 0xE5, 3, 9, 14, 5, 30])), Some(String::from("2021-03-09 14:05:30")));
+        assert_eq!(decode(&chunk("acTL", &[0, 0, 0, 3, 0, 0, 0, 0])), Some(String::from("3 frames, looping forever")));
+
+        // Malformed data, and chunk types without a decoder
+        assert_eq!(decode(&chunk("gAMA", &[0, 1])), None);
+        assert_eq!(decode(&chunk("PLTE", &[0; 4])), None);
+        assert_eq!(decode(&chunk("IDAT", &[1, 2, 3])), None);
+        assert_eq!(decode(&chunk("ruSt", b"secret")), None);
+    }
+
+    #[test]
+    fn test_describe_any() {
+        assert_eq!(describe_any(&ChunkType::from_str("pHYs").unwrap()), "Physical pixel dimensions");
+        assert_eq!(describe_any(&ChunkType::from_str("RuSt").unwrap()), "Unknown critical chunk");
+        assert_eq!(describe_any(&ChunkType::from_str("vpAg").unwrap()), "Private chunk");
+        assert_eq!(describe_any(&ChunkType::from_str("vPAg").unwrap()), "Unknown public chunk");
+    }
+
+    #[test]
+    fn test_check_writable() {
+        assert!(check_writable(&ChunkType::from_str("ruSt").unwrap()).is_ok());
+        assert!(check_writable(&ChunkType::from_str("RuSt").unwrap()).is_err());
+        assert!(check_writable(&ChunkType::from_str("IDAT").unwrap()).is_err());
+        assert!(check_writable(&ChunkType::from_str("tEXt").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::error;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::metadata;
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Characters that can't appear in a file name on common file systems
+const RESERVED_CHARACTERS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// A value extracted from a PNG file to build its new name
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Field {
+    /// Text of a tEXt, zTXt or iTXt chunk with the given keyword
+    Keyword(String),
+
+    /// Date of the tIME chunk, as YYYY-MM-DD
+    Date,
+
+    /// Message of the first chunk with the given chunk type
+    Chunk(String),
+
+    /// Current file name without its extension
+    Name
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Keyword(keyword) => write!(f, "{{keyword:{}}}", keyword),
+            Field::Date => write!(f, "{{date}}"),
+            Field::Chunk(chunk_type) => write!(f, "{{chunk:{}}}", chunk_type),
+            Field::Name => write!(f, "{{name}}")
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Segment {
+    Literal(String),
+    Field(Field)
+}
+
+/// File name template such as `{keyword:Title}_{date}.png`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Pattern {
+    segments: Vec<Segment>
+}
+
+impl Pattern {
+    /// Build the new file name of a PNG file from its metadata
+    pub fn render(&self, png: &Png, filepath: &Path) -> Result<String> {
+        let mut name = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => name.push_str(literal),
+                Segment::Field(field) => {
+                    let value = field_value(field, png, filepath)?
+                        .ok_or_else(|| RenameError::MissingField(field.to_string()))?;
+                    name.push_str(&sanitize(&value));
+                }
+            }
+        }
+
+        let name = name.trim().to_string();
+
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(RenameError::InvalidFileName(name).into());
+        }
+
+        Ok(name)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || RenameError::InvalidPattern(s.to_string());
+
+        // New names stay in the same directory
+        if s.contains(['/', '\\']) {
+            return Err(invalid().into());
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = s;
+
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(start) if rest[start..].starts_with('{') => {
+                    if start > 0 {
+                        segments.push(Segment::Literal(rest[..start].to_string()));
+                    }
+
+                    let end = rest[start..].find('}').ok_or_else(invalid)? + start;
+                    let field = match rest[start + 1..end].split_once(':') {
+                        Some(("keyword", keyword)) if !keyword.is_empty() => {
+                            Field::Keyword(keyword.to_string())
+                        },
+                        Some(("chunk", chunk_type)) if chunk_type.len() == 4 => {
+                            Field::Chunk(chunk_type.to_string())
+                        },
+                        None if &rest[start + 1..end] == "date" => Field::Date,
+                        None if &rest[start + 1..end] == "name" => Field::Name,
+                        _ => return Err(invalid().into())
+                    };
+
+                    segments.push(Segment::Field(field));
+                    rest = &rest[end + 1..];
+                },
+                Some(_) => return Err(invalid().into()),
+                None => {
+                    segments.push(Segment::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+
+        Ok(Pattern { segments })
+    }
+}
+
+/// What to do when a new file name is already taken
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Collision {
+    /// Leave the file as it is
+    Skip,
+
+    /// Append a number to the new file name until it is free
+    Suffix
+}
+
+impl FromStr for Collision {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Collision::Skip),
+            "suffix" => Ok(Collision::Suffix),
+            _ => Err(RenameError::InvalidCollision(s.to_string()).into())
+        }
+    }
+}
+
+/// Planned outcome for a single file
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Action {
+    /// File is renamed
+    Rename { from: PathBuf, to: PathBuf },
+
+    /// File already has its new name
+    Unchanged(PathBuf),
+
+    /// New name is already taken and the file is left as it is
+    Collision { from: PathBuf, to: PathBuf }
+}
+
+/// Decide how every file is renamed, given its current path and its new path
+///
+/// A new path collides with files that already exist and with new paths planned earlier
+pub fn plan<F>(targets: &[(PathBuf, PathBuf)], collision: Collision, exists: F) -> Vec<Action>
+where
+    F: Fn(&Path) -> bool
+{
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+    let mut actions = Vec::new();
+
+    for (from, to) in targets {
+        if from == to {
+            taken.insert(to.clone());
+            actions.push(Action::Unchanged(from.clone()));
+            continue;
+        }
+
+        let is_free = |path: &Path| !taken.contains(path) && !exists(path);
+
+        let free = match collision {
+            _ if is_free(to) => Some(to.clone()),
+            Collision::Skip => None,
+            Collision::Suffix => (1..)
+                .map(|number| with_suffix(to, number))
+                .find(|path| is_free(path))
+        };
+
+        match free {
+            Some(path) => {
+                taken.insert(path.clone());
+                actions.push(Action::Rename { from: from.clone(), to: path });
+            },
+            None => actions.push(Action::Collision { from: from.clone(), to: to.clone() })
+        }
+    }
+
+    actions
+}
+
+fn field_value(field: &Field, png: &Png, filepath: &Path) -> Result<Option<String>> {
+    match field {
+        Field::Keyword(keyword) => metadata::keyword_text(png, keyword),
+        Field::Date => Ok(metadata::modification_time(png)?.map(|time| time.date())),
+        Field::Chunk(chunk_type) => png
+            .chunk_by_type(chunk_type)
+            .map(|chunk| chunk.data_as_string())
+            .transpose(),
+        Field::Name => Ok(filepath
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string()))
+    }
+}
+
+/// Replace characters that aren't allowed in file names
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_control() || RESERVED_CHARACTERS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Path with `_<number>` appended to its file stem
+fn with_suffix(path: &Path, number: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, number)
+    };
+
+    path.with_file_name(name)
+}
+
+/// Renaming errors
+#[derive(Debug)]
+pub enum RenameError {
+    /// Pattern has an unknown or unterminated placeholder, or a path separator
+    InvalidPattern(String),
+
+    /// Unknown collision strategy
+    InvalidCollision(String),
+
+    /// PNG file has no value for a placeholder of the pattern
+    MissingField(String),
+
+    /// Pattern produced a name that can't be used as a file name
+    InvalidFileName(String)
+}
+
+impl error::Error for RenameError {}
+
+impl Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameError::InvalidPattern(pattern) => write!(
+                f,
+                "Invalid pattern '{}', expected a file name with {{keyword:<keyword>}}, {{date}}, {{chunk:<type>}} or {{name}} placeholders",
+                pattern
+            ),
+            RenameError::InvalidCollision(collision) => {
+                write!(f, "Invalid collision strategy '{}', expected skip or suffix", collision)
+            },
+            RenameError::MissingField(field) => write!(f, "No value found for {}", field),
+            RenameError::InvalidFileName(name) => write!(f, "Invalid file name '{}'", name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"Title\0Dice: red/blue".to_vec()),
+            Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![0x07, 0xE5, 3, 9, 14, 5, 30]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"secret".to_vec())
+        ])
+    }
+
+    #[test]
+    fn test_pattern_from_str() {
+        let pattern = Pattern::from_str("{keyword:Title}_{date}.png").unwrap();
+
+        assert_eq!(
+            pattern.segments,
+            vec![
+                Segment::Field(Field::Keyword(String::from("Title"))),
+                Segment::Literal(String::from("_")),
+                Segment::Field(Field::Date),
+                Segment::Literal(String::from(".png"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(Pattern::from_str("{title}.png").is_err());
+        assert!(Pattern::from_str("{keyword:Title.png").is_err());
+        assert!(Pattern::from_str("date}.png").is_err());
+        assert!(Pattern::from_str("{keyword:}.png").is_err());
+        assert!(Pattern::from_str("photos/{date}.png").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let png = testing_png();
+        let pattern = Pattern::from_str("{keyword:Title}_{date}_{chunk:ruSt}_{name}.png").unwrap();
+
+        assert_eq!(
+            pattern.render(&png, Path::new("photos/IMG_01.png")).unwrap(),
+            "Dice_ red_blue_2021-03-09_secret_IMG_01.png"
+        );
+    }
+
+    #[test]
+    fn test_render_missing_field() {
+        let png = testing_png();
+        let pattern = Pattern::from_str("{keyword:Author}.png").unwrap();
+
+        assert!(pattern.render(&png, Path::new("dice.png")).is_err());
+    }
+
+    #[test]
+    fn test_plan_with_suffix() {
+        let targets = vec![
+            (PathBuf::from("a.png"), PathBuf::from("Dice.png")),
+            (PathBuf::from("b.png"), PathBuf::from("Dice.png")),
+            (PathBuf::from("c.png"), PathBuf::from("c.png")),
+            (PathBuf::from("d.png"), PathBuf::from("Cat.png"))
+        ];
+
+        let actions = plan(&targets, Collision::Suffix, |path| path == Path::new("Cat.png"));
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::Rename { from: PathBuf::from("a.png"), to: PathBuf::from("Dice.png") },
+                Action::Rename { from: PathBuf::from("b.png"), to: PathBuf::from("Dice_1.png") },
+                Action::Unchanged(PathBuf::from("c.png")),
+                Action::Rename { from: PathBuf::from("d.png"), to: PathBuf::from("Cat_1.png") }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_with_skip() {
+        let targets = vec![
+            (PathBuf::from("a.png"), PathBuf::from("Dice.png")),
+            (PathBuf::from("b.png"), PathBuf::from("Dice.png"))
+        ];
+
+        let actions = plan(&targets, Collision::Skip, |_| false);
+
+        assert_eq!(
+            actions[1],
+            Action::Collision { from: PathBuf::from("b.png"), to: PathBuf::from("Dice.png") }
+        );
+    }
+}
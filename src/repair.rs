@@ -0,0 +1,508 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::image_data::{self, ImageHeader};
+use crate::png::{Png, PngError};
+use crate::zlib::{self, Fault};
+use crate::Result;
+
+/// A fix applied to a damaged PNG
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Fix {
+    /// The signature was damaged or missing and has been written again
+    Signature,
+
+    /// The IHDR chunk was missing and has been regenerated from the given header
+    Header(ImageHeader),
+
+    /// The Adler-32 checksum of the image data was wrong and has been recomputed from the intact deflate data
+    Adler { stored: u32, computed: u32 },
+
+    /// The intact chunks the file starts with were kept, and the damaged or cut short bytes after them dropped
+    Truncated { kept: usize, dropped: usize },
+
+    /// The IEND chunk was missing and has been added
+    End,
+
+    /// The image data was cut short or damaged, and has been padded with zeros after the bytes recovered
+    ImageData { recovered: usize, length: usize }
+}
+
+impl Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fix::Signature => write!(f, "Restored the PNG signature"),
+            Fix::Header(header) => write!(
+                f,
+                "Regenerated the IHDR chunk for a {}x{} image with bit depth {} and color type {}",
+                header.width, header.height, header.bit_depth, header.color_type
+            ),
+            Fix::Adler { stored, computed } => write!(
+                f,
+                "Recomputed the Adler-32 checksum of the image data: {:#010x} instead of {:#010x}",
+                computed, stored
+            ),
+            Fix::Truncated { kept, dropped } => write!(
+                f,
+                "Kept the {} intact chunks the file starts with and dropped the {} bytes after them",
+                kept, dropped
+            ),
+            Fix::End => write!(f, "Added the missing IEND chunk"),
+            Fix::ImageData { recovered, length } => write!(
+                f,
+                "Padded the image data with zeros after the {} of {} bytes recovered",
+                recovered, length
+            )
+        }
+    }
+}
+
+/// Repair a damaged PNG, returning it along with the fixes that were needed
+///
+/// The signature is only restored when an intact IHDR chunk follows it, and a missing IHDR chunk is
+/// only regenerated from the given header when it agrees with the size of the image data
+pub fn repair(bytes: &[u8], header: Option<ImageHeader>) -> Result<(Png, Vec<Fix>)> {
+    let mut fixes = Vec::new();
+
+    let mut png = match Png::try_from(bytes) {
+        Ok(png) => png,
+        Err(error) if bytes.starts_with(&Png::STANDARD_HEADER) => return Err(error),
+        Err(_) => {
+            fixes.push(Fix::Signature);
+            restore_signature(bytes)?
+        }
+    };
+
+    if png.chunk_index("IHDR").is_none() {
+        // Image data, when it can be decompressed, tells how large the image is
+        let data_length = image_data::inflate(&png).ok().map(|data| data.len());
+
+        let header = header.ok_or(RepairError::MissingHeader(data_length))?;
+        let expected = header.data_length()?;
+
+        match data_length {
+            Some(actual) if actual != expected => {
+                return Err(RepairError::DimensionMismatch(expected, actual).into());
+            },
+            _ => {}
+        }
+
+        png.insert_chunk(0, Chunk::new(ChunkType::try_from(*b"IHDR")?, header.as_bytes()))?;
+        fixes.push(Fix::Header(header));
+    }
+
+    Ok((png, fixes))
+}
+
+/// Recompute the Adler-32 checksum of the image data when it is the only damage, returning the fix if one was needed
+///
+/// The deflate data must decompress in full, so a wrong checksum is known to be the fault of the checksum alone.
+/// The checksum may straddle two IDAT chunks, whose CRCs are recomputed along with it
+pub fn recompute_adler(png: &mut Png) -> Result<Option<Fix>> {
+    let stream: Vec<u8> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        .flat_map(|chunk| chunk.data().iter().copied())
+        .collect();
+
+    let (stored, computed, adler_offset) = match zlib::check_stream(&stream) {
+        Ok(_) => return Ok(None),
+        Err(Fault::AdlerMismatch { stored, computed, offset }) => (stored, computed, offset),
+        Err(fault) => return Err(RepairError::CorruptImageData(fault).into())
+    };
+
+    let checksum = computed.to_be_bytes();
+    let mut offset = 0;
+    let mut chunks = Vec::with_capacity(png.chunks().len());
+
+    for chunk in png.chunks() {
+        if chunk.chunk_type().to_string() != "IDAT" {
+            chunks.push(chunk.clone());
+            continue;
+        }
+
+        let range = offset..offset + chunk.data().len();
+        offset = range.end;
+
+        let mut data = chunk.data().to_vec();
+        let mut patched = false;
+
+        for (index, byte) in checksum.iter().enumerate() {
+            if range.contains(&(adler_offset + index)) {
+                data[adler_offset + index - range.start] = *byte;
+                patched = true;
+            }
+        }
+
+        match patched {
+            true => chunks.push(Chunk::new(chunk.chunk_type().clone(), data)),
+            false => chunks.push(chunk.clone())
+        }
+    }
+
+    *png = Png::from_chunks(chunks);
+
+    Ok(Some(Fix::Adler { stored, computed }))
+}
+
+/// Keep the intact chunks a truncated or damaged PNG starts with, and end it with an IEND chunk if it has none
+///
+/// With `pad_image_data`, image data cut short is decompressed as far as it goes and padded with zeros up to the size
+/// the IHDR chunk calls for, so decoders show the top of the image and blank rows below
+pub fn salvage(bytes: &[u8], pad_image_data: bool) -> Result<(Png, Vec<Fix>)> {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        return Err(PngError::InvalidSignature.into());
+    }
+
+    let mut fixes = Vec::new();
+    let mut chunks = Vec::new();
+    let mut index = Png::STANDARD_HEADER.len();
+
+    // The first chunk cut short or failing its CRC ends what can be trusted
+    while let Ok(chunk) = Chunk::try_from(&bytes[index..]) {
+        index += chunk.length() as usize + 12;
+        chunks.push(chunk);
+    }
+
+    if chunks.is_empty() {
+        return Err(RepairError::NothingToSalvage.into());
+    }
+
+    if index < bytes.len() {
+        fixes.push(Fix::Truncated { kept: chunks.len(), dropped: bytes.len() - index });
+    }
+
+    let mut png = Png::from_chunks(chunks);
+
+    if png.chunk_index("IEND").is_none() {
+        png.append_chunk(Chunk::new(ChunkType::try_from(*b"IEND")?, Vec::new()));
+        fixes.push(Fix::End);
+    }
+
+    if pad_image_data {
+        fixes.extend(pad(&mut png)?);
+    }
+
+    Ok((png, fixes))
+}
+
+/// Pad the image data cut short with zeros, which decode as rows without a filter and with every sample 0
+fn pad(png: &mut Png) -> Result<Option<Fix>> {
+    let header = ImageHeader::from_png(png)?;
+
+    // The header may claim far more image data than the file ever held, and all of it would be zeros
+    let length = header
+        .data_length()
+        .ok()
+        .filter(|length| *length <= Chunk::MAX_LENGTH as usize)
+        .ok_or(RepairError::OversizedImage(header.width, header.height))?;
+
+    let stream: Vec<u8> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        .flat_map(|chunk| chunk.data().iter().copied())
+        .collect();
+
+    if zlib::check_stream(&stream).is_ok_and(|inflated| inflated.data.len() == length) {
+        return Ok(None);
+    }
+
+    let mut data = zlib::inflate_prefix(&stream);
+    data.truncate(length);
+    let recovered = data.len();
+    data.resize(length, 0);
+
+    // Image data lost altogether goes right before IEND
+    if png.chunk_index("IDAT").is_none() {
+        let index = png.chunk_index("IEND").unwrap_or(png.chunks().len());
+        png.insert_chunk(index, Chunk::new(ChunkType::try_from(*b"IDAT")?, Vec::new()))?;
+    }
+
+    *png = image_data::replace_image_data(png, &data)?;
+
+    Ok(Some(Fix::ImageData { recovered, length }))
+}
+
+/// Parse a PNG whose signature is damaged or missing, as long as an intact IHDR chunk comes first
+fn restore_signature(bytes: &[u8]) -> Result<Png> {
+    let signature_length = Png::STANDARD_HEADER.len();
+
+    // The signature was either overwritten or cut off, as files carved from a disk image may be
+    let candidates = [bytes.get(signature_length..).unwrap_or_default(), bytes];
+
+    let chunks = candidates
+        .iter()
+        .find(|chunks| {
+            Chunk::try_from(**chunks).is_ok_and(|chunk| {
+                chunk.chunk_type().to_string() == "IHDR" && ImageHeader::try_from(chunk.data()).is_ok()
+            })
+        })
+        .ok_or(RepairError::UnrecoverableSignature)?;
+
+    let restored: Vec<u8> = Png::STANDARD_HEADER
+        .iter()
+        .chain(chunks.iter())
+        .copied()
+        .collect();
+
+    Png::try_from(&restored[..])
+}
+
+/// Repair errors
+#[derive(Debug)]
+pub enum RepairError {
+    /// The signature is damaged and no intact IHDR chunk follows it
+    UnrecoverableSignature,
+
+    /// The IHDR chunk is missing and no dimensions were given, with the size of the image data when known
+    MissingHeader(Option<usize>),
+
+    /// The given dimensions need a different amount of image data than the PNG holds
+    DimensionMismatch(usize, usize),
+
+    /// The image data is damaged beyond its Adler-32 checksum
+    CorruptImageData(Fault),
+
+    /// Not even the first chunk of the file is intact
+    NothingToSalvage,
+
+    /// The IHDR chunk claims an image with more data than can be padded, with its width and height
+    OversizedImage(u32, u32)
+}
+
+impl error::Error for RepairError {}
+
+impl Display for RepairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepairError::UnrecoverableSignature => write!(
+                f,
+                "The PNG signature is damaged and no intact IHDR chunk follows it"
+            ),
+            RepairError::MissingHeader(Some(length)) => write!(
+                f,
+                "Missing IHDR chunk, give --width and --height to regenerate it ({} bytes of image data were found)",
+                length
+            ),
+            RepairError::MissingHeader(None) => {
+                write!(f, "Missing IHDR chunk, give --width and --height to regenerate it")
+            },
+            RepairError::DimensionMismatch(expected, actual) => write!(
+                f,
+                "The given dimensions need {} bytes of image data but the PNG holds {} bytes",
+                expected, actual
+            ),
+            RepairError::CorruptImageData(fault) => write!(
+                f,
+                "The image data is corrupt in its {}, {}, so its Adler-32 checksum can't be recomputed",
+                fault.layer(),
+                fault
+            ),
+            RepairError::NothingToSalvage => write!(f, "Not even the first chunk of the file is intact"),
+            RepairError::OversizedImage(width, height) => write!(
+                f,
+                "The IHDR chunk claims a {}x{} image, whose data is more than {} bytes and can't be padded",
+                width,
+                height,
+                Chunk::MAX_LENGTH
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PngmeError;
+    use crate::image_data::tests::testing_png;
+
+    fn testing_header() -> ImageHeader {
+        ImageHeader::from_png(&testing_png(10, 4, 2, 8, 0)).unwrap()
+    }
+
+    #[test]
+    fn test_intact_png() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let (repaired, fixes) = repair(&png.as_bytes(), None).unwrap();
+
+        assert_eq!(repaired, png);
+        assert!(fixes.is_empty());
+    }
+
+    #[test]
+    fn test_damaged_signature() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let mut bytes = png.as_bytes();
+        bytes[1..4].copy_from_slice(b"JPG");
+
+        let (repaired, fixes) = repair(&bytes, None).unwrap();
+        assert_eq!(repaired, png);
+        assert_eq!(fixes, vec![Fix::Signature]);
+    }
+
+    #[test]
+    fn test_missing_signature() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let bytes = png.as_bytes();
+
+        let (repaired, fixes) = repair(&bytes[8..], None).unwrap();
+        assert_eq!(repaired, png);
+        assert_eq!(fixes, vec![Fix::Signature]);
+    }
+
+    #[test]
+    fn test_unrecoverable_signature() {
+        let mut png = testing_png(10, 4, 2, 8, 0);
+        png.remove_chunk("IHDR").unwrap();
+        let mut bytes = png.as_bytes();
+        bytes[0] = 0;
+
+        assert!(matches!(
+            repair(&bytes, None),
+            Err(PngmeError::Repair(RepairError::UnrecoverableSignature))
+        ));
+    }
+
+    #[test]
+    fn test_regenerate_header() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let mut damaged = png.clone();
+        damaged.remove_chunk("IHDR").unwrap();
+
+        assert!(matches!(
+            repair(&damaged.as_bytes(), None),
+            Err(PngmeError::Repair(RepairError::MissingHeader(Some(_))))
+        ));
+
+        let (repaired, fixes) = repair(&damaged.as_bytes(), Some(testing_header())).unwrap();
+        assert_eq!(repaired, png);
+        assert_eq!(fixes, vec![Fix::Header(testing_header())]);
+    }
+
+    #[test]
+    fn test_regenerate_header_mismatch() {
+        let mut damaged = testing_png(10, 4, 2, 8, 0);
+        damaged.remove_chunk("IHDR").unwrap();
+
+        let header = ImageHeader { width: 12, ..testing_header() };
+
+        assert!(matches!(
+            repair(&damaged.as_bytes(), Some(header)),
+            Err(PngmeError::Repair(RepairError::DimensionMismatch(_, _)))
+        ));
+    }
+
+    #[test]
+    fn test_recompute_adler() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let stream = image_data::deflate(&image_data::inflate(&png).unwrap()).unwrap();
+
+        // The checksum straddles the last two IDAT chunks
+        let split = stream.len() - 2;
+        let mut damaged_stream = stream.clone();
+        damaged_stream[split - 1] ^= 0xFF;
+        damaged_stream[split] ^= 0xFF;
+
+        let idat = |data: &[u8]| Chunk::new(ChunkType::try_from(*b"IDAT").unwrap(), data.to_vec());
+        let mut chunks = png.chunks().to_vec();
+        chunks.retain(|chunk| chunk.chunk_type().to_string() != "IDAT");
+        chunks.insert(1, idat(&damaged_stream[..split]));
+        chunks.insert(2, idat(&damaged_stream[split..]));
+        let mut damaged = Png::from_chunks(chunks);
+
+        let fix = recompute_adler(&mut damaged).unwrap();
+        assert!(matches!(fix, Some(Fix::Adler { .. })));
+        assert!(zlib::check_png(&damaged).iter().all(|check| check.fault.is_none()));
+        assert_eq!(image_data::inflate(&damaged).unwrap(), image_data::inflate(&png).unwrap());
+        assert_eq!(damaged.chunks()[2].data(), &stream[split..]);
+
+        // Nothing to fix the second time
+        assert_eq!(recompute_adler(&mut damaged).unwrap(), None);
+    }
+
+    #[test]
+    fn test_recompute_adler_of_corrupt_deflate_data() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        let mut stream = image_data::deflate(&image_data::inflate(&png).unwrap()).unwrap();
+        stream.truncate(stream.len() / 2);
+
+        let mut chunks = png.chunks().to_vec();
+        let index = chunks.iter().position(|chunk| chunk.chunk_type().to_string() == "IDAT").unwrap();
+        chunks[index] = Chunk::new(ChunkType::try_from(*b"IDAT").unwrap(), stream);
+
+        assert!(matches!(
+            recompute_adler(&mut Png::from_chunks(chunks)),
+            Err(PngmeError::Repair(RepairError::CorruptImageData(Fault::TruncatedDeflate)))
+        ));
+    }
+
+    /// Testing PNG whose image data is spread over IDAT chunks of at most 16 bytes
+    fn fragmented_png() -> Png {
+        let png = testing_png(16, 16, 2, 8, 0);
+        let stream = image_data::deflate(&image_data::inflate(&png).unwrap()).unwrap();
+
+        let mut chunks = vec![png.chunks()[0].clone()];
+        chunks.extend(stream.chunks(16).map(|part| Chunk::new(ChunkType::try_from(*b"IDAT").unwrap(), part.to_vec())));
+        chunks.push(Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), Vec::new()));
+
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_salvage_truncated() {
+        let png = fragmented_png();
+        let bytes = png.as_bytes();
+        let cut = bytes.len() * 2 / 3;
+
+        let (salvaged, fixes) = salvage(&bytes[..cut], false).unwrap();
+        let kept = salvaged.chunks().len() - 1;
+
+        assert_eq!(&salvaged.chunks()[..kept], &png.chunks()[..kept]);
+        assert_eq!(salvaged.chunks()[kept].chunk_type().to_string(), "IEND");
+        assert!(matches!(fixes[..], [Fix::Truncated { .. }, Fix::End]));
+
+        // An intact file is left alone
+        assert_eq!(salvage(&bytes, true).unwrap(), (png, Vec::new()));
+        assert!(matches!(salvage(&bytes[..20], false), Err(PngmeError::Repair(RepairError::NothingToSalvage))));
+    }
+
+    #[test]
+    fn test_salvage_pad_image_data() {
+        let png = fragmented_png();
+        let original = image_data::inflate(&png).unwrap();
+        let bytes = png.as_bytes();
+
+        let (salvaged, fixes) = salvage(&bytes[..bytes.len() * 2 / 3], true).unwrap();
+        let padded = image_data::inflate(&salvaged).unwrap();
+
+        let recovered = match fixes[..] {
+            [Fix::Truncated { .. }, Fix::End, Fix::ImageData { recovered, length }] => {
+                assert_eq!(length, original.len());
+                recovered
+            },
+            _ => panic!("Unexpected fixes {:?}", fixes)
+        };
+
+        assert!(recovered > 0 && recovered < original.len());
+        assert_eq!(padded.len(), original.len());
+        assert_eq!(&padded[..recovered], &original[..recovered]);
+        assert!(padded[recovered..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_salvage_pad_oversized_image() {
+        let header = ImageHeader { width: 0x7fff_ffff, height: 0x7fff_ffff, ..testing_header() };
+        let mut chunks = fragmented_png().chunks().to_vec();
+        chunks[0] = Chunk::new(ChunkType::try_from(*b"IHDR").unwrap(), header.as_bytes());
+
+        assert!(matches!(
+            salvage(&Png::from_chunks(chunks).as_bytes(), true),
+            Err(PngmeError::Repair(RepairError::OversizedImage(0x7fff_ffff, 0x7fff_ffff)))
+        ));
+    }
+}
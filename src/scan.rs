@@ -0,0 +1,161 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::diagnostics::Rule;
+use crate::settings::Settings;
+use crate::{Error, Result};
+
+/// External command screening extracted payloads, such as `clamscan --no-summary -`
+///
+/// The payload is written to the command's standard input, and any exit code other than 0 flags it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Scanner {
+    program: String,
+    args: Vec<String>
+}
+
+impl Scanner {
+    /// Run the scanner on a payload, returning its exit code when it flags the payload
+    pub fn scan(&self, payload: &[u8]) -> Result<Option<i32>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        // Scanners may stop reading once they reach a verdict
+        if let Some(mut stdin) = child.stdin.take() {
+            match stdin.write_all(payload) {
+                Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error.into()),
+                _ => {}
+            }
+        }
+
+        let status = child.wait()?;
+
+        // Scanners killed by a signal have no exit code
+        Ok(match status.code() {
+            Some(0) => None,
+            code => Some(code.unwrap_or(-1))
+        })
+    }
+
+    /// Run the scanner on a payload and apply the policy if it flags the payload
+    pub fn screen(&self, settings: &Settings, payload: &[u8], policy: ScanPolicy) -> Result<()> {
+        match (self.scan(payload)?, policy) {
+            (None, _) => Ok(()),
+            (Some(code), ScanPolicy::Block) => Err(ScanError::Rejected(self.program.clone(), code).into()),
+            (Some(code), ScanPolicy::Flag) => settings.warn(
+                Rule::FlaggedPayload,
+                format!("{} flagged the payload with exit code {}", self.program, code)
+            )
+        }
+    }
+}
+
+impl FromStr for Scanner {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut words = s.split_whitespace().map(String::from);
+
+        let program = words.next().ok_or(ScanError::MissingCommand)?;
+
+        Ok(Scanner {
+            program,
+            args: words.collect()
+        })
+    }
+}
+
+/// What happens to a payload flagged by the scanner
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScanPolicy {
+    /// The payload is neither shown nor written, and the command fails
+    Block,
+
+    /// A warning is printed and the payload is handled as usual
+    Flag
+}
+
+impl FromStr for ScanPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block" => Ok(ScanPolicy::Block),
+            "flag" => Ok(ScanPolicy::Flag),
+            _ => Err(ScanError::InvalidPolicy(s.to_string()).into())
+        }
+    }
+}
+
+/// Payload scanning errors
+#[derive(Debug)]
+pub enum ScanError {
+    /// The scanner command is empty
+    MissingCommand,
+
+    /// Scan policy is neither block nor flag
+    InvalidPolicy(String),
+
+    /// The scanner flagged the payload, with its exit code
+    Rejected(String, i32)
+}
+
+impl error::Error for ScanError {}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScanError::MissingCommand => write!(f, "Scanner command is empty"),
+            ScanError::InvalidPolicy(policy) => {
+                write!(f, "Invalid scan policy '{}', expected block or flag", policy)
+            },
+            ScanError::Rejected(program, code) => write!(
+                f,
+                "Payload rejected: {} flagged it with exit code {}",
+                program, code
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PngmeError;
+
+    #[test]
+    fn test_scanner_from_str() {
+        let scanner = Scanner::from_str("clamscan  --no-summary -").unwrap();
+
+        assert_eq!(scanner.program, "clamscan");
+        assert_eq!(scanner.args, vec!["--no-summary", "-"]);
+        assert!(Scanner::from_str("  ").is_err());
+    }
+
+    #[test]
+    fn test_scan_policy_from_str() {
+        assert_eq!(ScanPolicy::from_str("block").unwrap(), ScanPolicy::Block);
+        assert_eq!(ScanPolicy::from_str("flag").unwrap(), ScanPolicy::Flag);
+        assert!(ScanPolicy::from_str("allow").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_screen() {
+        // Flags payloads containing the word "virus"
+        let scanner = Scanner::from_str("grep -qv virus").unwrap();
+
+        assert!(scanner.screen(&Settings::default(), b"This is a test", ScanPolicy::Block).is_ok());
+        assert!(matches!(
+            scanner.screen(&Settings::default(), b"virus", ScanPolicy::Block),
+            Err(PngmeError::Scan(ScanError::Rejected(_, 1)))
+        ));
+        assert!(scanner.screen(&Settings::default(), b"virus", ScanPolicy::Flag).is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::diagnostics::Rule;
+use crate::envelope;
+use crate::png::Png;
+use crate::settings::Settings;
+use crate::Result;
+
+/// Start of a sealed payload, the outermost envelope so the digest is checked before anything is decompressed
+const MAGIC: [u8; 4] = [envelope::LEAD, b'P', b'S', 0x1A];
+
+/// Magic and digest of the image
+const HEADER_LENGTH: usize = MAGIC.len() + 32;
+
+/// Payload wrapped in an envelope recording a digest of the image it is hidden in
+pub fn seal(png: &Png, chunk_type: &str, data: &[u8]) -> Vec<u8> {
+    let mut sealed = MAGIC.to_vec();
+    sealed.extend_from_slice(&digest(png, chunk_type));
+    sealed.extend_from_slice(data);
+
+    sealed
+}
+
+/// Payload out of its envelope, or as it is when it wasn't sealed
+pub fn strip(data: &[u8]) -> Result<&[u8]> {
+    match opened(data)? {
+        Some((_, payload)) => Ok(payload),
+        None => Ok(data)
+    }
+}
+
+/// Warn when a sealed payload was hidden in an image whose critical chunks have changed since
+///
+/// Resizing or recompressing the image rewrites its critical chunks, so the payload may predate what the image shows
+pub fn check(settings: &Settings, filepath: &Path, png: &Png, chunk_type: &str, data: &[u8]) -> Result<()> {
+    match opened(data)? {
+        Some((sealed, _)) if sealed != digest(png, chunk_type) => settings.warn(
+            Rule::ModifiedCarrier,
+            format!(
+                "{}: the image was modified since the {} message was hidden, it may predate what the image shows",
+                filepath.display(),
+                chunk_type
+            )
+        ),
+        _ => Ok(())
+    }
+}
+
+/// SHA-256 of the critical chunks of an image, leaving out chunks of the payload's type
+///
+/// Image data split across IDAT chunks differently is the same image, so IDAT chunks are hashed as one stream.
+/// Chunks of the payload's type are left out in case it was forced into a critical chunk
+fn digest(png: &Png, chunk_type: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut image_data = Sha256::new();
+
+    for chunk in png.chunks() {
+        let current = chunk.chunk_type().to_string();
+
+        if !chunk.chunk_type().is_critical() || current == chunk_type {
+            continue;
+        }
+
+        if current == "IDAT" {
+            image_data.update(chunk.data());
+        } else {
+            hasher.update(chunk.chunk_type().bytes());
+            hasher.update(chunk.length().to_be_bytes());
+            hasher.update(chunk.data());
+        }
+    }
+
+    hasher.update(image_data.finalize());
+    hasher.finalize().into()
+}
+
+/// Digest and payload of a sealed envelope, `None` when the data isn't sealed
+fn opened(data: &[u8]) -> Result<Option<([u8; 32], &[u8])>> {
+    if !data.starts_with(&MAGIC) {
+        return Ok(None);
+    }
+
+    if data.len() < HEADER_LENGTH {
+        return Err(SealError::InvalidSeal.into());
+    }
+
+    let mut digest = [0; 32];
+    digest.copy_from_slice(&data[MAGIC.len()..HEADER_LENGTH]);
+
+    Ok(Some((digest, &data[HEADER_LENGTH..])))
+}
+
+/// Seal errors
+#[derive(Debug)]
+pub enum SealError {
+    /// Sealed envelope is cut short
+    InvalidSeal,
+
+    /// Hiding the payload in the pixels changes the image data the seal covers
+    LsbUnsupported
+}
+
+impl error::Error for SealError {}
+
+impl Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SealError::InvalidSeal => write!(f, "Sealed payload is damaged"),
+            SealError::LsbUnsupported => write!(f, "Messages can only be sealed in chunks, not in lsb mode")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::image_data::tests::testing_png;
+    use crate::Error;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let png = testing_png(4, 4, 2, 8, 0);
+        let sealed = seal(&png, "ruSt", b"message");
+
+        assert_eq!(strip(&sealed).unwrap(), b"message");
+        assert_eq!(strip(b"message").unwrap(), b"message");
+        assert!(matches!(strip(&sealed[..HEADER_LENGTH - 1]), Err(Error::Seal(SealError::InvalidSeal))));
+    }
+
+    #[test]
+    fn test_digest() {
+        let png = testing_png(4, 4, 2, 8, 0);
+        let original = digest(&png, "ruSt");
+
+        // Ancillary chunks and the payload's own chunk don't change the image
+        let mut annotated = png.clone();
+        annotated.insert_chunk(1, chunk("tEXt", b"Title\0Dice")).unwrap();
+        annotated.insert_chunk(1, chunk("RUST", b"forced")).unwrap();
+        assert_eq!(digest(&annotated, "RUST"), original);
+
+        // Splitting the image data differently doesn't either
+        let mut split = png.clone();
+        split.coalesce_idat(2).unwrap();
+        assert_eq!(digest(&split, "ruSt"), original);
+
+        let recompressed = testing_png(4, 4, 2, 16, 0);
+        assert_ne!(digest(&recompressed, "ruSt"), original);
+    }
+}
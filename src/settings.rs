@@ -0,0 +1,50 @@
+use crate::diagnostics::{Policy, Rule};
+use crate::events::{Event, Events};
+use crate::locale::Locale;
+use crate::output::OutputFormat;
+use crate::profile::Profile;
+use crate::Result;
+
+/// Environment variable turning on deterministic output when set to anything but an empty string, like
+/// `--deterministic`
+pub const DETERMINISTIC_VAR: &str = "PNGME_DETERMINISTIC";
+
+/// Settings chosen on the command line, built once by main and passed to the commands
+///
+/// Commands are given their settings like they are given the files they read and the renderer they show results
+/// with, so tests can run them with any settings
+#[derive(Default)]
+pub struct Settings {
+    /// How warnings are handled
+    pub policy: Policy,
+
+    /// Locale of the numbers, sizes and dates in text output
+    pub locale: Locale,
+
+    /// Profile every PNG file written conforms to, if any
+    pub profile: Option<Profile>,
+
+    /// Format warnings are shown in
+    pub format: OutputFormat,
+
+    /// Where progress events are written, if anywhere
+    pub events: Events,
+
+    /// Whether output must be the same on every machine and every run
+    pub deterministic: bool
+}
+
+impl Settings {
+    /// Show a warning and write it as an event, or fail with it in strict mode unless its rule is allowed
+    pub fn warn(&self, rule: Rule, message: String) -> Result<()> {
+        if self.policy.ignores(rule) {
+            return Ok(());
+        }
+
+        self.policy.check(rule, &message)?;
+        self.format.warning(rule, &message);
+        self.events.emit(&Event::Warning { code: rule.code().to_string(), rule: rule.name().to_string(), message });
+
+        Ok(())
+    }
+}
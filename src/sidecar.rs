@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::error;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::files::FileProvider;
+use crate::metadata::{self, TextEntry, TEXT_CHUNK_TYPES};
+use crate::png::Png;
+use crate::Result;
+
+/// Textual metadata keywords with a field of their own in the sidecar
+const TITLE: &str = "Title";
+const AUTHOR: &str = "Author";
+const DESCRIPTION: &str = "Description";
+const COPYRIGHT: &str = "Copyright";
+const KEYWORDS: &str = "Keywords";
+
+/// Metres in an inch, to convert the pixels per metre of pHYs chunks to dots per inch
+const METRES_PER_INCH: f64 = 0.0254;
+
+/// gAMA chunks store the gamma times 100000
+const GAMMA_SCALE: f64 = 100_000.0;
+
+/// Image metadata kept in a JSON file next to the image
+///
+/// Every field is optional, and only the fields present are written to the image:
+/// - `title`, `author`, `description` and `copyright` are textual metadata with the keyword of the same name
+/// - `keywords` are joined with commas into the `Keywords` textual metadata
+/// - `dpi` is the resolution of a pHYs chunk, the same horizontally and vertically
+/// - `gamma` is the file gamma of a gAMA chunk, such as 0.45455
+/// - `custom` maps any other keyword to its text
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sidecar {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copyright: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, String>
+}
+
+impl Sidecar {
+    /// Read a sidecar from a JSON file
+    pub fn read(files: &dyn FileProvider, path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice(&files.read(path)?)?)
+    }
+
+    /// Sidecar as pretty printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Collect the metadata of a PNG, the first entry wins when a keyword is repeated
+    pub fn from_png(png: &Png) -> Result<Self> {
+        let mut sidecar = Sidecar::default();
+
+        for entry in metadata::text_entries(png)? {
+            let field = match entry.keyword.as_str() {
+                TITLE => &mut sidecar.title,
+                AUTHOR => &mut sidecar.author,
+                DESCRIPTION => &mut sidecar.description,
+                COPYRIGHT => &mut sidecar.copyright,
+                KEYWORDS if sidecar.keywords.is_empty() => {
+                    sidecar.keywords = entry
+                        .text
+                        .split(',')
+                        .map(|keyword| keyword.trim().to_string())
+                        .filter(|keyword| !keyword.is_empty())
+                        .collect();
+                    continue;
+                },
+                KEYWORDS => continue,
+                _ => {
+                    sidecar.custom.entry(entry.keyword).or_insert(entry.text);
+                    continue;
+                }
+            };
+
+            field.get_or_insert(entry.text);
+        }
+
+        // Only resolutions in pixels per metre can be converted, unit 0 merely gives the aspect ratio
+        if let Some(chunk) = png.chunk_by_type("pHYs") {
+            let data = chunk.data();
+
+            if data.len() != 9 {
+                return Err(SidecarError::InvalidPhysicalDimensions.into());
+            }
+
+            if data[8] == 1 {
+                let pixels_per_metre = u32::from_be_bytes(data[0..4].try_into()?);
+                sidecar.dpi = Some((pixels_per_metre as f64 * METRES_PER_INCH).round() as u32);
+            }
+        }
+
+        if let Some(chunk) = png.chunk_by_type("gAMA") {
+            let gamma: [u8; 4] = chunk
+                .data()
+                .try_into()
+                .map_err(|_| SidecarError::InvalidGamma)?;
+
+            sidecar.gamma = Some(u32::from_be_bytes(gamma) as f64 / GAMMA_SCALE);
+        }
+
+        Ok(sidecar)
+    }
+
+    /// Write the metadata into a PNG, replacing the chunks it already has for the same fields
+    pub fn apply(&self, png: &Png) -> Result<Png> {
+        let mut png = png.clone();
+
+        for entry in self.text_entries() {
+            let chunk = entry.to_chunk()?;
+
+            // Textual metadata may be anywhere before IEND
+            remove_text(&mut png, &entry.keyword)?;
+            let index = png.chunk_index("IEND").unwrap_or(png.chunks().len());
+            png.insert_chunk(index, chunk)?;
+        }
+
+        if let Some(dpi) = self.dpi {
+            if dpi == 0 {
+                return Err(SidecarError::ResolutionTooLow(dpi).into());
+            }
+
+            let pixels_per_metre = (dpi as f64 / METRES_PER_INCH).round() as u32;
+            let data = pixels_per_metre
+                .to_be_bytes()
+                .iter()
+                .chain(pixels_per_metre.to_be_bytes().iter())
+                .chain([1].iter())
+                .copied()
+                .collect();
+
+            set_header_chunk(&mut png, "pHYs", data)?;
+        }
+
+        if let Some(gamma) = self.gamma {
+            let scaled = (gamma * GAMMA_SCALE).round();
+
+            if !(1.0..=u32::MAX as f64).contains(&scaled) {
+                return Err(SidecarError::GammaOutOfRange(gamma).into());
+            }
+
+            set_header_chunk(&mut png, "gAMA", (scaled as u32).to_be_bytes().to_vec())?;
+        }
+
+        Ok(png)
+    }
+
+    /// Textual metadata entries for every text field present
+    fn text_entries(&self) -> Vec<TextEntry> {
+        let keywords = match self.keywords.is_empty() {
+            true => None,
+            false => Some(self.keywords.join(", "))
+        };
+
+        let fields = [
+            (TITLE, &self.title),
+            (AUTHOR, &self.author),
+            (DESCRIPTION, &self.description),
+            (COPYRIGHT, &self.copyright),
+            (KEYWORDS, &keywords)
+        ];
+
+        fields
+            .iter()
+            .filter_map(|(keyword, text)| {
+                text.as_ref().map(|text| TextEntry {
+                    keyword: keyword.to_string(),
+                    text: text.clone()
+                })
+            })
+            .chain(self.custom.iter().map(|(keyword, text)| TextEntry {
+                keyword: keyword.clone(),
+                text: text.clone()
+            }))
+            .collect()
+    }
+}
+
+/// Remove every textual metadata chunk with the given keyword
+fn remove_text(png: &mut Png, keyword: &str) -> Result<()> {
+    let mut chunks = Vec::new();
+
+    for chunk in png.chunks() {
+        let is_text = TEXT_CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str());
+
+        if !is_text || TextEntry::try_from(chunk)?.keyword != keyword {
+            chunks.push(chunk.clone());
+        }
+    }
+
+    *png = Png::from_chunks(chunks);
+
+    Ok(())
+}
+
+/// Replace the chunk of the given type, or insert it right after IHDR so it comes before PLTE and IDAT
+fn set_header_chunk(png: &mut Png, chunk_type: &str, data: Vec<u8>) -> Result<()> {
+    let chunk = Chunk::new(ChunkType::from_str(chunk_type)?, data);
+
+    match png.chunk_index(chunk_type) {
+        Some(_) => {
+            png.replace_chunk(chunk)?;
+        },
+        None => {
+            let index = png.chunk_index("IHDR").map_or(0, |index| index + 1);
+            png.insert_chunk(index, chunk)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sidecar errors
+#[derive(Debug)]
+pub enum SidecarError {
+    /// Resolution must be at least 1 dot per inch
+    ResolutionTooLow(u32),
+
+    /// Gamma is out of the range a gAMA chunk can store
+    GammaOutOfRange(f64),
+
+    /// gAMA chunk is malformed
+    InvalidGamma,
+
+    /// pHYs chunk is malformed
+    InvalidPhysicalDimensions
+}
+
+impl error::Error for SidecarError {}
+
+impl Display for SidecarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SidecarError::ResolutionTooLow(dpi) => {
+                write!(f, "Invalid resolution {} dpi, expected at least 1 dpi", dpi)
+            },
+            SidecarError::GammaOutOfRange(gamma) => write!(
+                f,
+                "Invalid gamma {}, expected a positive value of at most {}",
+                gamma,
+                u32::MAX as f64 / GAMMA_SCALE
+            ),
+            SidecarError::InvalidGamma => write!(f, "Invalid gAMA chunk"),
+            SidecarError::InvalidPhysicalDimensions => write!(f, "Invalid pHYs chunk")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("tEXt", b"Title\0Old title"),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IEND", &[])
+        ])
+    }
+
+    fn testing_sidecar() -> Sidecar {
+        Sidecar {
+            title: Some(String::from("Dice")),
+            author: Some(String::from("Luís")),
+            keywords: vec![String::from("dice"), String::from("render")],
+            dpi: Some(300),
+            gamma: Some(0.45455),
+            custom: vec![(String::from("Camera"), String::from("東京 X100"))].into_iter().collect(),
+            ..Sidecar::default()
+        }
+    }
+
+    #[test]
+    fn test_sidecar_json() {
+        let json = r#"{ "title": "Dice", "keywords": ["dice"], "dpi": 72, "custom": { "Camera": "X100" } }"#;
+        let sidecar: Sidecar = serde_json::from_str(json).unwrap();
+
+        assert_eq!(sidecar.title, Some(String::from("Dice")));
+        assert_eq!(sidecar.dpi, Some(72));
+        assert_eq!(sidecar.custom["Camera"], "X100");
+
+        assert!(serde_json::from_str::<Sidecar>(r#"{ "titel": "Dice" }"#).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let png = testing_sidecar().apply(&testing_png()).unwrap();
+
+        assert_eq!(
+            chunk_types(&png),
+            vec!["IHDR", "gAMA", "pHYs", "IDAT", "tEXt", "tEXt", "tEXt", "iTXt", "IEND"]
+        );
+        assert_eq!(metadata::keyword_text(&png, "Title").unwrap(), Some(String::from("Dice")));
+        assert_eq!(metadata::keyword_text(&png, "Keywords").unwrap(), Some(String::from("dice, render")));
+        assert_eq!(png.chunk_by_type("pHYs").unwrap().data(), &[0, 0, 0x2E, 0x23, 0, 0, 0x2E, 0x23, 1]);
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data(), &45455u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let png = testing_sidecar().apply(&testing_png()).unwrap();
+
+        assert_eq!(Sidecar::from_png(&png).unwrap(), testing_sidecar());
+
+        // Applying the same metadata twice replaces the chunks instead of adding more
+        let again = testing_sidecar().apply(&png).unwrap();
+        assert_eq!(chunk_types(&again), chunk_types(&png));
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let sidecar = Sidecar { dpi: Some(0), ..Sidecar::default() };
+        assert!(sidecar.apply(&testing_png()).is_err());
+
+        let sidecar = Sidecar { gamma: Some(-1.0), ..Sidecar::default() };
+        assert!(sidecar.apply(&testing_png()).is_err());
+
+        let sidecar = Sidecar {
+            custom: vec![(String::from(" Camera"), String::new())].into_iter().collect(),
+            ..Sidecar::default()
+        };
+        assert!(sidecar.apply(&testing_png()).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::error;
+use std::fmt::{self, Display};
+
+use crate::Result;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Length of the HMAC-SHA256 tag appended to signed chunk data
+pub const TAG_LENGTH: usize = 32;
+
+/// Signs a payload with the given key and returns the payload followed by its tag
+pub fn sign(key: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|_| SignatureError::InvalidKey)?;
+    mac.update(payload);
+    let tag = mac.finalize().into_bytes();
+
+    Ok(payload.iter().chain(tag.iter()).copied().collect())
+}
+
+/// Verifies signed chunk data with the given key and returns the original payload
+pub fn verify<'a>(key: &[u8], data: &'a [u8]) -> Result<&'a [u8]> {
+    // Signed data must at least hold the tag
+    if data.len() < TAG_LENGTH {
+        return Err(SignatureError::MissingSignature.into());
+    }
+
+    // Separate payload from the tag at the end of the data
+    let (payload, tag) = data.split_at(data.len() - TAG_LENGTH);
+
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|_| SignatureError::InvalidKey)?;
+    mac.update(payload);
+    mac.verify_slice(tag)
+        .map_err(|_| SignatureError::VerificationFailed)?;
+
+    Ok(payload)
+}
+
+/// Signature errors
+#[derive(Debug)]
+pub enum SignatureError {
+    /// The key could not be used to build the HMAC
+    InvalidKey,
+
+    /// Chunk data is too short to contain a signature
+    MissingSignature,
+
+    /// The signature does not match the payload
+    VerificationFailed
+}
+
+impl error::Error for SignatureError {}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::InvalidKey => write!(f, "Invalid signing key"),
+            SignatureError::MissingSignature => {
+                write!(f, "Chunk data is too short to contain a signature")
+            },
+            SignatureError::VerificationFailed => {
+                write!(f, "Signature verification failed, the message was tampered with")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PngmeError;
+
+    #[test]
+    fn test_sign_appends_tag() {
+        let signed = sign(b"secret", b"This is a test").unwrap();
+        assert_eq!(signed.len(), "This is a test".len() + TAG_LENGTH);
+        assert_eq!(&signed[..14], b"This is a test");
+    }
+
+    #[test]
+    fn test_verify_valid_signature() {
+        let signed = sign(b"secret", b"This is a test").unwrap();
+        let payload = verify(b"secret", &signed).unwrap();
+        assert_eq!(payload, b"This is a test");
+    }
+
+    #[test]
+    fn test_verify_wrong_key() {
+        let signed = sign(b"secret", b"This is a test").unwrap();
+        assert!(verify(b"other", &signed).is_err());
+    }
+
+    #[test]
+    fn test_verify_tampered_payload() {
+        let mut signed = sign(b"secret", b"This is a test").unwrap();
+        signed[0] = b't';
+        let err = verify(b"secret", &signed).unwrap_err();
+        assert!(matches!(
+            err,
+            PngmeError::Signature(SignatureError::VerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_verify_too_short() {
+        assert!(verify(b"secret", b"short").is_err());
+    }
+}
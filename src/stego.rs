@@ -0,0 +1,307 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::image_data::{self, ImageHeader, Scanline};
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Length in bytes of the header holding the hidden message's length
+const LENGTH_HEADER: usize = 4;
+
+/// Where a message is hidden in a PNG
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// In an ancillary chunk of its own
+    Chunk,
+
+    /// In the least significant bits of the pixels
+    Lsb
+}
+
+impl Mode {
+    pub const ALL: [Mode; 2] = [Mode::Chunk, Mode::Lsb];
+
+    /// Name given to `--mode`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Chunk => "chunk",
+            Mode::Lsb => "lsb"
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Mode::ALL
+            .iter()
+            .find(|mode| mode.name() == s)
+            .copied()
+            .ok_or_else(|| StegoError::InvalidMode(s.to_string()).into())
+    }
+}
+
+/// Number of message bytes that can be hidden in the pixels of a PNG
+pub fn capacity(png: &Png) -> Result<usize> {
+    let (header, scanlines) = carrier(png)?;
+    let bytes = carrier_bytes(&header, &scanlines).count() / 8;
+
+    Ok(bytes.saturating_sub(LENGTH_HEADER))
+}
+
+/// Average difference between neighbouring samples of the bytes carrying a message
+///
+/// Noisy images hide changes to their lowest bits better than flat ones
+pub fn noise(png: &Png) -> Result<f64> {
+    let (header, scanlines) = carrier(png)?;
+    let step = carrier_step(&header);
+    let distance = header.filter_distance();
+
+    let mut total = 0u64;
+    let mut count = 0u64;
+
+    for scanline in &scanlines {
+        for index in (distance + step - 1..scanline.data.len()).step_by(step) {
+            total += (scanline.data[index] as i32 - scanline.data[index - distance] as i32).unsigned_abs() as u64;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return Ok(0.0);
+    }
+
+    Ok(total as f64 / count as f64)
+}
+
+/// Hides a message in the least significant bits of the pixels of a PNG
+pub fn embed(png: &Png, message: &[u8]) -> Result<Png> {
+    let (header, mut scanlines) = carrier(png)?;
+
+    // Message is preceded by its length so it can be extracted later
+    let framed: Vec<u8> = (message.len() as u32)
+        .to_be_bytes()
+        .iter()
+        .chain(message.iter())
+        .copied()
+        .collect();
+
+    let available = carrier_bytes(&header, &scanlines).count() / 8;
+
+    if framed.len() > available {
+        return Err(StegoError::MessageTooLarge(
+            message.len(),
+            available.saturating_sub(LENGTH_HEADER)
+        ).into());
+    }
+
+    let bits = framed
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+
+    for (byte, bit) in carrier_bytes_mut(&header, &mut scanlines).zip(bits) {
+        *byte = (*byte & !1) | bit;
+    }
+
+    let data = image_data::filter(&header, &scanlines)?;
+    image_data::replace_image_data(png, &data)
+}
+
+/// Extracts a message hidden in the least significant bits of the pixels of a PNG
+pub fn extract(png: &Png) -> Result<Vec<u8>> {
+    let (header, scanlines) = carrier(png)?;
+
+    let mut bytes = carrier_bytes(&header, &scanlines)
+        .collect::<Vec<u8>>()
+        .chunks_exact(8)
+        .map(|bits| bits.iter().fold(0, |byte, bit| (byte << 1) | (bit & 1)))
+        .collect::<Vec<u8>>();
+
+    if bytes.len() < LENGTH_HEADER {
+        return Err(StegoError::NoMessage.into());
+    }
+
+    let message = bytes.split_off(LENGTH_HEADER);
+    let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+
+    if length > message.len() {
+        return Err(StegoError::NoMessage.into());
+    }
+
+    Ok(message[..length].to_vec())
+}
+
+/// Header and unfiltered scanlines of a PNG that can carry a message
+fn carrier(png: &Png) -> Result<(ImageHeader, Vec<Scanline>)> {
+    let header = ImageHeader::from_png(png)?;
+
+    // Palette indices and packed samples change visibly when their lowest bit flips
+    if header.color_type == 3 || header.bit_depth < 8 {
+        return Err(StegoError::UnsupportedFormat.into());
+    }
+
+    let scanlines = image_data::unfilter(&header, &image_data::inflate(png)?)?;
+
+    Ok((header, scanlines))
+}
+
+/// Step between the bytes holding a message bit, only the low byte of 16 bit samples is used
+fn carrier_step(header: &ImageHeader) -> usize {
+    if header.bit_depth == 16 {
+        2
+    } else {
+        1
+    }
+}
+
+fn carrier_bytes<'a>(header: &ImageHeader, scanlines: &'a [Scanline]) -> impl Iterator<Item = u8> + 'a {
+    let step = carrier_step(header);
+
+    scanlines
+        .iter()
+        .flat_map(move |scanline| scanline.data.iter().skip(step - 1).step_by(step).copied())
+}
+
+fn carrier_bytes_mut<'a>(
+    header: &ImageHeader,
+    scanlines: &'a mut [Scanline]
+) -> impl Iterator<Item = &'a mut u8> {
+    let step = carrier_step(header);
+
+    scanlines
+        .iter_mut()
+        .flat_map(move |scanline| scanline.data.iter_mut().skip(step - 1).step_by(step))
+}
+
+/// Steganography errors
+#[derive(Debug)]
+pub enum StegoError {
+    /// Unknown encoding mode
+    InvalidMode(String),
+
+    /// The image is palette based or has less than 8 bits per sample
+    UnsupportedFormat,
+
+    /// The message does not fit in the image, holds message length and capacity
+    MessageTooLarge(usize, usize),
+
+    /// No message is hidden in the image
+    NoMessage
+}
+
+impl error::Error for StegoError {}
+
+impl Display for StegoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StegoError::InvalidMode(mode) => {
+                write!(f, "Invalid mode '{}', expected chunk or lsb", mode)
+            },
+            StegoError::UnsupportedFormat => write!(
+                f,
+                "Only truecolor and grayscale images with 8 or 16 bits per sample can hide messages in their pixels"
+            ),
+            StegoError::MessageTooLarge(length, capacity) => write!(
+                f,
+                "Message has {} bytes but the image can only hide {} bytes",
+                length, capacity
+            ),
+            StegoError::NoMessage => write!(f, "No message found in the image's pixels")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::tests::testing_png;
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!(Mode::from_str("chunk").unwrap(), Mode::Chunk);
+        assert_eq!(Mode::from_str("lsb").unwrap(), Mode::Lsb);
+        assert!(Mode::from_str("pixels").is_err());
+    }
+
+    #[test]
+    fn test_capacity() {
+        // 10 * 4 pixels with 3 samples each hold 15 bytes, 4 of them for the length
+        let png = testing_png(10, 4, 2, 8, 0);
+        assert_eq!(capacity(&png).unwrap(), 11);
+
+        // Only the low byte of 16 bit samples carries a bit
+        let png = testing_png(10, 4, 2, 16, 0);
+        assert_eq!(capacity(&png).unwrap(), 11);
+    }
+
+    #[test]
+    fn test_noise() {
+        // Unfiltered rows of 4 gray pixels, either flat or alternating between 0 and 100
+        let flat: Vec<u8> = (0..4).flat_map(|_| vec![0, 50, 50, 50, 50]).collect();
+        let noisy: Vec<u8> = (0..4).flat_map(|_| vec![0, 0, 100, 0, 100]).collect();
+
+        let png = testing_png(4, 4, 0, 8, 0);
+        let flat = image_data::replace_image_data(&png, &flat).unwrap();
+        let noisy = image_data::replace_image_data(&png, &noisy).unwrap();
+
+        assert_eq!(noise(&flat).unwrap(), 0.0);
+        assert_eq!(noise(&noisy).unwrap(), 100.0);
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        for (color_type, bit_depth, interlace_method) in [(2, 8, 0), (6, 16, 0), (0, 8, 1), (4, 8, 1)].iter() {
+            let png = testing_png(20, 20, *color_type, *bit_depth, *interlace_method);
+            let embedded = embed(&png, b"This is a test").unwrap();
+
+            assert_eq!(extract(&embedded).unwrap(), b"This is a test");
+            assert_eq!(embedded.chunks().len(), png.chunks().len());
+        }
+    }
+
+    #[test]
+    fn test_embed_changes_only_lowest_bits() {
+        let png = testing_png(20, 20, 2, 8, 0);
+        let embedded = embed(&png, b"This is a test").unwrap();
+
+        let header = ImageHeader::from_png(&png).unwrap();
+        let original = image_data::unfilter(&header, &image_data::inflate(&png).unwrap()).unwrap();
+        let changed = image_data::unfilter(&header, &image_data::inflate(&embedded).unwrap()).unwrap();
+
+        for (original, changed) in original.iter().zip(changed.iter()) {
+            assert_eq!(original.filter, changed.filter);
+
+            for (a, b) in original.data.iter().zip(changed.data.iter()) {
+                assert_eq!(a & !1, b & !1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let png = testing_png(10, 4, 2, 8, 0);
+        assert!(embed(&png, b"This is a test").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let png = testing_png(20, 20, 3, 8, 0);
+        assert!(embed(&png, b"Test").is_err());
+
+        let png = testing_png(20, 20, 0, 4, 0);
+        assert!(embed(&png, b"Test").is_err());
+    }
+
+    #[test]
+    fn test_extract_without_message() {
+        // Unfiltered scanlines of 20 RGB pixels whose lowest bits are all set
+        let data: Vec<u8> = (0..20)
+            .flat_map(|_| std::iter::once(0).chain(std::iter::repeat_n(255, 60)))
+            .collect();
+        let png = image_data::replace_image_data(&testing_png(20, 20, 2, 8, 0), &data).unwrap();
+
+        assert!(extract(&png).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::metadata::{TextEntry, TEXT_CHUNK_TYPES};
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// How derivative files are matched with their originals
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MatchBy {
+    /// Same file name
+    Name
+}
+
+impl FromStr for MatchBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "name" => Ok(MatchBy::Name),
+            _ => Err(SyncError::InvalidMatchBy(s.to_string()).into())
+        }
+    }
+}
+
+/// Pair every target with its original, leaving out targets without one
+pub fn pair(originals: &[PathBuf], targets: &[PathBuf], match_by: MatchBy) -> Vec<(PathBuf, PathBuf)> {
+    targets
+        .iter()
+        .filter_map(|target| {
+            let original = match match_by {
+                MatchBy::Name => originals
+                    .iter()
+                    .find(|original| original.file_name() == target.file_name())
+            };
+
+            original.map(|original| (original.clone(), target.clone()))
+        })
+        .collect()
+}
+
+/// Copy the safe to copy ancillary chunks of the original that the target lost
+///
+/// A chunk is lost when the target has no chunk of its type, or for textual metadata no entry with its keyword.
+/// Chunks found before the image data in the original are inserted before the target's image data, as pHYs must be,
+/// and the other ones before IEND. Returns the updated target along with the number of chunks copied
+pub fn sync(original: &Png, target: &Png) -> Result<(Png, usize)> {
+    let target_keywords = keywords(target)?;
+    let first_idat = original.chunk_index("IDAT").unwrap_or(original.chunks().len());
+
+    let mut png = target.clone();
+    let mut count = 0;
+
+    for (index, chunk) in original.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+
+        if chunk_type.is_critical() || !chunk_type.is_safe_to_copy() {
+            continue;
+        }
+
+        let lost = match text_keyword(chunk)? {
+            Some(keyword) => !target_keywords.contains(&keyword),
+            None => target.chunk_index(&chunk_type.to_string()).is_none()
+        };
+
+        if !lost {
+            continue;
+        }
+
+        let position = match index < first_idat {
+            true => png.chunk_index("IDAT"),
+            false => png.chunk_index("IEND")
+        };
+
+        png.insert_chunk(position.unwrap_or(png.chunks().len()), chunk.clone())?;
+        count += 1;
+    }
+
+    Ok((png, count))
+}
+
+/// Keyword of a textual metadata chunk, none for other chunks
+fn text_keyword(chunk: &Chunk) -> Result<Option<String>> {
+    if !TEXT_CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str()) {
+        return Ok(None);
+    }
+
+    Ok(Some(TextEntry::try_from(chunk)?.keyword))
+}
+
+/// Keywords of every textual metadata chunk of a PNG
+fn keywords(png: &Png) -> Result<Vec<String>> {
+    let mut keywords = Vec::new();
+
+    for chunk in png.chunks() {
+        if let Some(keyword) = text_keyword(chunk)? {
+            keywords.push(keyword);
+        }
+    }
+
+    Ok(keywords)
+}
+
+/// Metadata sync errors
+#[derive(Debug)]
+pub enum SyncError {
+    /// Matching strategy is unknown
+    InvalidMatchBy(String)
+}
+
+impl error::Error for SyncError {}
+
+impl Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncError::InvalidMatchBy(match_by) => {
+                write!(f, "Invalid match strategy '{}', expected name", match_by)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_pair() {
+        let originals = vec![PathBuf::from("originals/a.png"), PathBuf::from("originals/b.png")];
+        let targets = vec![PathBuf::from("processed/b.png"), PathBuf::from("processed/c.png")];
+
+        assert_eq!(
+            pair(&originals, &targets, MatchBy::Name),
+            vec![(PathBuf::from("originals/b.png"), PathBuf::from("processed/b.png"))]
+        );
+    }
+
+    #[test]
+    fn test_sync() {
+        let original = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("gAMA", &[0, 0, 0xB1, 0x8F]),
+            chunk("pHYs", &[0, 0, 0x0B, 0x13, 0, 0, 0x0B, 0x13, 1]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("tEXt", b"Author\0Luis"),
+            chunk("tIME", &[0x07, 0xE5, 3, 9, 14, 5, 30]),
+            chunk("IEND", &[])
+        ]);
+
+        let target = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[4, 5, 6]),
+            chunk("tEXt", b"Title\0Processed dice"),
+            chunk("IEND", &[])
+        ]);
+
+        let (synced, count) = sync(&original, &target).unwrap();
+
+        // gAMA and tIME aren't safe to copy, and the title is kept
+        assert_eq!(count, 2);
+        assert_eq!(chunk_types(&synced), vec!["IHDR", "pHYs", "IDAT", "tEXt", "tEXt", "IEND"]);
+        assert_eq!(synced.chunks()[3].data(), b"Title\0Processed dice");
+        assert_eq!(synced.chunks()[4].data(), b"Author\0Luis");
+
+        // Nothing is left to copy the second time
+        let (_, count) = sync(&original, &synced).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_match_by_from_str() {
+        assert_eq!(MatchBy::from_str("name").unwrap(), MatchBy::Name);
+        assert!(MatchBy::from_str("hash").is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::registry;
+use crate::Result;
+
+/// What the case of each letter of a chunk type means, shown before asking for one
+const CHUNK_TYPE_HELP: &str = "\
+A chunk type is 4 ASCII letters, and the case of each letter is a flag:
+  1st letter - uppercase: critical, viewers need it to show the image / lowercase: ancillary, viewers may skip it
+  2nd letter - uppercase: public, defined by the PNG specification / lowercase: private, defined by anyone
+  3rd letter - always uppercase
+  4th letter - uppercase: unsafe to copy when editing the image / lowercase: safe to copy
+Secret messages belong in ancillary private chunks, such as ruSt";
+
+/// Operations the wizard walks through, with the subcommand running them and an explanation
+const OPERATIONS: [(&str, &str); 4] = [
+    ("encode", "Hide a message in a chunk of a PNG file"),
+    ("decode", "Show a message hidden in a chunk of a PNG file"),
+    ("remove", "Remove a chunk holding a message from a PNG file"),
+    ("print", "List every chunk of a PNG file")
+];
+
+/// Ways print can show the chunks' data, with the options selecting them
+const DATA_FORMATS: [(&str, &[&str]); 3] = [
+    ("Decoded when possible, as bytes otherwise", &[]),
+    ("As a hex dump", &["--hex"]),
+    ("Not at all", &["--no-data"])
+];
+
+/// Asks a user what to do, one question at a time, and builds the equivalent command line
+///
+/// Questions are written to `prompts` and answers read line by line from `input`. Invalid answers are explained
+/// and the question asked again
+pub struct Wizard<R: BufRead, W: Write> {
+    input: R,
+    prompts: W
+}
+
+impl<R: BufRead, W: Write> Wizard<R, W> {
+    pub fn new(input: R, prompts: W) -> Self {
+        Wizard { input, prompts }
+    }
+
+    /// Ask every question, returning the arguments of the equivalent command without the program name
+    pub fn run(&mut self) -> Result<Vec<String>> {
+        writeln!(self.prompts, "Welcome to pngme! Answer a few questions to build a command.\n")?;
+
+        let descriptions: Vec<&str> = OPERATIONS.iter().map(|(_, description)| *description).collect();
+        let (operation, _) = OPERATIONS[self.choose("What do you want to do?", &descriptions)?];
+
+        let filepath = self.ask_file()?;
+        let mut args = vec![operation.to_string(), filepath.clone()];
+
+        match operation {
+            "encode" => {
+                args.push(self.ask_chunk_type(&filepath, true)?.to_string());
+                args.push(self.ask("Message to hide", None)?);
+
+                let output_file = self.ask("Output file - leave empty to overwrite the PNG file", Some(""))?;
+
+                if !output_file.is_empty() {
+                    args.push(output_file);
+                }
+            },
+            "decode" | "remove" => args.push(self.ask_chunk_type(&filepath, false)?.to_string()),
+            _ => {
+                let descriptions: Vec<&str> = DATA_FORMATS.iter().map(|(description, _)| *description).collect();
+                let (_, options) = DATA_FORMATS[self.choose("How should the chunks' data be shown?", &descriptions)?];
+
+                args.extend(options.iter().map(|option| option.to_string()));
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// Read the next answer, without its line break
+    fn read_line(&mut self) -> Result<String> {
+        self.prompts.flush()?;
+
+        let mut line = String::new();
+
+        if self.input.read_line(&mut line)? == 0 {
+            return Err(WizardError::Cancelled.into());
+        }
+
+        Ok(line.trim_end_matches(['\n', '\r']).to_string())
+    }
+
+    /// Ask for some text, returning the default for an empty answer when there is one
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        loop {
+            write!(self.prompts, "{}: ", question)?;
+            let answer = self.read_line()?;
+
+            match (answer.is_empty(), default) {
+                (false, _) => return Ok(answer),
+                (true, Some(default)) => return Ok(default.to_string()),
+                (true, None) => writeln!(self.prompts, "An answer is required")?
+            }
+        }
+    }
+
+    /// Ask to pick one of the options by number, returning its index
+    fn choose(&mut self, question: &str, options: &[&str]) -> Result<usize> {
+        writeln!(self.prompts, "{}", question)?;
+
+        for (index, option) in options.iter().enumerate() {
+            writeln!(self.prompts, "  {}) {}", index + 1, option)?;
+        }
+
+        loop {
+            let answer = self.ask("Choice", None)?;
+
+            match answer.trim().parse::<usize>() {
+                Ok(choice) if (1..=options.len()).contains(&choice) => return Ok(choice - 1),
+                _ => writeln!(self.prompts, "Type a number from 1 to {}", options.len())?
+            }
+        }
+    }
+
+    /// Ask for the path of an existing file
+    fn ask_file(&mut self) -> Result<String> {
+        loop {
+            let filepath = self.ask("PNG file", None)?;
+
+            match Path::new(&filepath).is_file() {
+                true => return Ok(filepath),
+                false => writeln!(self.prompts, "There is no file at {}", filepath)?
+            }
+        }
+    }
+
+    /// Ask for a chunk type, explaining what it means once it is valid
+    ///
+    /// Types that would corrupt the image are refused when writing, and the types already in the file are listed
+    /// when reading
+    fn ask_chunk_type(&mut self, filepath: &str, writing: bool) -> Result<ChunkType> {
+        writeln!(self.prompts, "\n{}", CHUNK_TYPE_HELP)?;
+
+        if !writing {
+            if let Some(chunk_types) = chunk_types(filepath) {
+                writeln!(self.prompts, "Chunk types in {}: {}", filepath, chunk_types.join(", "))?;
+            }
+        }
+
+        loop {
+            let answer = self.ask("Chunk type", None)?;
+
+            let chunk_type = match ChunkType::from_str(&answer) {
+                Ok(chunk_type) => chunk_type,
+                Err(error) => {
+                    writeln!(self.prompts, "{}", error)?;
+                    continue;
+                }
+            };
+
+            if writing {
+                if let Err(error) = registry::check_writable(&chunk_type) {
+                    writeln!(self.prompts, "{}", error)?;
+                    continue;
+                }
+            }
+
+            writeln!(self.prompts, "{}: {}\n", chunk_type, explain(&chunk_type))?;
+
+            return Ok(chunk_type);
+        }
+    }
+}
+
+/// Description and flags of a chunk type, such as "Private chunk - ancillary, private, safe to copy"
+fn explain(chunk_type: &ChunkType) -> String {
+    let flags = [
+        if chunk_type.is_critical() { "critical" } else { "ancillary" },
+        if chunk_type.is_public() { "public" } else { "private" },
+        if chunk_type.is_safe_to_copy() { "safe to copy" } else { "unsafe to copy" }
+    ];
+
+    format!("{} - {}", registry::describe_any(chunk_type), flags.join(", "))
+}
+
+/// Distinct chunk types of a PNG file in order of appearance, `None` when it can't be read
+fn chunk_types(filepath: &str) -> Option<Vec<String>> {
+    let png = Png::try_from(&fs::read(filepath).ok()?[..]).ok()?;
+    let mut chunk_types: Vec<String> = Vec::new();
+
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type().to_string();
+
+        if !chunk_types.contains(&chunk_type) {
+            chunk_types.push(chunk_type);
+        }
+    }
+
+    Some(chunk_types)
+}
+
+/// Command line running pngme with the arguments, quoting those a shell would interpret
+pub fn command_line(args: &[String]) -> String {
+    let quoted = args.iter().map(|arg| {
+        let plain = !arg.is_empty()
+            && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:,=@%+".contains(c));
+
+        match plain {
+            true => arg.clone(),
+            false => format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    });
+
+    std::iter::once(String::from("pngme")).chain(quoted).collect::<Vec<String>>().join(" ")
+}
+
+/// Wizard errors
+#[derive(Debug)]
+pub enum WizardError {
+    /// Input ended before every question was answered
+    Cancelled
+}
+
+impl error::Error for WizardError {}
+
+impl Display for WizardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WizardError::Cancelled => write!(f, "Wizard cancelled before every question was answered")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Opt;
+    use std::env;
+    use structopt::StructOpt;
+
+    /// Run the wizard with the given answers, returning its arguments and prompts
+    fn run(answers: &str) -> (Result<Vec<String>>, String) {
+        let mut prompts = Vec::new();
+        let result = Wizard::new(answers.as_bytes(), &mut prompts).run();
+
+        (result, String::from_utf8(prompts).unwrap())
+    }
+
+    fn dice() -> String {
+        env::current_dir().unwrap().join("dice.png").to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_encode() {
+        let (args, prompts) = run(&format!("1\nmissing.png\n{}\nIDAT\nruSt\nIt's a secret\n\n", dice()));
+        let args = args.unwrap();
+
+        assert_eq!(args, vec!["encode", &dice(), "ruSt", "It's a secret"]);
+        assert!(prompts.contains("There is no file at missing.png"));
+        assert!(prompts.contains("Refusing to write chunk type IDAT"));
+        assert!(prompts.contains("ruSt: Private chunk - ancillary, private, safe to copy"));
+
+        // The command runs the same operation
+        let opt = Opt::from_iter_safe(std::iter::once(String::from("pngme")).chain(args)).unwrap();
+        assert_eq!(opt.subcommand.name(), "encode");
+    }
+
+    #[test]
+    fn test_decode_lists_chunk_types() {
+        let (args, prompts) = run(&format!("2\n{}\nru\nruSt\n", dice()));
+
+        assert_eq!(args.unwrap(), vec!["decode", &dice(), "ruSt"]);
+        assert!(prompts.contains("Chunk types in"));
+        assert!(prompts.contains("IHDR, gAMA, tEXt, IDAT, tIME, IEND"));
+    }
+
+    #[test]
+    fn test_print_options() {
+        let (args, prompts) = run(&format!("5\nprint\n4\n{}\n2\n", dice()));
+
+        assert_eq!(args.unwrap(), vec!["print", &dice(), "--hex"]);
+        assert_eq!(prompts.matches("Type a number from 1 to 4").count(), 2);
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let (result, _) = run("1\n");
+
+        assert!(matches!(result, Err(crate::Error::Wizard(WizardError::Cancelled))));
+    }
+
+    #[test]
+    fn test_command_line() {
+        let args = ["encode", "my dice.png", "ruSt", "It's a secret", "", "--hex"];
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+
+        assert_eq!(
+            command_line(&args),
+            "pngme encode 'my dice.png' ruSt 'It'\\''s a secret' '' --hex"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use flate2::{Decompress, FlushDecompress, Status};
+use std::error;
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::diagnostics::Rule;
+use crate::png::Png;
+
+/// Only compression method defined by the PNG specification: zlib deflate with a window of at most 32 KiB
+const DEFLATE_METHOD: u8 = 0;
+
+/// Layer of a compressed stream, from the outermost to the innermost
+///
+/// The CRC of every chunk is checked when the file is read, so a corrupt layer here means the damage happened before
+/// the CRC was computed, or that the file was written that way
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Layer {
+    /// Compression method byte of the chunk, or of IHDR for the image data
+    Method,
+
+    /// Two byte zlib header
+    Header,
+
+    /// Deflate data
+    Deflate,
+
+    /// Adler-32 checksum of the decompressed data, after the deflate data
+    Adler
+}
+
+impl Layer {
+    /// Name of the layer in machine readable output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Method => "method",
+            Layer::Header => "zlib-header",
+            Layer::Deflate => "deflate",
+            Layer::Adler => "adler32"
+        }
+    }
+
+    /// Rule `validate` warns about a stream corrupt in this layer with
+    pub fn rule(&self) -> Rule {
+        match self {
+            Layer::Method => Rule::CorruptMethod,
+            Layer::Header => Rule::CorruptZlibHeader,
+            Layer::Deflate => Rule::CorruptDeflate,
+            Layer::Adler => Rule::CorruptAdler
+        }
+    }
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layer::Method => write!(f, "compression method"),
+            Layer::Header => write!(f, "zlib header"),
+            Layer::Deflate => write!(f, "deflate data"),
+            Layer::Adler => write!(f, "Adler-32 checksum")
+        }
+    }
+}
+
+/// What is wrong with a compressed stream
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Fault {
+    /// The chunk ends before its compression method byte
+    MissingMethod,
+
+    /// Compression method isn't 0
+    UnknownMethod(u8),
+
+    /// The zlib header is shorter than two bytes, or its check bits don't match
+    InvalidHeader,
+
+    /// The zlib header names another algorithm than deflate, or a window bigger than 32 KiB
+    UnsupportedHeader(u8),
+
+    /// The zlib header asks for a preset dictionary, which PNG doesn't allow
+    PresetDictionary,
+
+    /// The deflate data can't be decompressed
+    InvalidDeflate(String),
+
+    /// The deflate data ends before its last block
+    TruncatedDeflate,
+
+    /// The stream ends before the Adler-32 checksum
+    MissingAdler,
+
+    /// The Adler-32 checksum at the offset of the stream doesn't match the decompressed data
+    AdlerMismatch { stored: u32, computed: u32, offset: usize }
+}
+
+impl Fault {
+    /// Layer the fault is in
+    pub fn layer(&self) -> Layer {
+        match self {
+            Fault::MissingMethod | Fault::UnknownMethod(_) => Layer::Method,
+            Fault::InvalidHeader | Fault::UnsupportedHeader(_) | Fault::PresetDictionary => Layer::Header,
+            Fault::InvalidDeflate(_) | Fault::TruncatedDeflate => Layer::Deflate,
+            Fault::MissingAdler | Fault::AdlerMismatch { .. } => Layer::Adler
+        }
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::MissingMethod => write!(f, "the chunk ends before its compression method"),
+            Fault::UnknownMethod(method) => write!(f, "method {} is unknown, only 0 (deflate) exists", method),
+            Fault::InvalidHeader => write!(f, "the check bits don't match"),
+            Fault::UnsupportedHeader(cmf) => write!(f, "CMF byte {:#04x} isn't deflate with a 32 KiB window", cmf),
+            Fault::PresetDictionary => write!(f, "a preset dictionary is required, which PNG doesn't allow"),
+            Fault::InvalidDeflate(reason) => write!(f, "{}", reason),
+            Fault::TruncatedDeflate => write!(f, "the data ends before the last block"),
+            Fault::MissingAdler => write!(f, "the stream ends before the checksum"),
+            Fault::AdlerMismatch { stored, computed, .. } => {
+                write!(f, "stored {:#010x} but the data gives {:#010x}", stored, computed)
+            }
+        }
+    }
+}
+
+/// Decompressed content of an intact zlib stream
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Inflated {
+    pub data: Vec<u8>,
+
+    /// Offset of the Adler-32 checksum within the stream, right after the deflate data
+    pub adler_offset: usize
+}
+
+/// Compressed stream of a PNG and what is wrong with it, if anything
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StreamCheck {
+    /// Chunk holding the stream, or IDAT for the image data spread over every IDAT chunk
+    pub chunk_type: String,
+
+    /// Index of the chunk, or of the first IDAT chunk
+    pub index: usize,
+
+    pub fault: Option<Fault>
+}
+
+impl Display for StreamCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stream = match self.chunk_type.as_str() {
+            "IDAT" => String::from("image data in IDAT chunks"),
+            chunk_type => format!("{} chunk", chunk_type)
+        };
+
+        match &self.fault {
+            None => write!(f, "[{}] {}: intact", self.index, stream),
+            Some(fault) => write!(f, "[{}] {}: corrupt {}, {}", self.index, stream, fault.layer(), fault)
+        }
+    }
+}
+
+/// Check every compressed stream of a PNG: zTXt, iCCP and compressed iTXt chunks, and the image data
+pub fn check_png(png: &Png) -> Vec<StreamCheck> {
+    let chunks = png.chunks();
+    let mut checks = Vec::new();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type().to_string();
+
+        let fault = match chunk_type.as_str() {
+            "zTXt" | "iCCP" => match after_keyword(chunk) {
+                Some([method, stream @ ..]) => check_method(*method).or_else(|| check_stream(stream).err()),
+                _ => Some(Fault::MissingMethod)
+            },
+            "iTXt" => match after_keyword(chunk) {
+                // A compression flag of 0 means the text is stored as it is
+                Some([0, ..]) => continue,
+                Some([_, method, rest @ ..]) => {
+                    // The compressed text follows the language tag and the translated keyword
+                    let text = rest.splitn(3, |&byte| byte == 0).nth(2).unwrap_or_default();
+                    check_method(*method).or_else(|| check_stream(text).err())
+                },
+                _ => Some(Fault::MissingMethod)
+            },
+            _ => continue
+        };
+
+        checks.push(StreamCheck { chunk_type, index, fault });
+    }
+
+    if let Some(index) = png.chunk_index("IDAT") {
+        // The compression method of the image data is recorded in IHDR
+        let method = png.chunk_by_type("IHDR").and_then(|header| header.data().get(10).copied());
+        let stream: Vec<u8> = chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+
+        let fault = method.and_then(check_method).or_else(|| check_stream(&stream).err());
+
+        checks.push(StreamCheck { chunk_type: String::from("IDAT"), index, fault });
+    }
+
+    checks
+}
+
+/// Decompress a zlib stream, checking its header, its deflate data and its Adler-32 checksum in turn
+pub fn check_stream(stream: &[u8]) -> std::result::Result<Inflated, Fault> {
+    let (cmf, flg) = match stream {
+        [cmf, flg, ..] => (*cmf, *flg),
+        _ => return Err(Fault::InvalidHeader)
+    };
+
+    if (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(Fault::InvalidHeader);
+    }
+
+    if cmf & 0x0F != 8 || cmf >> 4 > 7 {
+        return Err(Fault::UnsupportedHeader(cmf));
+    }
+
+    if flg & 0x20 != 0 {
+        return Err(Fault::PresetDictionary);
+    }
+
+    let mut data = Vec::new();
+    let deflate_length = inflate_raw(&stream[2..], &mut data)?;
+    let adler_offset = 2 + deflate_length;
+
+    let stored = match stream.get(adler_offset..adler_offset + 4) {
+        Some(bytes) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        None => return Err(Fault::MissingAdler)
+    };
+
+    let computed = adler32(&data);
+
+    if stored != computed {
+        return Err(Fault::AdlerMismatch { stored, computed, offset: adler_offset });
+    }
+
+    Ok(Inflated { data, adler_offset })
+}
+
+/// As much of a zlib stream as can be decompressed, up to where it is cut short or corrupt
+///
+/// Neither the header nor the checksum are checked, so the data is only as trustworthy as it looks
+pub fn inflate_prefix(stream: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    if let Some(deflate) = stream.get(2..) {
+        let _ = inflate_raw(deflate, &mut data);
+    }
+
+    data
+}
+
+/// Adler-32 checksum, as zlib streams end with
+pub fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+
+    // Largest number of bytes summed before the sums could overflow
+    const BLOCK: usize = 5552;
+
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for block in data.chunks(BLOCK) {
+        for &byte in block {
+            a += u32::from(byte);
+            b += a;
+        }
+
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+
+    b << 16 | a
+}
+
+/// Decompress deflate data into `data`, returning the number of bytes it takes
+///
+/// Whatever could be decompressed is kept in `data` when the deflate data turns out to be corrupt
+fn inflate_raw(deflate: &[u8], data: &mut Vec<u8>) -> std::result::Result<usize, Fault> {
+    let mut inflater = Decompress::new(false);
+    data.reserve(deflate.len() * 2);
+
+    loop {
+        if data.len() == data.capacity() {
+            data.reserve(data.len().max(8192));
+        }
+
+        let (consumed, produced) = (inflater.total_in(), inflater.total_out());
+        let status = inflater
+            .decompress_vec(&deflate[consumed as usize..], data, FlushDecompress::None)
+            .map_err(|error| Fault::InvalidDeflate(error.to_string()))?;
+
+        match status {
+            Status::StreamEnd => return Ok(inflater.total_in() as usize),
+            // Nothing more can be decompressed even with room left, so the data stops midway
+            _ if inflater.total_in() == consumed && inflater.total_out() == produced => {
+                return Err(Fault::TruncatedDeflate)
+            },
+            _ => {}
+        }
+
+        if data.len() > Chunk::MAX_LENGTH as usize {
+            return Err(Fault::InvalidDeflate(String::from("the data decompresses to more than a chunk can hold")));
+        }
+    }
+}
+
+/// Bytes after the null terminated keyword a zTXt, iCCP or iTXt chunk starts with
+fn after_keyword(chunk: &Chunk) -> Option<&[u8]> {
+    let data = chunk.data();
+    data.iter().position(|&byte| byte == 0).map(|null| &data[null + 1..])
+}
+
+fn check_method(method: u8) -> Option<Fault> {
+    match method {
+        DEFLATE_METHOD => None,
+        method => Some(Fault::UnknownMethod(method))
+    }
+}
+
+/// Zlib errors
+#[derive(Debug)]
+pub enum ZlibError {
+    /// Compressed streams are corrupt, with their number
+    CorruptStreams(usize)
+}
+
+impl error::Error for ZlibError {}
+
+impl Display for ZlibError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZlibError::CorruptStreams(1) => write!(f, "1 compressed stream is corrupt"),
+            ZlibError::CorruptStreams(count) => write!(f, "{} compressed streams are corrupt", count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::image_data;
+    use crate::image_data::tests::testing_png;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn ztxt(method: u8, stream: &[u8]) -> Chunk {
+        chunk("zTXt", [&b"Comment\0"[..], &[method], stream].concat())
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(&vec![0xFF; 100_000]), 0x149A_302C);
+    }
+
+    #[test]
+    fn test_check_stream() {
+        let stream = image_data::deflate(b"Hello, hello, hello").unwrap();
+        let inflated = check_stream(&stream).unwrap();
+
+        assert_eq!(inflated.data, b"Hello, hello, hello");
+        assert_eq!(inflated.adler_offset, stream.len() - 4);
+
+        let mut bad_adler = stream.clone();
+        *bad_adler.last_mut().unwrap() ^= 1;
+        assert!(matches!(check_stream(&bad_adler), Err(Fault::AdlerMismatch { .. })));
+        assert_eq!(check_stream(&stream[..stream.len() - 2]), Err(Fault::MissingAdler));
+        assert_eq!(check_stream(&stream[..stream.len() - 8]), Err(Fault::TruncatedDeflate));
+
+        let mut bad_header = stream.clone();
+        bad_header[1] ^= 1;
+        assert_eq!(check_stream(&bad_header), Err(Fault::InvalidHeader));
+        assert_eq!(check_stream(&[0x78, 0xBB]), Err(Fault::PresetDictionary));
+        assert_eq!(check_stream(&[0x88, 0x1C]), Err(Fault::UnsupportedHeader(0x88)));
+        assert!(matches!(check_stream(&[0x78, 0x9C, 0xFF, 0xFF]), Err(Fault::InvalidDeflate(_))));
+    }
+
+    #[test]
+    fn test_check_png() {
+        let stream = image_data::deflate(b"Compressed comment").unwrap();
+        let mut png = testing_png(4, 4, 2, 8, 0);
+        png.insert_chunk(1, ztxt(0, &stream)).unwrap();
+        png.insert_chunk(2, ztxt(1, &stream)).unwrap();
+        png.insert_chunk(3, chunk("iTXt", b"Title\0\0\0en\0Titre\0Plain".to_vec())).unwrap();
+        png.insert_chunk(4, chunk("iTXt", [&b"Title\0\x01\0en\0Titre\0"[..], &stream[..4]].concat())).unwrap();
+
+        let faults: Vec<(String, usize, Option<Layer>)> = check_png(&png)
+            .into_iter()
+            .map(|check| (check.chunk_type, check.index, check.fault.map(|fault| fault.layer())))
+            .collect();
+
+        assert_eq!(
+            faults,
+            vec![
+                (String::from("zTXt"), 1, None),
+                (String::from("zTXt"), 2, Some(Layer::Method)),
+                (String::from("iTXt"), 4, Some(Layer::Deflate)),
+                (String::from("IDAT"), 5, None)
+            ]
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! Golden tests of the output of every subcommand, run on generated fixtures
+//!
+//! Snapshots live in tests/snapshots. After a deliberate output change, review them with `cargo insta review`
+//! or accept them all with `INSTA_UPDATE=always cargo test`
+
+mod common;
+
+use std::io::{Cursor, Write};
+
+use common::Fixtures;
+use insta::assert_snapshot;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+#[test]
+fn print() {
+    let fixtures = Fixtures::new("print");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "dice.png"],
+        &["print", "rgb.png", "--no-data"],
+        &["print", "secret.png", "--type", "ruSt", "--hex"],
+        &["print", "rgb.png", "--ancillary-only", "--no-data"]
+    ]));
+}
+
+#[test]
+fn encode_and_decode() {
+    let fixtures = Fixtures::new("encode_and_decode");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", "Hello world", "encoded.png", "--position", "after-ihdr"],
+        &["decode", "encoded.png", "ruSt"],
+        &["decode", "encoded.png", "ruSt", "--raw"],
+        &["decode", "rgb.png", "ruSt"],
+        &["encode", "rgb.png", "IDAT", "Hello world", "encoded.png"]
+    ]));
+}
+
+#[test]
+fn compression() {
+    let fixtures = Fixtures::new("compression");
+    let message = r#"{"type": "message", "user": {"username": "ferris", "email": "ferris@example.com"}}"#;
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", message, "encoded.png", "--compress", "zstd", "--dict", "builtin-text"],
+        &["decode", "encoded.png", "ruSt", "--raw"],
+        &["decode", "encoded.png", "ruSt"],
+        &["decode", "encoded.png", "ruSt", "--format", "json"],
+        &["print", "encoded.png", "--type", "ruSt", "--hex"],
+        &["encode", "rgb.png", "ruSt", message, "encoded.png", "--dict", "builtin-text"]
+    ]));
+}
+
+#[test]
+fn delta() {
+    let fixtures = Fixtures::new("delta");
+    let config = r#"{"server": {"host": "localhost", "port": 8080, "workers": 4}, "log": {"level": "info"}}"#;
+    let updated = r#"{"server": {"host": "localhost", "port": 9090, "workers": 4}, "log": {"level": "debug"}}"#;
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", config, "--delta"],
+        &["encode", "rgb.png", "ruSt", updated, "--delta"],
+        &["print", "rgb.png", "--type", "ruSt", "--hex"],
+        &["decode", "rgb.png", "ruSt", "--raw"],
+        &["decode", "rgb.png", "ruSt", "--format", "json"],
+        &["encode", "rgb.png", "ruSt", config, "--delta", "--mode", "lsb"],
+        &["remove", "rgb.png", "ruSt"],
+        &["decode", "rgb.png", "ruSt"],
+        &["encode", "rgb.png", "ruSt", config, "--delta"],
+        &["decode", "rgb.png", "ruSt", "--raw"]
+    ]));
+}
+
+#[test]
+fn remove() {
+    let fixtures = Fixtures::new("remove");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["remove", "secret.png", "ruSt"],
+        &["remove", "secret.png", "ruSt"],
+        &["print", "secret.png", "--no-data"]
+    ]));
+}
+
+#[test]
+fn assert() {
+    let fixtures = Fixtures::new("assert");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["assert", "secret.png", "ruSt", "--equals", "This is a secret message!"],
+        &["assert", "secret.png", "ruSt", "--matches", "^This"],
+        &["assert", "secret.png", "ruSt", "--equals", "Something else"]
+    ]));
+}
+
+#[test]
+fn assert_decoded() {
+    let fixtures = Fixtures::new("assert_decoded");
+    let message = r#"{"type": "message", "user": {"username": "ferris", "email": "ferris@example.com"}}"#;
+    let updated = r#"{"type": "message", "user": {"username": "ferris", "email": "crab@example.com"}}"#;
+    fixtures.write("key", b"secret key");
+    fixtures.write("other_key", b"another key");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", message, "compressed.png", "--compress", "zstd"],
+        &["assert", "compressed.png", "ruSt", "--equals", message],
+        &["encode", "rgb.png", "ruSt", "Signed message", "signed.png", "--sign", "key"],
+        &["assert", "signed.png", "ruSt", "--equals", "Signed message", "--verify", "key"],
+        &["assert", "signed.png", "ruSt", "--equals", "Signed message", "--verify", "other_key"],
+        &["encode", "rgb.png", "ruSt", message, "--delta"],
+        &["encode", "rgb.png", "ruSt", updated, "--delta"],
+        &["assert", "rgb.png", "ruSt", "--equals", updated],
+        &["assert", "rgb.png", "ruSt", "--matches", "crab@"],
+        &["assert", "rgb.png", "ruSt", "--equals", message]
+    ]));
+}
+
+#[test]
+fn diff() {
+    let fixtures = Fixtures::new("diff");
+
+    let text = common::chunk(b"tEXt", b"Title\0Dice");
+    let image_data = common::chunk(b"IDAT", &common::image_data(2, 2, 3));
+    let end = common::chunk(b"IEND", &[]);
+    fixtures.write(
+        "ordered.png",
+        &common::png(&[common::header(2, 2, 2), text.clone(), image_data.clone(), end.clone()])
+    );
+    fixtures.write(
+        "reordered.png",
+        &common::png(&[common::header(2, 2, 2), common::chunk(b"ruSt", b"Hi"), image_data, text, end])
+    );
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["diff", "rgb.png", "secret.png"],
+        &["diff", "rgb.png", "secret.png", "--ignore-idat"],
+        &["diff", "rgb.png", "rgb.png"],
+        &["diff", "ordered.png", "reordered.png"],
+        &["diff", "ordered.png", "reordered.png", "--side-by-side"],
+        &["diff", "ordered.png", "reordered.png", "--side-by-side", "--format", "csv"]
+    ]));
+}
+
+#[test]
+fn protect() {
+    let fixtures = Fixtures::new("protect");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["protect", "rgb.png", "protected.png", "--chunks", "tEXt,tIME", "--password", "secret"],
+        &["print", "protected.png", "--ancillary-only", "--hex"],
+        &["unprotect", "protected.png", "restored.png", "--password", "guess"],
+        &["unprotect", "protected.png", "restored.png", "--password", "secret"],
+        &["diff", "rgb.png", "restored.png"],
+        &["protect", "rgb.png", "--chunks", "IDAT", "--password", "secret"]
+    ]));
+}
+
+#[test]
+fn metadata() {
+    let fixtures = Fixtures::new("metadata");
+    fixtures.write(
+        "sidecar.json",
+        br#"{ "title": "Dice", "keywords": ["dice", "render"], "dpi": 300, "custom": { "Software": "pngme" } }"#
+    );
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["export-metadata", "rgb.png"],
+        &["export-metadata", "dice.png"],
+        &["import-metadata", "rgb.png", "imported.png", "--from", "sidecar.json"],
+        &["export-metadata", "imported.png"]
+    ]));
+}
+
+#[test]
+fn sync_metadata() {
+    let fixtures = Fixtures::new("sync_metadata");
+    fixtures.write("originals/image.png", &common::rgb_png());
+    fixtures.write("processed/image.png", &common::png(&[
+        common::header(8, 8, 2),
+        common::chunk(b"IDAT", &common::image_data(8, 8, 3)),
+        common::chunk(b"IEND", &[])
+    ]));
+    fixtures.write("processed/orphan.png", &common::secret_png());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["sync-metadata", "--from", "originals", "--to", "processed", "--dry-run"],
+        &["sync-metadata", "--from", "originals", "--to", "processed"],
+        &["sync-metadata", "--from", "originals", "--to", "processed"]
+    ]));
+}
+
+#[test]
+fn quantize() {
+    let fixtures = Fixtures::new("quantize");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["quantize", "secret.png", "quantized.png", "--colors", "4", "--dither"],
+        &["print", "quantized.png", "--type", "PLTE,tRNS", "--hex"]
+    ]));
+}
+
+#[test]
+fn normalize() {
+    let fixtures = Fixtures::new("normalize");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["normalize", "fragmented.png", "normalized.png"],
+        &["print", "normalized.png", "--no-data"],
+        &["normalize", "normalized.png"]
+    ]));
+}
+
+#[test]
+fn validate() {
+    let fixtures = Fixtures::new("validate");
+
+    let mut bad_adler = common::image_data(8, 8, 3);
+    *bad_adler.last_mut().unwrap() ^= 0xFF;
+    let mut comment = b"Comment\0\0".to_vec();
+    comment.extend_from_slice(&common::image_data(1, 1, 1));
+
+    fixtures.write("corrupt.png", &common::png(&[
+        common::header(8, 8, 2),
+        common::chunk(b"zTXt", &comment),
+        common::chunk(b"zTXt", b"Comment\0\x01\x78\x9c"),
+        common::chunk(b"IDAT", &bad_adler),
+        common::chunk(b"IEND", &[])
+    ]));
+    let mut trailing = common::rgb_png();
+    trailing.extend(common::chunk(b"ruSt", b"After the end"));
+    fixtures.write("trailing.png", &trailing);
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["validate", "rgb.png"],
+        &["validate", "corrupt.png"],
+        &["validate", "corrupt.png", "--format", "json"],
+        &["validate", "corrupt.png", "--strict", "--allow", "corrupt-method"],
+        &["validate", "corrupt.png", "--ignore", "PNGME012", "--ignore", "corrupt-adler32"],
+        &["validate", "trailing.png"],
+        &["--strict", "validate", "trailing.png"],
+        &["--strict", "--allow", "trailing-data", "validate", "trailing.png"]
+    ]));
+}
+
+#[test]
+fn repair() {
+    let fixtures = Fixtures::new("repair");
+
+    let mut damaged = common::rgb_png();
+    damaged[1..4].copy_from_slice(b"JPG");
+    fixtures.write("damaged.png", &damaged);
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["repair", "damaged.png", "repaired.png"],
+        &["diff", "rgb.png", "repaired.png"],
+        &["repair", "rgb.png"]
+    ]));
+}
+
+#[test]
+fn rename_by() {
+    let fixtures = Fixtures::new("rename_by");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["rename-by", ".", "--pattern", "{keyword:Title}_{date}.png", "--dry-run"],
+        &["rename-by", ".", "--pattern", "{chunk:ruSt}.png", "--dry-run", "--on-collision", "skip"]
+    ]));
+}
+
+#[test]
+fn pick_carrier() {
+    let fixtures = Fixtures::new("pick_carrier");
+    fixtures.write("oversized.png", &common::oversized_png());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["pick-carrier", ".", "--payload-size", "16"],
+        &["pick-carrier", ".", "--payload-size", "1MiB"]
+    ]));
+}
+
+#[test]
+fn invalid_chunk_types() {
+    let fixtures = Fixtures::new("invalid_chunk_types");
+    let mut corrupted = common::secret_png();
+    let end = corrupted.len() - 12;
+    corrupted.splice(end..end, common::chunk(&[0x89, 0x50, 0x4E, 0x47], b"Overwritten"));
+    fixtures.write("corrupted.png", &corrupted);
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["decode", "corrupted.png", "ruSt"],
+        &["print", "corrupted.png", "--no-data"],
+        &["remove", "corrupted.png", "0x89504E47"],
+        &["decode", "corrupted.png", "ruSt"]
+    ]));
+}
+
+#[test]
+fn signed() {
+    let fixtures = Fixtures::new("signed");
+    fixtures.write("key", b"secret key");
+    fixtures.write("other_key", b"another key");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", "Signed message", "signed.png", "--sign", "key"],
+        &["decode", "signed.png", "ruSt", "--verify", "key"],
+        &["decode", "signed.png", "ruSt", "--verify", "key", "--format", "json"],
+        &["decode", "signed.png", "ruSt", "--verify", "other_key"],
+        &["encode", "secret.png", "ruSt", "Hidden", "pixels.png", "--sign", "key", "--mode", "lsb"],
+        &["decode", "pixels.png", "ruSt", "--verify", "key", "--mode", "lsb"],
+        &["decode", "pixels.png", "ruSt", "--verify", "other_key", "--mode", "lsb"]
+    ]));
+}
+
+#[test]
+fn mismatched_files() {
+    let fixtures = Fixtures::new("mismatched_files");
+    fixtures.write("text.png", b"This is not a PNG");
+    fixtures.write("disguised.dat", &common::secret_png());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["pick-carrier", ".", "--payload-size", "16"],
+        &["pick-carrier", ".", "--payload-size", "16", "--by-content"]
+    ]));
+}
+
+#[cfg(unix)]
+#[test]
+fn special_files() {
+    let fixtures = Fixtures::new("special_files");
+    std::os::unix::fs::symlink("secret.png", fixtures.path("link.png")).unwrap();
+
+    let fifo = std::process::Command::new("mkfifo").arg(fixtures.path("pipe.png")).status().unwrap();
+    assert!(fifo.success());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["pick-carrier", ".", "--payload-size", "16"],
+        &["pick-carrier", ".", "--payload-size", "16", "--follow-symlinks", "--one-file-system"]
+    ]));
+}
+
+#[test]
+fn events() {
+    let fixtures = Fixtures::new("events");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "rgb.png", "--no-data", "--events", "stderr"],
+        &["decode", "missing.png", "ruSt", "--events", "stdout"]
+    ]));
+}
+
+#[test]
+fn frames() {
+    let fixtures = Fixtures::new("frames");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["frames", "import", "rgb.png", "rgb.png", "-o", "animated.png", "--delay", "100,250", "--plays", "2"],
+        &["print", "animated.png", "--no-data"],
+        &["frames", "export", "animated.png", "-o", "frames"],
+        &["frames", "export", "animated.png", "-o", "frames", "--format", "json"],
+        &["frames", "import", "--manifest", "frames/manifest.json", "-o", "reimported.png"],
+        &["diff", "animated.png", "reimported.png"],
+        &["frames", "export", "rgb.png", "-o", "frames"],
+        &["frames", "import", "rgb.png", "dice.png", "-o", "animated.png"]
+    ]));
+}
+
+#[test]
+fn demo() {
+    let fixtures = Fixtures::new("demo");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["demo", "--directory", "demo"],
+        &["decode", "demo/secret.png", "ruSt"]
+    ]));
+}
+
+#[test]
+fn formats() {
+    let fixtures = Fixtures::new("formats");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "secret.png", "--no-data", "--format", "json"],
+        &["diff", "rgb.png", "secret.png", "--format", "csv"],
+        &["diff", "rgb.png", "rgb.png", "--format", "json"],
+        &["normalize", "fragmented.png", "normalized.png", "--format", "quiet"],
+        &["print", "rgb.png", "--format", "yaml"]
+    ]));
+}
+
+#[test]
+fn strict() {
+    let fixtures = Fixtures::new("strict");
+    let mut trailing = common::rgb_png();
+    trailing.extend(common::chunk(b"ruSt", b"After the end"));
+    fixtures.write("trailing.png", &trailing);
+    fixtures.write("broken.png", &[&common::SIGNATURE[..], b"Not a chunk"].concat());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "trailing.png", "ruSt", "Hello", "encoded.png"],
+        &["encode", "trailing.png", "ruSt", "Hello", "encoded.png", "--strict"],
+        &["encode", "trailing.png", "ruSt", "Hello", "encoded.png", "--strict", "--allow", "trailing-data"],
+        &["pick-carrier", ".", "--payload-size", "16", "--strict"],
+        &["pick-carrier", ".", "--payload-size", "16", "--strict", "--allow", "skipped-file"],
+        &["pick-carrier", ".", "--payload-size", "1MiB", "--strict", "--ignore", "PNGME004"],
+        &["pick-carrier", ".", "--payload-size", "1MiB", "--events", "stdout", "--format", "quiet"],
+        &["pick-carrier", ".", "--payload-size", "16", "--format", "json"],
+        &["pick-carrier", ".", "--payload-size", "16", "--format", "json", "--strict"]
+    ]));
+}
+
+#[test]
+fn control_characters() {
+    let fixtures = Fixtures::new("control_characters");
+    fixtures.write("evil.png", &common::png(&[
+        common::header(1, 1, 0),
+        common::chunk(b"IDAT", &common::image_data(1, 1, 1)),
+        common::chunk(b"tEXt", b"Title\0\x1b[2J\x1b]0;pwned\x07\rInnocent"),
+        common::chunk(b"IEND", &[])
+    ]));
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "evil.png", "--type", "tEXt"],
+        &["decode", "evil.png", "tEXt", "--format", "json"]
+    ]));
+}
+
+#[test]
+fn repair_adler() {
+    let fixtures = Fixtures::new("repair_adler");
+
+    let mut image_data = common::image_data(8, 8, 3);
+    *image_data.last_mut().unwrap() ^= 0xFF;
+    fixtures.write("bad_adler.png", &common::png(&[
+        common::header(8, 8, 2),
+        common::chunk(b"IDAT", &image_data),
+        common::chunk(b"IEND", &[])
+    ]));
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["repair", "bad_adler.png", "--fix-adler"],
+        &["validate", "bad_adler.png"],
+        &["repair", "bad_adler.png", "--fix-adler"]
+    ]));
+}
+
+#[test]
+fn salvage() {
+    let fixtures = Fixtures::new("salvage");
+
+    let fragmented = common::fragmented_png();
+    fixtures.write("partial.png", &fragmented[..fragmented.len() * 2 / 3]);
+    fixtures.write("oversized.png", &common::oversized_png());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "partial.png"],
+        &["salvage", "partial.png", "salvaged.png"],
+        &["salvage", "partial.png", "padded.png", "--pad-image-data"],
+        &["validate", "padded.png"],
+        &["salvage", "rgb.png"],
+        &["salvage", "oversized.png", "padded.png", "--pad-image-data"]
+    ]));
+}
+
+#[test]
+fn recover() {
+    let fixtures = Fixtures::new("recover");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", "Survives a re-save", "carrier.png", "--par2-out", "recovery.par2"],
+        &["remove", "carrier.png", "ruSt"],
+        &["decode", "carrier.png", "ruSt"],
+        &["recover", "carrier.png", "recovery.par2", "recovered.png"],
+        &["decode", "recovered.png", "ruSt"],
+        &["recover", "recovered.png", "recovery.par2"],
+        &["recover", "recovered.png", "rgb.png"]
+    ]));
+}
+
+#[test]
+fn seal() {
+    let fixtures = Fixtures::new("seal");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "rgb.png", "ruSt", "Sealed", "sealed.png", "--seal"],
+        &["normalize", "sealed.png", "--max-size", "64"],
+        &["decode", "sealed.png", "ruSt"],
+        &["quantize", "sealed.png", "--colors", "4"],
+        &["decode", "sealed.png", "ruSt"],
+        &["decode", "sealed.png", "ruSt", "--strict"],
+        &["encode", "rgb.png", "ruSt", "Sealed", "lsb.png", "--seal", "--mode", "lsb"]
+    ]));
+}
+
+#[test]
+fn archives() {
+    let fixtures = Fixtures::new("archives");
+
+    let mut document = ZipWriter::new(Cursor::new(Vec::new()));
+    document.start_file("word/document.xml", SimpleFileOptions::default()).unwrap();
+    document.write_all(b"<w:document/>").unwrap();
+    document.start_file("word/media/image1.png", SimpleFileOptions::default()).unwrap();
+    document.write_all(&common::secret_png()).unwrap();
+    fixtures.write("report.docx", &document.finish().unwrap().into_inner());
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "report.docx!word/media/image1.png", "--no-data"],
+        &["decode", "report.docx", "ruSt", "--zip-member", "word/media/image1.png"],
+        &["print", "report.docx!word/media/image2.png"],
+        &["remove", "report.docx!word/media/image1.png", "ruSt"],
+        &["encode", "report.docx!word/media/image1.png", "ruSt", "Extracted", "extracted.png", "--replace"],
+        &["decode", "extracted.png", "ruSt"]
+    ]));
+}
+
+#[test]
+fn web_safe_profile() {
+    let fixtures = Fixtures::new("web_safe_profile");
+
+    let mut chunks = vec![common::header(8, 8, 0), common::chunk(b"gAMA", &[0, 0, 177, 143])];
+    chunks.extend(common::image_data(8, 8, 1).chunks(4).map(|part| common::chunk(b"IDAT", part)));
+    chunks.push(common::chunk(b"IEND", &[]));
+    chunks.push(common::chunk(b"tEXt", b"Comment\0After IEND"));
+    fixtures.write("picky.png", &common::png(&chunks));
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["encode", "picky.png", "ruSt", "Hello", "web_safe.png", "--position", "after-ihdr", "--profile", "web-safe"],
+        &["print", "web_safe.png", "--no-data"],
+        &["decode", "web_safe.png", "ruSt"],
+        &["remove", "picky.png", "gAMA", "--profile", "web"],
+        &["encode", "picky.png", "ruST", "Hello", "unsafe.png", "--profile", "web-safe"],
+        &["encode", "picky.png", "RUST", "Hello", "critical.png", "--profile", "web-safe", "--force"],
+        &["decode", "critical.png", "RUST", "--raw"]
+    ]));
+}
+
+#[test]
+fn locale() {
+    let fixtures = Fixtures::new("locale");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "rgb.png", "--type", "tIME", "--locale", "de_DE.UTF-8"],
+        &["print", "rgb.png", "--type", "tIME", "--locale", "en-US", "--format", "json"],
+        &["pick-carrier", ".", "--payload-size", "1MiB", "--locale", "en_GB"],
+        &["print", "rgb.png", "--locale", "tlh"]
+    ]));
+}
+
+#[test]
+fn capabilities() {
+    let fixtures = Fixtures::new("capabilities");
+
+    assert_snapshot!(fixtures.run_all(&[&["capabilities"], &["capabilities", "--json"]]));
+}
+
+#[test]
+fn errors() {
+    let fixtures = Fixtures::new("errors");
+    fixtures.write("text.png", b"This is not a PNG");
+
+    assert_snapshot!(fixtures.run_all(&[
+        &["print", "missing.png"],
+        &["print", "text.png"],
+        &["encode", "rgb.png", "ruSt"],
+        &["encode", "rgb.png", "ruSt", "message", "--position", "middle"],
+        &["encode", "rgb.png", "ruSt", "message", "--position", "index:0"]
+    ]));
+}
@@ -0,0 +1,185 @@
+//! Generator of the PNG fixtures the CLI tests run on, and a runner capturing pngme's output
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Standard PNG signature
+pub const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// Chunk as bytes: length, type, data and CRC
+pub fn chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let checked: Vec<u8> = chunk_type.iter().chain(data.iter()).copied().collect();
+
+    (data.len() as u32)
+        .to_be_bytes()
+        .iter()
+        .chain(checked.iter())
+        .chain(crc::crc32::checksum_ieee(&checked).to_be_bytes().iter())
+        .copied()
+        .collect()
+}
+
+/// PNG made of the signature followed by the given chunks
+pub fn png(chunks: &[Vec<u8>]) -> Vec<u8> {
+    SIGNATURE.iter().chain(chunks.iter().flatten()).copied().collect()
+}
+
+/// IHDR chunk of a non-interlaced 8 bit image
+pub fn header(width: u32, height: u32, color_type: u8) -> Vec<u8> {
+    let data: Vec<u8> = width
+        .to_be_bytes()
+        .iter()
+        .chain(height.to_be_bytes().iter())
+        .chain([8, color_type, 0, 0, 0].iter())
+        .copied()
+        .collect();
+
+    chunk(b"IHDR", &data)
+}
+
+/// Compressed data of an 8 bit image with the given number of channels, every sample following a fixed pattern
+pub fn image_data(width: u32, height: u32, channels: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    for y in 0..height {
+        // Every scanline is unfiltered
+        data.push(0);
+
+        for x in 0..width * channels {
+            data.push((x * 31 + y * 17 + (x * y) % 7 * 11) as u8);
+        }
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Truecolor image with metadata
+pub fn rgb_png() -> Vec<u8> {
+    png(&[
+        header(8, 8, 2),
+        chunk(b"pHYs", &[0, 0, 0x0B, 0x13, 0, 0, 0x0B, 0x13, 1]),
+        chunk(b"IDAT", &image_data(8, 8, 3)),
+        chunk(b"tEXt", b"Title\0Gradient"),
+        chunk(b"tEXt", b"Author\0Jane Doe"),
+        chunk(b"tIME", &[0x07, 0xE5, 3, 9, 14, 5, 30]),
+        chunk(b"IEND", &[])
+    ])
+}
+
+/// Truecolor image with alpha and a hidden message
+pub fn secret_png() -> Vec<u8> {
+    png(&[
+        header(16, 16, 6),
+        chunk(b"IDAT", &image_data(16, 16, 4)),
+        chunk(b"ruSt", b"This is a secret message!"),
+        chunk(b"IEND", &[])
+    ])
+}
+
+/// Grayscale image whose image data is split into tiny IDAT chunks
+pub fn fragmented_png() -> Vec<u8> {
+    let mut chunks = vec![header(8, 8, 0)];
+    chunks.extend(image_data(8, 8, 1).chunks(4).map(|part| chunk(b"IDAT", part)));
+    chunks.push(chunk(b"IEND", &[]));
+
+    png(&chunks)
+}
+
+/// Image whose header claims far more pixels than any image data could hold
+pub fn oversized_png() -> Vec<u8> {
+    png(&[
+        header(0x7FFF_FFFF, 0x7FFF_FFFF, 6),
+        chunk(b"IDAT", &image_data(4, 4, 4)),
+        chunk(b"IEND", &[])
+    ])
+}
+
+/// Directory holding every fixture, removed when dropped
+pub struct Fixtures {
+    directory: PathBuf
+}
+
+impl Fixtures {
+    /// Write the fixtures to a directory of their own for the given test
+    pub fn new(test: &str) -> Self {
+        let directory = env::temp_dir().join(format!("pngme_cli_{}_{}", test, std::process::id()));
+
+        if directory.exists() {
+            fs::remove_dir_all(&directory).unwrap();
+        }
+        fs::create_dir_all(&directory).unwrap();
+
+        let fixtures = Fixtures { directory };
+
+        fixtures.write("dice.png", &fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("dice.png")).unwrap());
+        fixtures.write("rgb.png", &rgb_png());
+        fixtures.write("secret.png", &secret_png());
+        fixtures.write("fragmented.png", &fragmented_png());
+
+        fixtures
+    }
+
+    /// Path of a file in the fixtures directory
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.directory.join(name)
+    }
+
+    /// Write a file in the fixtures directory, creating its parent directories
+    pub fn write(&self, name: &str, bytes: &[u8]) {
+        let path = self.path(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+
+    /// Run pngme in the fixtures directory, returning the command line, exit code, stdout and stderr
+    ///
+    /// File paths are given relative to the fixtures directory so the output doesn't depend on where it is
+    pub fn run(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+            .args(args)
+            .current_dir(&self.directory)
+            .env("RUST_BACKTRACE", "0")
+            // Output is otherwise free to depend on the machine and the run, such as numbers following its locale
+            .env("PNGME_DETERMINISTIC", "1")
+            .output()
+            .unwrap();
+
+        format!(
+            "$ pngme {}\nexit code: {}\n--- stdout\n{}--- stderr\n{}",
+            args.iter().map(|arg| quote(arg)).collect::<Vec<String>>().join(" "),
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )
+    }
+
+    /// Run several pngme commands in a row, joining their output
+    pub fn run_all(&self, commands: &[&[&str]]) -> String {
+        commands
+            .iter()
+            .map(|args| self.run(args))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Argument as typed in a shell, quoted when it holds characters the shell would interpret
+fn quote(arg: &str) -> String {
+    match arg.contains(|c: char| c.is_whitespace() || c == '{' || c == '!') {
+        true => format!("'{}'", arg),
+        false => arg.to_string()
+    }
+}
+
+impl Drop for Fixtures {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
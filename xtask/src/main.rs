@@ -0,0 +1,214 @@
+//! Development tasks of pngme, run with `cargo xtask <task>`
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+/// Holds a `Result` of any kind of error
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Archive of the PngSuite conformance images
+const PNGSUITE_URL: &str = "http://www.schaik.com/pngsuite/PngSuite-2017jul19.tgz";
+
+const USAGE: &str = "Usage: cargo xtask <task>
+
+Tasks:
+    pngsuite [--archive <path>]    Download the PngSuite images, or extract a local copy of the archive,
+                                   and check pngme prints each one with the exit code of xtask/pngsuite.manifest";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("pngsuite") => pngsuite(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = result {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
+/// Check pngme against every PngSuite image
+fn pngsuite(args: &[String]) -> Result<()> {
+    let archive = match args {
+        [] => None,
+        [flag, path] if flag == "--archive" => Some(PathBuf::from(path)),
+        _ => return Err(USAGE.into())
+    };
+
+    let root = workspace_root();
+    let directory = root.join("target").join("pngsuite");
+    let manifest = Manifest::parse(&fs::read_to_string(root.join("xtask").join("pngsuite.manifest"))?)?;
+
+    fetch(archive.as_deref(), &directory)?;
+    let pngme = build_pngme(&root)?;
+
+    let mut images: Vec<PathBuf> = fs::read_dir(&directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::result::Result<Vec<PathBuf>, _>>()?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .collect();
+    images.sort();
+
+    let mut failures = Vec::new();
+
+    for image in &images {
+        let name = image.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let expected = manifest.expected_exit_code(&name);
+
+        let code = Command::new(&pngme)
+            .arg("print")
+            .arg(image)
+            .arg("--no-data")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?
+            .code()
+            .unwrap_or(-1);
+
+        if code != expected {
+            failures.push(format!("{}: expected exit code {}, got {}", name, expected, code));
+        }
+    }
+
+    // Entries of images missing from the suite are most likely typos
+    for name in manifest.names() {
+        if !images.iter().any(|image| image.file_name().is_some_and(|file_name| file_name == name)) {
+            failures.push(format!("{}: listed in the manifest but not in the suite", name));
+        }
+    }
+
+    for failure in &failures {
+        println!("{}", failure);
+    }
+
+    println!("{} images checked, {} unexpected outcomes", images.len(), failures.len());
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err("PngSuite check failed".into())
+    }
+}
+
+/// Directory holding the workspace's Cargo.toml
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in the workspace directory")
+        .to_path_buf()
+}
+
+/// Extract the PngSuite archive into the directory, downloading it first unless a local copy is given
+///
+/// Images already extracted are reused
+fn fetch(archive: Option<&Path>, directory: &Path) -> Result<()> {
+    if archive.is_none() && directory.is_dir() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(directory)?;
+
+    let archive = match archive {
+        Some(archive) => archive.to_path_buf(),
+        None => {
+            let archive = directory.join("PngSuite.tgz");
+            println!("Downloading {}", PNGSUITE_URL);
+            run(Command::new("curl").arg("-fsSL").arg("-o").arg(&archive).arg(PNGSUITE_URL))?;
+            archive
+        }
+    };
+
+    run(Command::new("tar").arg("-xzf").arg(&archive).arg("-C").arg(directory))
+}
+
+/// Build pngme, returning the path of its binary
+fn build_pngme(root: &Path) -> Result<PathBuf> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    run(Command::new(cargo).args(["build", "--quiet", "--bin", "pngme"]).current_dir(root))?;
+
+    Ok(root.join("target").join("debug").join(format!("pngme{}", env::consts::EXE_SUFFIX)))
+}
+
+/// Run a command to completion, failing if it does
+fn run(command: &mut Command) -> Result<()> {
+    let status = command.status()?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{:?} failed with {}", command, status).into())
+    }
+}
+
+/// Expected exit codes of the PngSuite images that aren't valid
+#[derive(Debug, PartialEq)]
+struct Manifest {
+    entries: Vec<(String, i32)>
+}
+
+impl Manifest {
+    /// Parse lines of image name, exit code and defect, skipping comments and blank lines
+    fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+
+            match (fields.next(), fields.next().map(str::parse::<i32>)) {
+                (Some(name), Some(Ok(code))) => entries.push((name.to_string(), code)),
+                _ => return Err(format!("Invalid manifest line {}: {}", number + 1, line).into())
+            }
+        }
+
+        Ok(Manifest { entries })
+    }
+
+    /// Exit code expected for an image, 0 for images missing from the manifest
+    fn expected_exit_code(&self, name: &str) -> i32 {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map_or(0, |(_, code)| *code)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse("# Comment\n\nxs1n0g01.png  3  signature byte 1\nxc1n0g08.png 0\n").unwrap();
+
+        assert_eq!(manifest.expected_exit_code("xs1n0g01.png"), 3);
+        assert_eq!(manifest.expected_exit_code("xc1n0g08.png"), 0);
+        assert_eq!(manifest.expected_exit_code("basn0g01.png"), 0);
+        assert_eq!(manifest.names().collect::<Vec<&str>>(), vec!["xs1n0g01.png", "xc1n0g08.png"]);
+
+        assert!(Manifest::parse("xs1n0g01.png three").is_err());
+    }
+
+    #[test]
+    fn test_bundled_manifest() {
+        let manifest = Manifest::parse(include_str!("../pngsuite.manifest")).unwrap();
+
+        assert_eq!(manifest.expected_exit_code("xhdn0g08.png"), 4);
+    }
+}